@@ -4,6 +4,8 @@ pub use ::migrations::{
 };
 
 pub mod schema;
+#[cfg(any(test, feature = "test-helpers"))]
+pub mod test_helpers;
 
 #[derive(thiserror::Error, Debug)]
 pub enum DatabaseError {
@@ -11,6 +11,7 @@ pub struct Account {
     pub username: String,
     pub administrator: bool,
     pub display_name: Option<String>,
+    pub email: Option<String>,
     pub password_hash: String,
     pub created_at: DateTime<Utc>,
 }
@@ -23,6 +24,10 @@ pub enum AccountError {
     UsernameInvalidCharacter(char),
     #[error("username already taken")]
     UsernameConflict,
+    #[error("invalid email address")]
+    InvalidEmail,
+    #[error("invalid time zone")]
+    InvalidTimezone,
     #[error("sql error: {0}")]
     Sql(#[from] sqlx::Error),
 }
@@ -39,6 +44,7 @@ impl Account {
             administrator,
             password_hash: Default::default(),
             display_name: None,
+            email: None,
             created_at: Utc::now(),
         };
         account.set_password_hash(&password)?;
@@ -62,32 +68,62 @@ impl Account {
         id: i64,
         executor: E,
     ) -> sqlx::Result<Account> {
-        sqlx::query_as!(Self, "SELECT id, username, administrator, password_hash, display_name, created_at FROM accounts WHERE id = $1", id).fetch_one(executor).await
+        sqlx::query_as!(Self, "SELECT id, username, administrator, password_hash, display_name, email, created_at FROM accounts WHERE id = $1", id).fetch_one(executor).await
     }
 
     pub async fn load_for_update<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
         id: i64,
         executor: E,
     ) -> sqlx::Result<Account> {
-        sqlx::query_as!(Self, "SELECT id, username, administrator, password_hash, display_name, created_at FROM accounts WHERE id = $1 FOR UPDATE", id).fetch_one(executor).await
+        sqlx::query_as!(Self, "SELECT id, username, administrator, password_hash, display_name, email, created_at FROM accounts WHERE id = $1 FOR UPDATE", id).fetch_one(executor).await
     }
 
     pub async fn find_by_username<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
         username: &str,
         executor: E,
     ) -> sqlx::Result<Account> {
-        sqlx::query_as!(Self, "SELECT id, username, administrator, password_hash, display_name, created_at FROM accounts WHERE username = $1", username).fetch_one(executor).await
+        sqlx::query_as!(Self, "SELECT id, username, administrator, password_hash, display_name, email, created_at FROM accounts WHERE username = $1", username).fetch_one(executor).await
+    }
+
+    /// Looks up the ids of the accounts with any of `usernames`. Usernames
+    /// that don't exist are omitted.
+    pub async fn ids_by_username<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        usernames: &[String],
+        executor: E,
+    ) -> sqlx::Result<Vec<(String, i64)>> {
+        let rows = sqlx::query!(
+            "SELECT id, username FROM accounts WHERE username = ANY($1)",
+            usernames
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(rows.into_iter().map(|row| (row.username, row.id)).collect())
     }
 
     pub async fn find_by_session_id<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
         session_id: Uuid,
         executor: E,
     ) -> sqlx::Result<Account> {
-        sqlx::query_as!(Self, "SELECT id, username, administrator, password_hash, display_name, created_at FROM accounts WHERE id = validate_session($1)", session_id)
+        sqlx::query_as!(Self, "SELECT id, username, administrator, password_hash, display_name, email, created_at FROM accounts WHERE id = validate_session($1)", session_id)
             .fetch_one(executor)
             .await
     }
 
+    pub async fn update_email<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        id: i64,
+        email: Option<&str>,
+        executor: E,
+    ) -> Result<(), AccountError> {
+        let email = match email {
+            Some(email) => Account::clean_email(email)?,
+            None => None,
+        };
+        sqlx::query!("UPDATE accounts SET email = $2 WHERE id = $1", id, email)
+            .execute(executor)
+            .await?;
+        Ok(())
+    }
+
     pub fn set_password_hash(&mut self, new_password: &str) -> anyhow::Result<()> {
         self.password_hash = bcrypt::hash(new_password.as_bytes(), bcrypt::DEFAULT_COST)?;
 
@@ -105,11 +141,12 @@ impl Account {
         self.username = Account::clean_username(&self.username)?;
         if self.id == 0 {
             let row = sqlx::query!(
-            "INSERT INTO accounts (username, password_hash, display_name, administrator) VALUES ($1, $2, $3, $4) RETURNING id, created_at",
+            "INSERT INTO accounts (username, password_hash, display_name, administrator, email) VALUES ($1, $2, $3, $4, $5) RETURNING id, created_at",
             &self.username,
             &self.password_hash,
             self.display_name.as_ref(),
             self.administrator,
+            self.email.as_ref(),
         )
         .fetch_one(executor)
         .await?;
@@ -117,12 +154,13 @@ impl Account {
             self.created_at = row.created_at;
         } else {
             sqlx::query!(
-                "UPDATE accounts SET username = $2, password_hash = $3, display_name = $4, administrator = $5 WHERE id = $1", 
+                "UPDATE accounts SET username = $2, password_hash = $3, display_name = $4, administrator = $5, email = $6 WHERE id = $1", 
                 self.id,
                 &self.username,
                 &self.password_hash,
                 self.display_name.as_ref(),
                 self.administrator,
+                self.email.as_ref(),
             ).execute(executor).await?;
         }
 
@@ -150,6 +188,29 @@ impl Account {
 
         Ok(username)
     }
+
+    /// Trims an email address, returning None if it's empty. This only
+    /// rejects addresses that are obviously malformed; deliverability is
+    /// up to the mail server.
+    pub fn clean_email(email: &str) -> Result<Option<String>, AccountError> {
+        let email = email.trim();
+        if email.is_empty() {
+            return Ok(None);
+        }
+
+        let mut parts = email.splitn(2, '@');
+        match (parts.next(), parts.next()) {
+            (Some(local), Some(domain))
+                if !local.is_empty()
+                    && !domain.is_empty()
+                    && !domain.contains('@')
+                    && !email.chars().any(char::is_whitespace) =>
+            {
+                Ok(Some(email.to_string()))
+            }
+            _ => Err(AccountError::InvalidEmail),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -173,6 +234,43 @@ impl User {
         .await
     }
 
+    /// Finds the user whose email address matches `email`, ignoring case
+    pub async fn find_by_email<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        email: &str,
+        executor: E,
+    ) -> sqlx::Result<Option<User>> {
+        sqlx::query_as!(
+            User,
+            "SELECT id, username, display_name FROM accounts WHERE lower(email) = lower($1) ORDER BY id LIMIT 1",
+            email
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Finds users whose username starts with `query` or whose display name contains it
+    pub async fn search<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        query: &str,
+        limit: i64,
+        executor: E,
+    ) -> sqlx::Result<Vec<User>> {
+        let escaped = query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        sqlx::query_as!(
+            User,
+            r#"SELECT id, username, display_name FROM accounts
+               WHERE username ILIKE $1 || '%' OR display_name ILIKE '%' || $1 || '%'
+               ORDER BY username
+               LIMIT $2"#,
+            escaped,
+            limit,
+        )
+        .fetch_all(executor)
+        .await
+    }
+
     pub async fn save(
         &mut self,
         executor: &mut Transaction<'_, sqlx::Postgres>,
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+
+use crate::sqlx;
+
+#[derive(Debug, Clone)]
+pub struct UserAvatar {
+    pub user_id: i64,
+    pub username: String,
+    pub avatar_key: Option<String>,
+    pub avatar_updated_at: Option<DateTime<Utc>>,
+}
+
+impl UserAvatar {
+    pub async fn load<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        user_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Self,
+            "SELECT id as user_id, username, avatar_key, avatar_updated_at FROM accounts WHERE id = $1",
+            user_id
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    /// Replaces the user's avatar, returning the previous avatar's storage key
+    pub async fn replace<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        user_id: i64,
+        avatar_key: Option<&str>,
+        executor: E,
+    ) -> sqlx::Result<Option<String>> {
+        let row = sqlx::query!(
+            r#"UPDATE accounts SET avatar_key = $2, avatar_updated_at = now()
+               FROM (SELECT avatar_key FROM accounts WHERE id = $1 FOR UPDATE) previous
+               WHERE id = $1
+               RETURNING previous.avatar_key as "previous_key?""#,
+            user_id,
+            avatar_key,
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(row.previous_key)
+    }
+}
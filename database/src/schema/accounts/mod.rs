@@ -1,4 +1,5 @@
 mod account;
+mod avatar;
 mod session;
 
-pub use self::{account::*, session::*};
+pub use self::{account::*, avatar::*, session::*};
@@ -0,0 +1,268 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx::{self, Done, FromRow};
+
+use crate::{DatabaseError, SqlxResultExt};
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: i64,
+    pub author_id: i64,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    pub storage_key: String,
+    pub thumbnail_key: Option<String>,
+    pub issue_id: Option<i64>,
+    pub issue_revision_id: Option<i64>,
+    pub article_slug: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Attachment {
+    pub fn new(
+        author_id: i64,
+        filename: String,
+        content_type: String,
+        size: i64,
+        storage_key: String,
+    ) -> Self {
+        Self {
+            author_id,
+            filename,
+            content_type,
+            size,
+            storage_key,
+            id: 0,
+            thumbnail_key: None,
+            issue_id: None,
+            issue_revision_id: None,
+            article_slug: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn permalink(&self) -> String {
+        format!("/attachment/{}/file/{}", self.id, self.filename)
+    }
+
+    /// Returns true if the attachment hasn't been associated with any content yet
+    pub fn is_orphaned(&self) -> bool {
+        self.issue_id.is_none() && self.issue_revision_id.is_none() && self.article_slug.is_none()
+    }
+
+    pub async fn load<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        attachment_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                id,
+                author_id,
+                filename,
+                content_type,
+                size,
+                storage_key,
+                thumbnail_key,
+                issue_id,
+                issue_revision_id,
+                article_slug,
+                created_at
+               FROM attachments WHERE id = $1"#,
+            attachment_id
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    /// Lists all attachments for an issue, including attachments added to comments on the issue
+    pub async fn list_for_issue<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                attachments.id,
+                attachments.author_id,
+                filename,
+                content_type,
+                size,
+                storage_key,
+                thumbnail_key,
+                attachments.issue_id,
+                issue_revision_id,
+                article_slug,
+                attachments.created_at
+               FROM attachments
+               LEFT OUTER JOIN issue_revisions ON issue_revisions.id = attachments.issue_revision_id
+               WHERE attachments.issue_id = $1 OR issue_revisions.issue_id = $1
+               ORDER BY attachments.created_at"#,
+            issue_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn list_for_article<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        article_slug: &str,
+        executor: E,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                id,
+                author_id,
+                filename,
+                content_type,
+                size,
+                storage_key,
+                thumbnail_key,
+                issue_id,
+                issue_revision_id,
+                article_slug,
+                created_at
+               FROM attachments WHERE article_slug = $1
+               ORDER BY created_at"#,
+            article_slug
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn save<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        &mut self,
+        executor: E,
+    ) -> Result<(), DatabaseError> {
+        if self.id == 0 {
+            let row = sqlx::query!(
+                r#"INSERT INTO attachments (
+                    author_id,
+                    filename,
+                    content_type,
+                    size,
+                    storage_key,
+                    thumbnail_key,
+                    issue_id,
+                    issue_revision_id,
+                    article_slug
+                   ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id, created_at"#,
+                self.author_id,
+                &self.filename,
+                &self.content_type,
+                self.size,
+                &self.storage_key,
+                self.thumbnail_key.as_ref(),
+                self.issue_id,
+                self.issue_revision_id,
+                self.article_slug.as_ref(),
+            )
+            .fetch_one(executor)
+            .await
+            .map_database_error()?;
+
+            self.id = row.id;
+            self.created_at = row.created_at;
+        } else {
+            let result = sqlx::query!(
+                r#"UPDATE attachments SET
+                    filename = $2,
+                    thumbnail_key = $3,
+                    issue_id = $4,
+                    issue_revision_id = $5,
+                    article_slug = $6
+                   WHERE id = $1"#,
+                self.id,
+                &self.filename,
+                self.thumbnail_key.as_ref(),
+                self.issue_id,
+                self.issue_revision_id,
+                self.article_slug.as_ref(),
+            )
+            .execute(executor)
+            .await
+            .map_database_error()?;
+            if result.rows_affected() == 0 {
+                return Err(DatabaseError::RowNotFound);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Associates orphaned attachments uploaded by `author_id` with an issue or a comment on an issue.
+    /// Attachments that are already associated with something else are left alone.
+    pub async fn claim<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        attachment_ids: &[i64],
+        author_id: i64,
+        issue_id: Option<i64>,
+        issue_revision_id: Option<i64>,
+        executor: E,
+    ) -> sqlx::Result<u64> {
+        if attachment_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let result = sqlx::query!(
+            r#"UPDATE attachments SET
+                issue_id = $3,
+                issue_revision_id = $4
+               WHERE id = ANY($1)
+                AND author_id = $2
+                AND issue_id IS NULL
+                AND issue_revision_id IS NULL
+                AND article_slug IS NULL"#,
+            attachment_ids,
+            author_id,
+            issue_id,
+            issue_revision_id,
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Lists attachments that were never associated with anything and were uploaded before `created_before`
+    pub async fn list_orphaned<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        created_before: DateTime<Utc>,
+        executor: E,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                id,
+                author_id,
+                filename,
+                content_type,
+                size,
+                storage_key,
+                thumbnail_key,
+                issue_id,
+                issue_revision_id,
+                article_slug,
+                created_at
+               FROM attachments
+               WHERE issue_id IS NULL
+                AND issue_revision_id IS NULL
+                AND article_slug IS NULL
+                AND created_at < $1"#,
+            created_before
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn delete<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        attachment_id: i64,
+        executor: E,
+    ) -> sqlx::Result<u64> {
+        let result = sqlx::query!("DELETE FROM attachments WHERE id = $1", attachment_id)
+            .execute(executor)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -1,3 +1,5 @@
+use migrations::sqlx::{self, Transaction};
+
 #[derive(Debug)]
 pub struct Article {
     pub id: i64,
@@ -13,4 +15,89 @@ impl Article {
             body: body.to_owned(),
         }
     }
+
+    /// The slug an article is now found at, if it used to be found at `slug`
+    pub async fn current_slug<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        slug: &str,
+        executor: E,
+    ) -> sqlx::Result<Option<String>> {
+        let row = sqlx::query!(
+            "SELECT current_slug FROM article_slug_history WHERE slug = $1",
+            slug
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(row.map(|row| row.current_slug))
+    }
+
+    /// Records that an article moved from `old_slug` to `new_slug`, bringing
+    /// its attachments along. Older slugs that led to `old_slug` are pointed
+    /// at `new_slug` too, so every redirect is a single hop.
+    pub async fn record_slug_change(
+        old_slug: &str,
+        new_slug: &str,
+        tx: &mut Transaction<'_, sqlx::Postgres>,
+    ) -> sqlx::Result<()> {
+        sqlx::query!("DELETE FROM article_slug_history WHERE slug = $1", new_slug)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "UPDATE article_slug_history SET current_slug = $2 WHERE current_slug = $1",
+            old_slug,
+            new_slug
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"INSERT INTO article_slug_history (slug, current_slug) VALUES ($1, $2)
+               ON CONFLICT (slug) DO UPDATE SET current_slug = $2, replaced_at = now()"#,
+            old_slug,
+            new_slug
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE attachments SET article_slug = $2 WHERE article_slug = $1",
+            old_slug,
+            new_slug
+        )
+        .execute(&mut *tx)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers;
+
+    #[tokio::test]
+    #[ignore]
+    async fn renames_redirect_in_one_hop() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        assert_eq!(Article::current_slug("rename-first", &mut tx).await?, None);
+
+        Article::record_slug_change("rename-first", "rename-second", &mut tx).await?;
+        Article::record_slug_change("rename-second", "rename-third", &mut tx).await?;
+        assert_eq!(
+            Article::current_slug("rename-first", &mut tx).await?,
+            Some(String::from("rename-third"))
+        );
+        assert_eq!(
+            Article::current_slug("rename-second", &mut tx).await?,
+            Some(String::from("rename-third"))
+        );
+
+        // Moving back to an old slug takes it out of the history
+        Article::record_slug_change("rename-third", "rename-first", &mut tx).await?;
+        assert_eq!(Article::current_slug("rename-first", &mut tx).await?, None);
+        assert_eq!(
+            Article::current_slug("rename-third", &mut tx).await?,
+            Some(String::from("rename-first"))
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx;
+
+/// A commit whose message referenced an issue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueCommit {
+    pub id: i64,
+    pub issue_id: i64,
+    pub repository: String,
+    pub sha: String,
+    pub message: String,
+    pub url: Option<String>,
+    pub author_name: String,
+    pub author_email: Option<String>,
+    pub author_id: Option<i64>,
+    pub committed_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl IssueCommit {
+    pub fn new(
+        issue_id: i64,
+        repository: String,
+        sha: String,
+        message: String,
+        committed_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            issue_id,
+            repository,
+            sha,
+            message,
+            committed_at,
+            id: 0,
+            url: None,
+            author_name: String::default(),
+            author_email: None,
+            author_id: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Links the commit to its issue, returning false if it was already
+    /// linked. Git hosts redeliver pushes and the same commit is often pushed
+    /// to more than one branch, so linking is idempotent.
+    pub async fn link<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        &mut self,
+        executor: E,
+    ) -> sqlx::Result<bool> {
+        let row = sqlx::query!(
+            r#"INSERT INTO issue_commits (
+                issue_id, repository, sha, message, url, author_name, author_email, author_id, committed_at
+               ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+               ON CONFLICT (issue_id, sha) DO NOTHING
+               RETURNING id, created_at"#,
+            self.issue_id,
+            &self.repository,
+            &self.sha,
+            &self.message,
+            self.url.as_ref(),
+            &self.author_name,
+            self.author_email.as_ref(),
+            self.author_id,
+            self.committed_at,
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        match row {
+            Some(row) => {
+                self.id = row.id;
+                self.created_at = row.created_at;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub async fn list_for_issue<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, issue_id, repository, sha, message, url, author_name, author_email, author_id, committed_at, created_at
+               FROM issue_commits WHERE issue_id = $1
+               ORDER BY created_at"#,
+            issue_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
@@ -0,0 +1,171 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyNode {
+    pub id: i64,
+    pub project_key: Option<String>,
+    pub number: Option<i32>,
+    pub summary: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub blocked: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct DependencyEdge {
+    pub blocker_id: i64,
+    pub blocked_id: i64,
+}
+
+/// An issue along with everything that transitively blocks it. Completed
+/// blockers are included, but what blocked them is not, as it no longer
+/// holds anything up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    pub issue_id: i64,
+    pub nodes: Vec<DependencyNode>,
+    pub edges: Vec<DependencyEdge>,
+}
+
+impl DependencyGraph {
+    pub async fn load(issue_id: i64) -> sqlx::Result<Self> {
+        // UNION rather than UNION ALL discards edges already visited, which
+        // also stops the walk if old data contains a blocking cycle
+        let edges = sqlx::query_as!(
+            DependencyEdge,
+            r#"WITH RECURSIVE blockers(issue_a, issue_b) AS (
+                SELECT issue_a, issue_b FROM issue_relationships
+                WHERE issue_b = $1 AND relationship = 'blocks'
+                UNION
+                SELECT issue_relationships.issue_a, issue_relationships.issue_b
+                FROM issue_relationships
+                INNER JOIN blockers ON issue_relationships.issue_b = blockers.issue_a
+                INNER JOIN issues ON issues.id = blockers.issue_a
+                WHERE issue_relationships.relationship = 'blocks' AND issues.completed_at IS NULL
+               )
+               SELECT issue_a as "blocker_id!", issue_b as "blocked_id!" FROM blockers
+               ORDER BY issue_b, issue_a"#,
+            issue_id,
+        )
+        .fetch_all(crate::pool())
+        .await?;
+
+        let mut ids = vec![issue_id];
+        ids.extend(edges.iter().map(|edge| edge.blocker_id));
+        let nodes = sqlx::query_as!(
+            DependencyNode,
+            r#"SELECT issues.id, projects.key as "project_key?", number, summary, started_at, completed_at, blocked
+               FROM issues LEFT OUTER JOIN projects ON projects.id = issues.project_id
+               WHERE issues.id = ANY($1) ORDER BY issues.id"#,
+            &ids,
+        )
+        .fetch_all(crate::pool())
+        .await?;
+
+        Ok(Self {
+            issue_id,
+            nodes,
+            edges,
+        })
+    }
+
+    /// The unfinished issues holding this one up, nearest first
+    pub fn unfinished_blockers(&self) -> Vec<&DependencyNode> {
+        let mut blockers: Vec<&DependencyNode> = Vec::new();
+        let mut frontier = vec![self.issue_id];
+        while !frontier.is_empty() {
+            let mut next = Vec::new();
+            for edge in &self.edges {
+                if frontier.contains(&edge.blocked_id) && !next.contains(&edge.blocker_id) {
+                    next.push(edge.blocker_id);
+                }
+            }
+            frontier.clear();
+            for id in next {
+                if let Some(node) = self.node(id) {
+                    if node.completed_at.is_none()
+                        && node.id != self.issue_id
+                        && !blockers.iter().any(|blocker| blocker.id == id)
+                    {
+                        blockers.push(node);
+                        frontier.push(id);
+                    }
+                }
+            }
+        }
+        blockers
+    }
+
+    pub fn node(&self, issue_id: i64) -> Option<&DependencyNode> {
+        self.nodes.iter().find(|node| node.id == issue_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unfinished_blockers_are_nearest_first() {
+        let release = DependencyNode {
+            id: 1,
+            project_key: None,
+            number: None,
+            summary: String::from("Release"),
+            started_at: None,
+            completed_at: None,
+            blocked: true,
+        };
+        let graph = DependencyGraph {
+            issue_id: 1,
+            nodes: vec![
+                DependencyNode {
+                    id: 2,
+                    summary: String::from("Fix the installer"),
+                    ..release.clone()
+                },
+                DependencyNode {
+                    id: 3,
+                    summary: String::from("Write the changelog"),
+                    completed_at: Some(Utc::now()),
+                    blocked: false,
+                    ..release.clone()
+                },
+                DependencyNode {
+                    id: 4,
+                    summary: String::from("Sign the installer"),
+                    ..release.clone()
+                },
+                release,
+            ],
+            edges: vec![
+                DependencyEdge {
+                    blocker_id: 2,
+                    blocked_id: 1,
+                },
+                DependencyEdge {
+                    blocker_id: 3,
+                    blocked_id: 1,
+                },
+                DependencyEdge {
+                    blocker_id: 4,
+                    blocked_id: 2,
+                },
+                DependencyEdge {
+                    blocker_id: 1,
+                    blocked_id: 4,
+                },
+            ],
+        };
+
+        let ids = graph
+            .unfinished_blockers()
+            .into_iter()
+            .map(|node| node.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![2, 4]);
+    }
+}
@@ -1,11 +1,14 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use migrations::sqlx::{self, postgres::PgRow, FromRow, Row, Transaction};
 
-use crate::schema::accounts::User;
+use crate::schema::{accounts::User, issues::Resolution};
+
+/// Advisory lock key serializing changes to issue parents
+const PARENT_CHANGE_LOCK: i64 = 0x6973_7375_6573;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IssueView {
@@ -15,12 +18,22 @@ pub struct IssueView {
     pub description: Option<String>,
     pub project_slug: Option<String>,
     pub project_name: Option<String>,
+    pub project_key: Option<String>,
+    /// The issue's number within its project
+    pub number: Option<i32>,
     pub parent_id: Option<i64>,
     pub blocked: bool,
+    pub workflow_state_id: Option<i64>,
+    pub estimate_hours: Option<i32>,
+    pub milestone_id: Option<i64>,
+    pub milestone_name: Option<String>,
+    pub iteration_id: Option<i64>,
+    pub iteration_name: Option<String>,
     pub current_revision_id: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
+    pub resolution: Option<Resolution>,
     pub tag_ids: Vec<i32>,
 }
 
@@ -36,16 +49,27 @@ impl IssueView {
                 issues.description,
                 projects.slug as "project_slug?",
                 projects.name as "project_name?",
+                projects.key as "project_key?",
+                number,
                 parent_id, 
                 blocked,
+                workflow_state_id,
+                estimate_hours,
+                milestone_id,
+                milestones.name as "milestone_name?",
+                iteration_id,
+                iterations.name as "iteration_name?",
                 current_revision_id, 
                 issues.created_at, 
                 started_at,
                 completed_at,
+                resolution as "resolution: Resolution",
                 array(SELECT issue_tags.tag_id FROM issue_tags WHERE issue_id = issues.id) as tag_ids 
                FROM issues
                INNER JOIN accounts ON issues.author_id = accounts.id 
-               LEFT OUTER JOIN projects ON projects.id = project_id
+               LEFT OUTER JOIN projects ON projects.id = issues.project_id
+               LEFT OUTER JOIN milestones ON milestones.id = milestone_id
+               LEFT OUTER JOIN iterations ON iterations.id = iteration_id
                WHERE issues.id = $1"#,
             issue_id
         )
@@ -63,12 +87,21 @@ impl IssueView {
             description: row.description,
             project_slug: row.project_slug,
             project_name: row.project_name,
+            project_key: row.project_key,
+            number: row.number,
             parent_id: row.parent_id,
             blocked: row.blocked,
+            workflow_state_id: row.workflow_state_id,
+            estimate_hours: row.estimate_hours,
+            milestone_id: row.milestone_id,
+            milestone_name: row.milestone_name,
+            iteration_id: row.iteration_id,
+            iteration_name: row.iteration_name,
             current_revision_id: row.current_revision_id,
             created_at: row.created_at,
             started_at: row.started_at,
             completed_at: row.completed_at,
+            resolution: row.resolution,
             tag_ids: row.tag_ids.unwrap_or_default(),
         })
     }
@@ -82,11 +115,18 @@ pub struct Issue {
     pub description: Option<String>,
     pub project_id: Option<i64>,
     pub parent_id: Option<i64>,
+    /// Orders an issue among its siblings
+    pub rank: i32,
     pub current_revision_id: Option<i64>,
     pub blocked: bool,
+    pub workflow_state_id: Option<i64>,
+    pub estimate_hours: Option<i32>,
+    pub milestone_id: Option<i64>,
+    pub iteration_id: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
+    pub resolution: Option<Resolution>,
 }
 
 impl Issue {
@@ -102,27 +142,36 @@ impl Issue {
             summary,
             description,
             parent_id,
+            rank: 0,
             id: 0,
             project_id,
             blocked: false,
+            workflow_state_id: None,
+            estimate_hours: None,
+            milestone_id: None,
+            iteration_id: None,
             current_revision_id: None,
             created_at: Utc::now(),
             completed_at: None,
             started_at: None,
+            resolution: None,
         }
     }
 
     pub async fn load(issue_id: i64) -> sqlx::Result<Self> {
-        sqlx::query_as!(Issue, "SELECT id, author_id, project_id, summary, description, parent_id, blocked, current_revision_id, created_at, started_at, completed_at FROM issues WHERE id = $1", issue_id).fetch_one(crate::pool()).await
+        sqlx::query_as!(Issue, r#"SELECT id, author_id, project_id, summary, description, parent_id, rank, blocked, workflow_state_id, estimate_hours, milestone_id, iteration_id, current_revision_id, created_at, started_at, completed_at, resolution as "resolution: Resolution" FROM issues WHERE id = $1"#, issue_id).fetch_one(crate::pool()).await
     }
 
     pub async fn load_for_update(
         issue_id: i64,
         transaction: &mut Transaction<'_, sqlx::Postgres>,
     ) -> sqlx::Result<Self> {
-        sqlx::query_as!(Issue, "SELECT id, author_id, project_id, summary, description, parent_id, blocked, current_revision_id, created_at, started_at, completed_at FROM issues WHERE id = $1 FOR UPDATE", issue_id).fetch_one(transaction).await
+        sqlx::query_as!(Issue, r#"SELECT id, author_id, project_id, summary, description, parent_id, rank, blocked, workflow_state_id, estimate_hours, milestone_id, iteration_id, current_revision_id, created_at, started_at, completed_at, resolution as "resolution: Resolution" FROM issues WHERE id = $1 FOR UPDATE"#, issue_id).fetch_one(transaction).await
     }
 
+    /// Saves the issue. `blocked` is maintained by database triggers as
+    /// blockers are completed, reopened, linked and unlinked, so it's never
+    /// written, only read back.
     pub async fn save<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
         &mut self,
         executor: E,
@@ -135,67 +184,228 @@ impl Issue {
                     summary, 
                     description, 
                     parent_id,
-                    blocked,
                     current_revision_id,
                     started_at,
-                    completed_at
-                   ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id, created_at"#,
+                    completed_at,
+                    workflow_state_id,
+                    resolution,
+                    estimate_hours,
+                    milestone_id,
+                    iteration_id,
+                    rank
+                   ) VALUES (
+                    $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13,
+                    (SELECT COALESCE(MAX(rank) + 1, 0) FROM issues WHERE parent_id = $5)
+                   ) RETURNING id, created_at, blocked, rank"#,
                 self.author_id,
                 self.project_id,
                 &self.summary,
                 self.description.as_ref(),
                 self.parent_id,
-                self.blocked,
                 self.current_revision_id,
                 self.started_at,
                 self.completed_at,
+                self.workflow_state_id,
+                self.resolution.map(|r| r as i32),
+                self.estimate_hours,
+                self.milestone_id,
+                self.iteration_id,
             )
             .fetch_one(executor)
             .await?;
 
             self.id = row.id;
             self.created_at = row.created_at;
+            self.blocked = row.blocked;
+            self.rank = row.rank;
         } else {
-            sqlx::query!(
+            let row = sqlx::query!(
                 r#"UPDATE issues SET 
                     author_id = $1,
                     summary = $2,
                     description = $3,
                     project_id = $4,
                     parent_id = $5,
-                    blocked = $6,
-                    current_revision_id = $7,
-                    started_at = $8,
-                    completed_at = $9
-                   WHERE id = $10"#,
+                    current_revision_id = $6,
+                    started_at = $7,
+                    completed_at = $8,
+                    workflow_state_id = $10,
+                    resolution = $11,
+                    estimate_hours = $12,
+                    rank = $13,
+                    milestone_id = $14,
+                    iteration_id = $15
+                   WHERE id = $9
+                   RETURNING blocked"#,
                 self.author_id,
                 &self.summary,
                 self.description.as_ref(),
                 self.project_id,
                 self.parent_id,
-                self.blocked,
                 self.current_revision_id,
                 self.started_at,
                 self.completed_at,
                 self.id,
+                self.workflow_state_id,
+                self.resolution.map(|r| r as i32),
+                self.estimate_hours,
+                self.rank,
+                self.milestone_id,
+                self.iteration_id,
             )
-            .execute(executor)
+            .fetch_one(executor)
             .await?;
+
+            self.blocked = row.blocked;
         }
 
         Ok(())
     }
 
+    /// Serializes changes to issue parents until `tx` ends, as two concurrent
+    /// moves could each pass `would_create_parent_cycle` and form a cycle
+    /// together. Take it before locking any issues so that moves can't
+    /// deadlock on each other's rows.
+    pub async fn lock_hierarchy(tx: &mut Transaction<'_, sqlx::Postgres>) -> sqlx::Result<()> {
+        // `query!` can't describe the `void` this returns
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(PARENT_CHANGE_LOCK)
+            .execute(tx)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether making `parent_id` the parent of `issue_id` would make the
+    /// issue its own ancestor. Only reliable while holding `lock_hierarchy`.
+    pub async fn would_create_parent_cycle<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_id: i64,
+        parent_id: i64,
+        executor: E,
+    ) -> sqlx::Result<bool> {
+        let row = sqlx::query!(
+            r#"WITH RECURSIVE ancestors(id, path) AS (
+                SELECT $2::BIGINT, ARRAY[$2::BIGINT]
+                UNION ALL
+                SELECT issues.parent_id, ancestors.path || issues.parent_id
+                FROM issues INNER JOIN ancestors ON issues.id = ancestors.id
+                WHERE issues.parent_id IS NOT NULL AND NOT issues.parent_id = ANY(ancestors.path)
+               )
+               SELECT EXISTS(SELECT 1 FROM ancestors WHERE id = $1) as "cycle!""#,
+            issue_id,
+            parent_id,
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(row.cycle)
+    }
+
+    /// Finds an issue by a project key and number, such as `GOOEY-42`.
+    /// Numbers an issue had in projects it has since moved from still match,
+    /// as do keys its project has since changed from.
+    pub async fn find_by_key<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        key: &str,
+        number: i32,
+        executor: E,
+    ) -> sqlx::Result<i64> {
+        let row = sqlx::query!(
+            r#"SELECT issue_numbers.issue_id FROM issue_numbers
+               WHERE issue_numbers.number = $2 AND issue_numbers.project_id = COALESCE(
+                   (SELECT id FROM projects WHERE key = $1),
+                   (SELECT project_id FROM project_key_history WHERE key = $1)
+               )"#,
+            key,
+            number,
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(row.issue_id)
+    }
+
+    /// Every issue beneath `issue_id`, however deeply nested
+    pub async fn descendant_ids<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Vec<i64>> {
+        let rows = sqlx::query!(
+            r#"WITH RECURSIVE subtree(id, path) AS (
+                SELECT id, ARRAY[$1::BIGINT, id] FROM issues WHERE parent_id = $1
+                UNION ALL
+                SELECT issues.id, subtree.path || issues.id
+                FROM issues INNER JOIN subtree ON issues.parent_id = subtree.id
+                WHERE NOT issues.id = ANY(subtree.path)
+               )
+               SELECT id as "id!" FROM subtree ORDER BY id"#,
+            issue_id,
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.id).collect())
+    }
+
+    /// Every issue's parent, as `(issue_id, parent_id)` pairs
+    pub async fn parent_links<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        executor: E,
+    ) -> sqlx::Result<Vec<(i64, i64)>> {
+        let rows = sqlx::query!(
+            r#"SELECT id, parent_id as "parent_id!" FROM issues WHERE parent_id IS NOT NULL"#
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.id, row.parent_id))
+            .collect())
+    }
+
+    /// The children of an issue, in order
+    pub async fn child_ids<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        parent_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Vec<i64>> {
+        let rows = sqlx::query!(
+            "SELECT id FROM issues WHERE parent_id = $1 ORDER BY rank, id",
+            parent_id
+        )
+        .fetch_all(executor)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.id).collect())
+    }
+
+    /// Ranks issues in the order given
+    pub async fn set_ranks<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_ids: &[i64],
+        executor: E,
+    ) -> sqlx::Result<()> {
+        let ranks = (0..issue_ids.len() as i32).collect::<Vec<_>>();
+        sqlx::query!(
+            r#"UPDATE issues SET rank = ranked.rank
+               FROM UNNEST($1::BIGINT[], $2::INTEGER[]) as ranked(id, rank)
+               WHERE issues.id = ranked.id AND issues.rank <> ranked.rank"#,
+            issue_ids,
+            &ranks,
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn all_parents(issue_id: i64) -> sqlx::Result<Vec<Issue>> {
         let mut issues = HashMap::new();
         for issue in sqlx::query_as!(
             Issue,
             r#"WITH RECURSIVE issue_hierarchy AS(
-                SELECT * FROM issues WHERE id = $1
+                SELECT issues.*, ARRAY[issues.id] AS path FROM issues WHERE id = $1
                 UNION ALL
-                SELECT parent.* FROM issues parent JOIN issue_hierarchy ON parent.id = issue_hierarchy.parent_id
+                SELECT parent.*, issue_hierarchy.path || parent.id FROM issues parent JOIN issue_hierarchy ON parent.id = issue_hierarchy.parent_id
+                WHERE NOT parent.id = ANY(issue_hierarchy.path)
             )
-            SELECT id as "id!", author_id as "author_id!", project_id, summary as "summary!", description, parent_id, blocked as "blocked!", current_revision_id, created_at as "created_at!", started_at, completed_at FROM issue_hierarchy"#,
+            SELECT id as "id!", author_id as "author_id!", project_id, summary as "summary!", description, parent_id, rank as "rank!", blocked as "blocked!", workflow_state_id, estimate_hours, milestone_id, iteration_id, current_revision_id, created_at as "created_at!", started_at, completed_at, resolution as "resolution: Resolution" FROM issue_hierarchy"#,
             issue_id,
         ).fetch_all(crate::pool()).await? {
             issues.insert(issue.id, issue);
@@ -219,72 +429,6 @@ impl Issue {
         Ok(ordered_issues)
     }
 
-    pub async fn update_blocked_status(
-        &mut self,
-        tx: &mut Transaction<'_, sqlx::Postgres>,
-    ) -> sqlx::Result<()> {
-        self.blocked = match Self::update_blocked_status_for_id(self.id, tx).await {
-            Ok(blocked) => blocked,
-            Err(sqlx::Error::RowNotFound) => return Ok(()),
-            Err(other) => return Err(other),
-        };
-
-        Ok(())
-    }
-
-    pub async fn update_blocked_status_for_id(
-        id: i64,
-        tx: &mut Transaction<'_, sqlx::Postgres>,
-    ) -> sqlx::Result<bool> {
-        sqlx::query!(
-            "UPDATE issues SET blocked = issue_blocked_statuses.blocked
-            FROM issue_blocked_statuses
-            WHERE issues.id = issue_blocked_statuses.id AND issues.id = $1 AND issues.blocked <> issue_blocked_statuses.blocked
-            RETURNING issues.blocked",
-            id
-        )
-            .fetch_one(tx)
-            .await
-            .map(|row| row.blocked)
-    }
-
-    pub async fn update_blocked_relationships<'a, 'e>(
-        issue_ids: &'a [i64],
-        mut tx: Transaction<'e, sqlx::Postgres>,
-    ) -> sqlx::Result<Transaction<'e, sqlx::Postgres>> {
-        let mut already_updated = HashSet::new();
-        let mut issues_to_update = issue_ids.to_vec();
-        while let Some(issue_id) = issues_to_update.pop() {
-            if already_updated.contains(&issue_id) {
-                continue;
-            }
-            already_updated.insert(issue_id);
-
-            sqlx::query!("UPDATE issues SET blocked = issue_blocked_statuses.blocked FROM issue_blocked_statuses WHERE issue_blocked_statuses.id = issues.id").execute(&mut tx).await?;
-
-            for row in sqlx::query!(
-                r#"SELECT 
-                    blocked.id,
-                    blocked.blocked
-                   FROM issue_relationships 
-                   INNER JOIN issues blocked ON blocked.id = issue_relationships.issue_b
-                   WHERE issue_a = $1 AND relationship = 1"#,
-                issue_id,
-            )
-            .fetch_all(&mut tx)
-            .await?
-            {
-                match Self::update_blocked_status_for_id(row.id, &mut tx).await {
-                    Ok(_) => issues_to_update.push(row.id),
-                    Err(sqlx::Error::RowNotFound) => {}
-                    Err(other) => return Err(other),
-                };
-            }
-        }
-
-        Ok(tx)
-    }
-
     pub async fn remove_tag<'e, E>(&self, tag_id: i32, executor: E) -> sqlx::Result<()>
     where
         E: sqlx::Executor<'e, Database = sqlx::Postgres>,
@@ -319,6 +463,7 @@ pub enum IssueOrderingField {
     Id,
     Creation,
     Completion,
+    Rank,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -335,6 +480,7 @@ impl IssueOrdering {
             IssueOrderingField::Id => format!("id {}", order),
             IssueOrderingField::Creation => format!("created_at {}", order),
             IssueOrderingField::Completion => format!("completed_at {0}, created_at {0}", order),
+            IssueOrderingField::Rank => format!("rank {0}, id {0}", order),
         }
     }
 }
@@ -391,6 +537,7 @@ pub struct IssueResult {
     pub created_at: DateTime<Utc>,
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
+    pub resolution: Option<Resolution>,
     pub tag_ids: Vec<i32>,
 }
 
@@ -461,6 +608,7 @@ impl IssueQueryBuilder {
                 issues.created_at, 
                 started_at,
                 completed_at,
+                resolution,
                 array(SELECT issue_tags.tag_id FROM issue_tags WHERE issue_id = issues.id) as tag_ids,
                 count(*) OVER() as total_count
             FROM issues 
@@ -487,6 +635,7 @@ impl IssueQueryBuilder {
                         created_at: row.get("created_at"),
                         started_at: row.get("started_at"),
                         completed_at: row.get("completed_at"),
+                        resolution: row.get("resolution"),
                         tag_ids: row.get("tag_ids"),
                     },
                 )
@@ -5,12 +5,40 @@ use serde::{export::Formatter, Deserialize, Serialize};
 
 use migrations::sqlx::{self, Done};
 
-#[derive(Clone, Copy, Debug, sqlx::Type, Serialize, Deserialize)]
-#[repr(i32)]
+/// Stored as the `relationship_kind` enum. Issue A of a relationship is the
+/// subject, so A blocks B, A duplicates B and A was split from B.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, sqlx::Type, Serialize, Deserialize,
+)]
+#[sqlx(rename = "relationship_kind", rename_all = "snake_case")]
 pub enum Relationship {
-    Blocks = 1,
+    Blocks,
+    #[sqlx(rename = "precedes")]
     Preceeds,
     Causes,
+    Duplicates,
+    RelatesTo,
+    SplitFrom,
+}
+
+impl Relationship {
+    /// Whether chains of this relationship must not loop back on themselves.
+    /// An issue in a blocking cycle could never be closed, and a cycle of
+    /// issues preceding each other has no order to follow.
+    pub fn is_acyclic(&self) -> bool {
+        matches!(self, Relationship::Blocks | Relationship::Preceeds)
+    }
+
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Relationship::Blocks => "blocks",
+            Relationship::Preceeds => "precedes",
+            Relationship::Causes => "causes",
+            Relationship::Duplicates => "duplicates",
+            Relationship::RelatesTo => "relates_to",
+            Relationship::SplitFrom => "split_from",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,11 +54,18 @@ pub struct IssueRelationship {
     pub created_at: DateTime<Utc>,
 }
 
+/// The issues related to an issue in the same way
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationshipGroup {
+    pub relationship: ContextualizedRelationship,
+    pub issues: Vec<IssueRelationship>,
+}
+
 impl IssueRelationship {
     pub async fn link<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>, S: ToString>(
         issue_a: i64,
         issue_b: i64,
-        relationship: Option<Relationship>,
+        relationship: Relationship,
         comment: Option<S>,
         executor: E,
     ) -> sqlx::Result<()> {
@@ -40,11 +75,11 @@ impl IssueRelationship {
                 issue_b,
                 relationship,
                 comment
-               ) VALUES ($1, $2, $3, $4)
-               ON CONFLICT (issue_a, issue_b) DO UPDATE SET relationship = $3, comment = $4"#,
+               ) VALUES ($1, $2, $3::text::relationship_kind, $4)
+               ON CONFLICT (issue_a, issue_b) DO UPDATE SET relationship = $3::text::relationship_kind, comment = $4"#,
             issue_a,
             issue_b,
-            relationship.map(|r| r as i32),
+            relationship.as_sql(),
             comment.map(|s| s.to_string()),
         )
         .execute(executor)
@@ -53,6 +88,44 @@ impl IssueRelationship {
         Ok(())
     }
 
+    /// Finds a chain of `relationship` links leading from `issue_b` back to
+    /// `issue_a`, which linking `issue_a` to `issue_b` would close into a
+    /// cycle. The shortest such cycle is returned, starting and ending with
+    /// `issue_a`.
+    pub async fn find_cycle<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_a: i64,
+        issue_b: i64,
+        relationship: Relationship,
+        executor: E,
+    ) -> sqlx::Result<Option<Vec<i64>>> {
+        let row = sqlx::query!(
+            r#"WITH RECURSIVE reachable(issue_id, path) AS (
+                SELECT $2::BIGINT, ARRAY[$2::BIGINT]
+                UNION ALL
+                SELECT issue_relationships.issue_b, reachable.path || issue_relationships.issue_b
+                FROM issue_relationships
+                INNER JOIN reachable ON issue_relationships.issue_a = reachable.issue_id
+                WHERE issue_relationships.relationship = $3::text::relationship_kind
+                  AND NOT issue_relationships.issue_b = ANY(reachable.path)
+               )
+               SELECT path as "path!" FROM reachable
+               WHERE issue_id = $1
+               ORDER BY array_length(path, 1)
+               LIMIT 1"#,
+            issue_a,
+            issue_b,
+            relationship.as_sql(),
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(row.map(|row| {
+            let mut cycle = vec![issue_a];
+            cycle.extend(row.path);
+            cycle
+        }))
+    }
+
     pub async fn unlink<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
         issue_a: i64,
         issue_b: i64,
@@ -69,10 +142,11 @@ impl IssueRelationship {
         Ok(result.rows_affected())
     }
 
+    /// Lists the issues related to `issue_id`, grouped by how they're related
     pub async fn list_for<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
         issue_id: i64,
         executor: E,
-    ) -> sqlx::Result<Vec<Self>> {
+    ) -> sqlx::Result<Vec<RelationshipGroup>> {
         // This query is complicated due to its use of CASE WHEN as a ternary. To only return the
         // relevant data from the database, we check on each issue_ field whether the queried issue
         // is the "a" side. If so, then the related issue being returned is the "b" issue. Otherwise,
@@ -92,29 +166,29 @@ impl IssueRelationship {
                FROM issue_relationships
                INNER JOIN issues issue_a ON issue_a.id = issue_relationships.issue_a
                INNER JOIN issues issue_b ON issue_b.id = issue_relationships.issue_b
-               WHERE issue_relationships.issue_a = $1 OR issue_relationships.issue_b = $1"#,
+               WHERE issue_relationships.issue_a = $1 OR issue_relationships.issue_b = $1
+               ORDER BY issue_relationships.created_at"#,
             issue_id,
         )
         .fetch_all(executor)
         .await?;
 
-        Ok(rows
-            .into_iter()
-            .map(|row| Self {
-                relationship: row
-                    .relationship
-                    .map(|r| ContextualizedRelationship::new(r, row.inverse_relationship))
-                    .unwrap_or_else(ContextualizedRelationship::plain),
-                issue_id: row.issue_id,
-                issue_summary: row.issue_summary,
-                issue_started_at: row.issue_started_at,
-                issue_completed_at: row.issue_completed_at,
-                issue_project_id: row.issue_project_id,
-                issue_blocked: row.issue_blocked,
-                comment: row.comment,
-                created_at: row.created_at,
-            })
-            .collect())
+        let relationships = rows.into_iter().map(|row| Self {
+            relationship: ContextualizedRelationship::new(
+                row.relationship,
+                row.inverse_relationship,
+            ),
+            issue_id: row.issue_id,
+            issue_summary: row.issue_summary,
+            issue_started_at: row.issue_started_at,
+            issue_completed_at: row.issue_completed_at,
+            issue_project_id: row.issue_project_id,
+            issue_blocked: row.issue_blocked,
+            comment: row.comment,
+            created_at: row.created_at,
+        });
+
+        Ok(group_relationships(relationships))
     }
 
     pub async fn find<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
@@ -146,10 +220,10 @@ impl IssueRelationship {
             .await?;
 
         Ok(Self {
-            relationship: row
-                .relationship
-                .map(|r| ContextualizedRelationship::new(r, row.inverse_relationship))
-                .unwrap_or_else(ContextualizedRelationship::plain),
+            relationship: ContextualizedRelationship::new(
+                row.relationship,
+                row.inverse_relationship,
+            ),
             issue_id: row.issue_id,
             issue_summary: row.issue_summary,
             issue_started_at: row.issue_started_at,
@@ -162,38 +236,65 @@ impl IssueRelationship {
     }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+/// Groups relationships by kind, keeping the order within each group. Groups
+/// are ordered by kind, with each kind's inverse following it.
+fn group_relationships(
+    relationships: impl IntoIterator<Item = IssueRelationship>,
+) -> Vec<RelationshipGroup> {
+    let mut relationships = relationships.into_iter().collect::<Vec<_>>();
+    relationships.sort_by_key(|r| (r.relationship.relationship, r.relationship.is_inverse));
+
+    let mut groups: Vec<RelationshipGroup> = Vec::new();
+    for relationship in relationships {
+        match groups.last_mut() {
+            Some(group) if group.relationship == relationship.relationship => {
+                group.issues.push(relationship)
+            }
+            _ => groups.push(RelationshipGroup {
+                relationship: relationship.relationship,
+                issues: vec![relationship],
+            }),
+        }
+    }
+
+    groups
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct ContextualizedRelationship {
-    pub relationship: Option<Relationship>,
+    pub relationship: Relationship,
     pub is_inverse: bool,
 }
 
 impl ContextualizedRelationship {
+    /// Symmetric relationships read the same from either issue, so they're
+    /// never inverse
     pub fn new(relationship: Relationship, is_inverse: bool) -> Self {
         Self {
-            relationship: Some(relationship),
-            is_inverse,
+            relationship,
+            is_inverse: is_inverse && relationship != Relationship::RelatesTo,
         }
     }
 
     pub fn plain() -> Self {
-        Self {
-            relationship: None,
-            is_inverse: false,
-        }
+        Self::new(Relationship::RelatesTo, false)
     }
 }
 
 impl std::fmt::Display for ContextualizedRelationship {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let string = match (self.relationship, self.is_inverse) {
-            (None, false) | (None, true) => "relates",
-            (Some(Relationship::Blocks), false) => "blocks",
-            (Some(Relationship::Blocks), true) => "blocked",
-            (Some(Relationship::Preceeds), false) => "precedes",
-            (Some(Relationship::Preceeds), true) => "preceded",
-            (Some(Relationship::Causes), false) => "causes",
-            (Some(Relationship::Causes), true) => "caused",
+            (Relationship::RelatesTo, _) => "relates",
+            (Relationship::Blocks, false) => "blocks",
+            (Relationship::Blocks, true) => "blocked",
+            (Relationship::Preceeds, false) => "precedes",
+            (Relationship::Preceeds, true) => "preceded",
+            (Relationship::Causes, false) => "causes",
+            (Relationship::Causes, true) => "caused",
+            (Relationship::Duplicates, false) => "duplicates",
+            (Relationship::Duplicates, true) => "duplicated",
+            (Relationship::SplitFrom, false) => "split-from",
+            (Relationship::SplitFrom, true) => "split-into",
         };
 
         f.write_str(string)
@@ -204,25 +305,233 @@ impl FromStr for ContextualizedRelationship {
     type Err = RelationshipParseError;
 
     fn from_str(relationship: &str) -> Result<Self, Self::Err> {
-        match relationship {
-            "relates" => Ok(ContextualizedRelationship::plain()),
-            "blocks" => Ok(ContextualizedRelationship::new(Relationship::Blocks, false)),
-            "blocked" => Ok(ContextualizedRelationship::new(Relationship::Blocks, true)),
-            "precedes" => Ok(ContextualizedRelationship::new(
-                Relationship::Preceeds,
-                false,
-            )),
-            "preceded" => Ok(ContextualizedRelationship::new(
-                Relationship::Preceeds,
-                true,
-            )),
-            "causes" => Ok(ContextualizedRelationship::new(Relationship::Causes, false)),
-            "caused" => Ok(ContextualizedRelationship::new(Relationship::Causes, true)),
-            _ => Err(RelationshipParseError),
-        }
+        let (relationship, is_inverse) = match relationship {
+            "relates" => (Relationship::RelatesTo, false),
+            "blocks" => (Relationship::Blocks, false),
+            "blocked" => (Relationship::Blocks, true),
+            "precedes" => (Relationship::Preceeds, false),
+            "preceded" => (Relationship::Preceeds, true),
+            "causes" => (Relationship::Causes, false),
+            "caused" => (Relationship::Causes, true),
+            "duplicates" => (Relationship::Duplicates, false),
+            "duplicated" => (Relationship::Duplicates, true),
+            "split-from" => (Relationship::SplitFrom, false),
+            "split-into" => (Relationship::SplitFrom, true),
+            _ => return Err(RelationshipParseError),
+        };
+
+        Ok(ContextualizedRelationship::new(relationship, is_inverse))
     }
 }
 
 #[derive(thiserror::Error, Debug)]
 #[error("invalid relationship")]
 pub struct RelationshipParseError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        schema::issues::Issue,
+        sqlx::{Postgres, Transaction},
+        test_helpers,
+    };
+
+    #[test]
+    fn relationships_round_trip_through_strings() {
+        for name in &[
+            "relates",
+            "blocks",
+            "blocked",
+            "precedes",
+            "preceded",
+            "causes",
+            "caused",
+            "duplicates",
+            "duplicated",
+            "split-from",
+            "split-into",
+        ] {
+            let relationship = ContextualizedRelationship::from_str(name).unwrap();
+            assert_eq!(relationship.to_string(), *name);
+        }
+        assert!(ContextualizedRelationship::from_str("follows").is_err());
+    }
+
+    #[test]
+    fn relates_to_is_never_inverse() {
+        assert_eq!(
+            ContextualizedRelationship::new(Relationship::RelatesTo, true),
+            ContextualizedRelationship::plain()
+        );
+    }
+
+    fn related(issue_id: i64, relationship: Relationship, is_inverse: bool) -> IssueRelationship {
+        IssueRelationship {
+            issue_id,
+            issue_summary: format!("Issue {}", issue_id),
+            issue_started_at: None,
+            issue_completed_at: None,
+            issue_project_id: None,
+            issue_blocked: false,
+            relationship: ContextualizedRelationship::new(relationship, is_inverse),
+            comment: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn relationships_are_grouped_by_kind() {
+        let groups = group_relationships(vec![
+            related(1, Relationship::RelatesTo, false),
+            related(2, Relationship::Blocks, true),
+            related(3, Relationship::Blocks, false),
+            related(4, Relationship::RelatesTo, true),
+            related(5, Relationship::Blocks, false),
+        ]);
+
+        let summary = groups
+            .iter()
+            .map(|group| {
+                (
+                    group.relationship.to_string(),
+                    group.issues.iter().map(|r| r.issue_id).collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (String::from("blocks"), vec![3, 5]),
+                (String::from("blocked"), vec![2]),
+                (String::from("relates"), vec![1, 4]),
+            ]
+        );
+    }
+
+    /// Creates a blocker and an issue it blocks
+    async fn blocked_pair(tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<(Issue, Issue)> {
+        let account = test_helpers::setup_test_account(tx).await?;
+        let mut blocker = Issue::new(account.id, String::from("Blocker"), None, None, None);
+        blocker.save(&mut *tx).await?;
+        let mut blocked = Issue::new(account.id, String::from("Blocked"), None, None, None);
+        blocked.save(&mut *tx).await?;
+
+        IssueRelationship::link(
+            blocker.id,
+            blocked.id,
+            Relationship::Blocks,
+            None::<String>,
+            &mut *tx,
+        )
+        .await?;
+
+        Ok((blocker, blocked))
+    }
+
+    async fn is_blocked(issue_id: i64, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<bool> {
+        Ok(Issue::load_for_update(issue_id, tx).await?.blocked)
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn every_relationship_round_trips_through_the_database() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        let account = test_helpers::setup_test_account(&mut tx).await?;
+
+        for &relationship in &[
+            Relationship::Blocks,
+            Relationship::Preceeds,
+            Relationship::Causes,
+            Relationship::Duplicates,
+            Relationship::RelatesTo,
+            Relationship::SplitFrom,
+        ] {
+            let mut issue_a = Issue::new(account.id, String::from("A"), None, None, None);
+            issue_a.save(&mut tx).await?;
+            let mut issue_b = Issue::new(account.id, String::from("B"), None, None, None);
+            issue_b.save(&mut tx).await?;
+            IssueRelationship::link(
+                issue_a.id,
+                issue_b.id,
+                relationship,
+                None::<String>,
+                &mut tx,
+            )
+            .await?;
+
+            let found = IssueRelationship::find(issue_a.id, issue_b.id, &mut tx).await?;
+            assert_eq!(found.relationship.relationship, relationship);
+            let groups = IssueRelationship::list_for(issue_b.id, &mut tx).await?;
+            assert_eq!(groups.len(), 1);
+            assert_eq!(groups[0].relationship.relationship, relationship);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn linking_and_unlinking_update_blocked() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        let (blocker, blocked) = blocked_pair(&mut tx).await?;
+        assert!(is_blocked(blocked.id, &mut tx).await?);
+        assert!(!is_blocked(blocker.id, &mut tx).await?);
+
+        IssueRelationship::unlink(blocker.id, blocked.id, &mut tx).await?;
+        assert!(!is_blocked(blocked.id, &mut tx).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn completing_and_reopening_update_blocked() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        let (mut blocker, blocked) = blocked_pair(&mut tx).await?;
+
+        blocker.completed_at = Some(Utc::now());
+        blocker.save(&mut tx).await?;
+        assert!(!is_blocked(blocked.id, &mut tx).await?);
+
+        blocker.completed_at = None;
+        blocker.save(&mut tx).await?;
+        assert!(is_blocked(blocked.id, &mut tx).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn changing_relationship_updates_blocked() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        let (blocker, blocked) = blocked_pair(&mut tx).await?;
+
+        IssueRelationship::link(
+            blocker.id,
+            blocked.id,
+            Relationship::RelatesTo,
+            None::<String>,
+            &mut tx,
+        )
+        .await?;
+        assert!(!is_blocked(blocked.id, &mut tx).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn deleting_blocker_updates_blocked() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        let (blocker, blocked) = blocked_pair(&mut tx).await?;
+
+        sqlx::query("DELETE FROM issues WHERE id = $1")
+            .bind(blocker.id)
+            .execute(&mut tx)
+            .await?;
+        assert!(!is_blocked(blocked.id, &mut tx).await?);
+
+        Ok(())
+    }
+}
@@ -8,7 +8,8 @@ use std::collections::HashMap;
 pub struct IssueRevision {
     pub id: i64,
     pub issue_id: i64,
-    pub author_id: i64,
+    /// `None` for changes made by the tracker itself
+    pub author_id: Option<i64>,
     pub comment: Option<String>,
     pub created_at: DateTime<Utc>,
 }
@@ -19,6 +20,24 @@ impl IssueRevision {
         author_id: i64,
         comment: Option<S>,
         executor: E,
+    ) -> sqlx::Result<Self> {
+        Self::insert(issue_id, Some(author_id), comment, executor).await
+    }
+
+    /// Creates a revision for a change nobody made by hand, such as carrying
+    /// an issue over to the next iteration
+    pub async fn create_automatic<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Self> {
+        Self::insert(issue_id, None, None::<String>, executor).await
+    }
+
+    async fn insert<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>, S: ToString>(
+        issue_id: i64,
+        author_id: Option<i64>,
+        comment: Option<S>,
+        executor: E,
     ) -> sqlx::Result<Self> {
         sqlx::query_as!(
             Self,
@@ -35,6 +54,16 @@ impl IssueRevision {
         .fetch_one(executor)
         .await
     }
+
+    pub async fn load(revision_id: i64) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Self,
+            "SELECT id, issue_id, author_id, comment, created_at FROM issue_revisions WHERE id = $1",
+            revision_id
+        )
+        .fetch_one(crate::pool())
+        .await
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,7 +114,7 @@ impl IssueRevisionChange {
 pub struct IssueRevisionView {
     pub id: i64,
     pub issue_id: i64,
-    pub author: User,
+    pub author: Option<User>,
     pub comment: Option<String>,
     pub created_at: DateTime<Utc>,
     pub changes: HashMap<String, IssueRevisionViewChange>,
@@ -104,9 +133,9 @@ impl IssueRevisionView {
         for row in         sqlx::query!(
             r#"SELECT 
                 issue_revisions.id as id, 
-                accounts.id as author_id, 
-                accounts.display_name as author_display_name, 
-                accounts.username as author_username, 
+                accounts.id as "author_id?", 
+                accounts.display_name as "author_display_name?", 
+                accounts.username as "author_username?", 
                 issue_revisions.comment,
                 issue_revisions.created_at,
                 issue_revision_changes.property as "property?",
@@ -114,7 +143,7 @@ impl IssueRevisionView {
                 issue_revision_changes.new_value
                FROM issue_revisions
                LEFT OUTER JOIN issue_revision_changes ON issue_revision_changes.issue_revision_id = issue_revisions.id
-               INNER JOIN accounts ON accounts.id = issue_revisions.author_id
+               LEFT OUTER JOIN accounts ON accounts.id = issue_revisions.author_id
                WHERE issue_revisions.issue_id = $1
                ORDER BY issue_revisions.created_at, issue_revision_changes.property"#,
             issue_id
@@ -135,10 +164,13 @@ impl IssueRevisionView {
                 revisions.push(Self {
                     id: row.id,
                     issue_id,
-                    author: User {
-                        id: row.author_id,
-                        username: row.author_username,
-                        display_name: row.author_display_name
+                    author: match (row.author_id, row.author_username) {
+                        (Some(id), Some(username)) => Some(User {
+                            id,
+                            username,
+                            display_name: row.author_display_name
+                        }),
+                        _ => None
                     },
                     comment: row.comment,
                     created_at: row.created_at,
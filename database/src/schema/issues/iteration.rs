@@ -0,0 +1,541 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx::{self, FromRow, Transaction};
+
+use crate::{
+    schema::issues::{IssueRevision, IssueRevisionChange},
+    DatabaseError, SqlxResultExt,
+};
+
+/// A time-boxed period of work. Iterations aren't tied to a project, so
+/// issues from any project can be planned into them.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Iteration {
+    pub id: i64,
+    pub name: String,
+    pub starts_on: NaiveDate,
+    /// The last day of the iteration
+    pub ends_on: NaiveDate,
+    /// How many estimated hours of work fit into the iteration
+    pub capacity_hours: Option<i32>,
+    /// When the incomplete issues were carried over to the next iteration
+    pub carried_over_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Iteration {
+    pub fn new(name: String, starts_on: NaiveDate, ends_on: NaiveDate) -> Self {
+        Self {
+            id: 0,
+            name,
+            starts_on,
+            ends_on,
+            capacity_hours: None,
+            carried_over_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub async fn load(iteration_id: i64) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Self,
+            "SELECT id, name, starts_on, ends_on, capacity_hours, carried_over_at, created_at FROM iterations WHERE id = $1",
+            iteration_id
+        )
+        .fetch_one(crate::pool())
+        .await
+    }
+
+    /// Every iteration, most recent first
+    pub async fn list() -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            "SELECT id, name, starts_on, ends_on, capacity_hours, carried_over_at, created_at FROM iterations ORDER BY starts_on DESC, id DESC"
+        )
+        .fetch_all(crate::pool())
+        .await
+    }
+
+    /// Iterations that haven't ended by `today`, for planning issues into
+    pub async fn list_unfinished(today: NaiveDate) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, name, starts_on, ends_on, capacity_hours, carried_over_at, created_at FROM iterations
+               WHERE ends_on >= $1
+               ORDER BY starts_on, id"#,
+            today
+        )
+        .fetch_all(crate::pool())
+        .await
+    }
+
+    /// Iterations that ended before `today` and haven't had their incomplete
+    /// issues carried over yet, oldest first
+    pub async fn list_awaiting_carry_over(today: NaiveDate) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, name, starts_on, ends_on, capacity_hours, carried_over_at, created_at FROM iterations
+               WHERE ends_on < $1 AND carried_over_at IS NULL
+               ORDER BY ends_on, id"#,
+            today
+        )
+        .fetch_all(crate::pool())
+        .await
+    }
+
+    /// Every iteration, for showing the iteration changes in issue timelines
+    pub async fn list_as_map() -> sqlx::Result<HashMap<i64, Self>> {
+        Ok(Self::list()
+            .await?
+            .into_iter()
+            .map(|iteration| (iteration.id, iteration))
+            .collect())
+    }
+
+    /// The iteration that follows this one: the earliest iteration starting
+    /// after this one that hasn't ended by `today`
+    pub async fn next(&self, today: NaiveDate) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, name, starts_on, ends_on, capacity_hours, carried_over_at, created_at FROM iterations
+               WHERE starts_on > $1 AND ends_on >= $2
+               ORDER BY starts_on, id
+               LIMIT 1"#,
+            self.starts_on,
+            today
+        )
+        .fetch_optional(crate::pool())
+        .await
+    }
+
+    pub async fn save<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        &mut self,
+        executor: E,
+    ) -> Result<(), DatabaseError> {
+        if self.id == 0 {
+            let row = sqlx::query!(
+                r#"INSERT INTO iterations (name, starts_on, ends_on, capacity_hours)
+                   VALUES ($1, $2, $3, $4) RETURNING id, created_at"#,
+                &self.name,
+                self.starts_on,
+                self.ends_on,
+                self.capacity_hours,
+            )
+            .fetch_one(executor)
+            .await
+            .map_database_error()?;
+
+            self.id = row.id;
+            self.created_at = row.created_at;
+        } else {
+            sqlx::query!(
+                r#"UPDATE iterations SET name = $2, starts_on = $3, ends_on = $4, capacity_hours = $5
+                   WHERE id = $1 RETURNING id"#,
+                self.id,
+                &self.name,
+                self.starts_on,
+                self.ends_on,
+                self.capacity_hours,
+            )
+            .fetch_one(executor)
+            .await
+            .map_database_error()?;
+        }
+
+        Ok(())
+    }
+
+    /// The number of issues still planned into this iteration that haven't
+    /// been completed
+    pub async fn incomplete_issue_count(&self) -> sqlx::Result<i64> {
+        let row = sqlx::query!(
+            r#"SELECT COUNT(*) as "count!" FROM issues WHERE iteration_id = $1 AND completed_at IS NULL"#,
+            self.id
+        )
+        .fetch_one(crate::pool())
+        .await?;
+        Ok(row.count)
+    }
+
+    /// Moves the incomplete issues into `next_iteration_id` and marks this
+    /// iteration as carried over. Each move is recorded as a revision of the
+    /// issue. The moved issues stay in this iteration's plan, marked with
+    /// where they went, so its report still covers them. Returns the number
+    /// of issues that were moved.
+    pub async fn carry_over(
+        &mut self,
+        next_iteration_id: Option<i64>,
+        transaction: &mut Transaction<'_, sqlx::Postgres>,
+    ) -> sqlx::Result<u64> {
+        let mut moved = 0;
+        if let Some(next_iteration_id) = next_iteration_id {
+            let issues = sqlx::query!(
+                "SELECT id FROM issues WHERE iteration_id = $1 AND completed_at IS NULL FOR UPDATE",
+                self.id
+            )
+            .fetch_all(&mut *transaction)
+            .await?;
+            sqlx::query!(
+                r#"UPDATE iteration_issues SET carried_over_to = $2
+                   WHERE iteration_id = $1 AND issue_id IN (
+                    SELECT id FROM issues WHERE iteration_id = $1 AND completed_at IS NULL
+                   )"#,
+                self.id,
+                next_iteration_id
+            )
+            .execute(&mut *transaction)
+            .await?;
+
+            for issue in &issues {
+                let revision = IssueRevision::create_automatic(issue.id, &mut *transaction).await?;
+                IssueRevisionChange::create(
+                    revision.id,
+                    "iteration_id",
+                    Some(self.id),
+                    Some(next_iteration_id),
+                    &mut *transaction,
+                )
+                .await?;
+                sqlx::query!(
+                    "UPDATE issues SET iteration_id = $2, current_revision_id = $3 WHERE id = $1",
+                    issue.id,
+                    next_iteration_id,
+                    revision.id
+                )
+                .execute(&mut *transaction)
+                .await?;
+            }
+            moved = issues.len() as u64;
+        }
+
+        let row = sqlx::query!(
+            "UPDATE iterations SET carried_over_at = now() WHERE id = $1 RETURNING carried_over_at",
+            self.id
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        self.carried_over_at = row.carried_over_at;
+
+        Ok(moved)
+    }
+
+    /// Every issue that was planned into this iteration, including the ones
+    /// that were carried over to a later iteration
+    pub async fn planned_issues(&self) -> sqlx::Result<Vec<IterationIssue>> {
+        sqlx::query_as!(
+            IterationIssue,
+            r#"SELECT
+                issues.id,
+                projects.key as "project_key?",
+                number,
+                summary,
+                estimate_hours,
+                started_at,
+                completed_at,
+                carried_over_to
+               FROM iteration_issues
+               INNER JOIN issues ON issues.id = iteration_issues.issue_id
+               LEFT OUTER JOIN projects ON projects.id = issues.project_id
+               WHERE iteration_issues.iteration_id = $1
+               ORDER BY iteration_issues.planned_at, issues.id"#,
+            self.id
+        )
+        .fetch_all(crate::pool())
+        .await
+    }
+
+    /// The moment the iteration starts
+    fn start(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.starts_on.and_hms(0, 0, 0), Utc)
+    }
+
+    /// The moment the iteration is over, the end of its last day
+    fn end(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.ends_on.succ().and_hms(0, 0, 0), Utc)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IterationIssue {
+    pub id: i64,
+    pub project_key: Option<String>,
+    pub number: Option<i32>,
+    pub summary: String,
+    pub estimate_hours: Option<i32>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// The iteration the issue was carried over to
+    pub carried_over_to: Option<i64>,
+}
+
+/// Where a planned issue stood when the iteration ended, or stands now while
+/// it's still running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IterationIssueStatus {
+    Completed,
+    InProgress,
+    NotStarted,
+    CarriedOver,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IterationReportIssue {
+    pub issue: IterationIssue,
+    pub status: IterationIssueStatus,
+}
+
+/// Planned against completed work for an iteration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IterationReport {
+    pub capacity_hours: Option<i32>,
+    pub planned: usize,
+    pub planned_hours: i64,
+    /// Planned issues without an estimate, which aren't part of the hours
+    pub unestimated: usize,
+    pub completed: usize,
+    pub completed_hours: i64,
+    /// Issues completed during the iteration that were started before it
+    pub started_before: usize,
+    pub carried_over: usize,
+    pub percent_complete: u32,
+    /// How much of the capacity the planned hours take up
+    pub percent_of_capacity: Option<u32>,
+    pub issues: Vec<IterationReportIssue>,
+}
+
+impl IterationReport {
+    /// Issues count as completed when they were completed before the end of
+    /// the iteration. Completing a carried over issue later doesn't change
+    /// this iteration's report.
+    pub fn new(iteration: &Iteration, issues: Vec<IterationIssue>) -> Self {
+        let start = iteration.start();
+        let end = iteration.end();
+        let mut report = Self {
+            capacity_hours: iteration.capacity_hours,
+            planned: issues.len(),
+            planned_hours: 0,
+            unestimated: 0,
+            completed: 0,
+            completed_hours: 0,
+            started_before: 0,
+            carried_over: 0,
+            percent_complete: 0,
+            percent_of_capacity: None,
+            issues: Vec::with_capacity(issues.len()),
+        };
+
+        for issue in issues {
+            let hours = i64::from(issue.estimate_hours.unwrap_or_default());
+            match issue.estimate_hours {
+                Some(_) => report.planned_hours += hours,
+                None => report.unestimated += 1,
+            }
+
+            let status = if issue.completed_at.map_or(false, |at| at < end) {
+                report.completed += 1;
+                report.completed_hours += hours;
+                if issue.started_at.map_or(false, |at| at < start) {
+                    report.started_before += 1;
+                }
+                IterationIssueStatus::Completed
+            } else if issue.carried_over_to.is_some() {
+                report.carried_over += 1;
+                IterationIssueStatus::CarriedOver
+            } else if issue.started_at.map_or(false, |at| at < end) {
+                IterationIssueStatus::InProgress
+            } else {
+                IterationIssueStatus::NotStarted
+            };
+            report.issues.push(IterationReportIssue { issue, status });
+        }
+
+        if report.planned > 0 {
+            report.percent_complete = (report.completed * 100 / report.planned) as u32;
+        }
+        report.percent_of_capacity = iteration
+            .capacity_hours
+            .filter(|capacity| *capacity > 0)
+            .map(|capacity| (report.planned_hours * 100 / i64::from(capacity)) as u32);
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{schema::issues::Issue, test_helpers};
+    use chrono::TimeZone;
+
+    fn iteration() -> Iteration {
+        let mut iteration = Iteration::new(
+            String::from("Week 1"),
+            NaiveDate::from_ymd(2021, 3, 1),
+            NaiveDate::from_ymd(2021, 3, 7),
+        );
+        iteration.capacity_hours = Some(20);
+        iteration
+    }
+
+    #[test]
+    fn report_compares_planned_and_completed_work() {
+        let before = Utc.ymd(2021, 2, 26).and_hms(12, 0, 0);
+        let during = Utc.ymd(2021, 3, 3).and_hms(12, 0, 0);
+        let last_day = Utc.ymd(2021, 3, 7).and_hms(23, 0, 0);
+        let after = Utc.ymd(2021, 3, 9).and_hms(12, 0, 0);
+        let finished = IterationIssue {
+            id: 1,
+            project_key: Some(String::from("CR")),
+            number: Some(1),
+            summary: String::from("Finished"),
+            estimate_hours: Some(5),
+            started_at: Some(during),
+            completed_at: Some(during),
+            carried_over_to: None,
+        };
+
+        let report = IterationReport::new(
+            &iteration(),
+            vec![
+                finished.clone(),
+                IterationIssue {
+                    id: 2,
+                    number: Some(2),
+                    summary: String::from("Started before"),
+                    estimate_hours: Some(8),
+                    started_at: Some(before),
+                    completed_at: Some(last_day),
+                    ..finished.clone()
+                },
+                IterationIssue {
+                    id: 3,
+                    number: Some(3),
+                    summary: String::from("In progress"),
+                    estimate_hours: None,
+                    completed_at: None,
+                    ..finished.clone()
+                },
+                IterationIssue {
+                    id: 4,
+                    number: Some(4),
+                    summary: String::from("Not started"),
+                    estimate_hours: Some(4),
+                    started_at: None,
+                    completed_at: None,
+                    ..finished.clone()
+                },
+                IterationIssue {
+                    id: 5,
+                    number: Some(5),
+                    summary: String::from("Carried over"),
+                    estimate_hours: Some(3),
+                    completed_at: Some(after),
+                    carried_over_to: Some(2),
+                    ..finished
+                },
+            ],
+        );
+
+        assert_eq!(report.planned, 5);
+        assert_eq!(report.planned_hours, 20);
+        assert_eq!(report.unestimated, 1);
+        assert_eq!(report.completed, 2);
+        assert_eq!(report.completed_hours, 13);
+        assert_eq!(report.started_before, 1);
+        assert_eq!(report.carried_over, 1);
+        assert_eq!(report.percent_complete, 40);
+        assert_eq!(report.percent_of_capacity, Some(100));
+        assert_eq!(
+            report
+                .issues
+                .iter()
+                .map(|issue| issue.status)
+                .collect::<Vec<_>>(),
+            vec![
+                IterationIssueStatus::Completed,
+                IterationIssueStatus::Completed,
+                IterationIssueStatus::InProgress,
+                IterationIssueStatus::NotStarted,
+                IterationIssueStatus::CarriedOver,
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_iteration_without_capacity() {
+        let mut iteration = iteration();
+        iteration.capacity_hours = None;
+        let report = IterationReport::new(&iteration, Vec::new());
+        assert_eq!(report.percent_complete, 0);
+        assert_eq!(report.percent_of_capacity, None);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn carrying_over_moves_incomplete_issues() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        let account = test_helpers::setup_test_account(&mut tx).await?;
+        let mut first = Iteration::new(
+            String::from("First"),
+            NaiveDate::from_ymd(2021, 3, 1),
+            NaiveDate::from_ymd(2021, 3, 7),
+        );
+        first.save(&mut tx).await?;
+        let mut second = Iteration::new(
+            String::from("Second"),
+            NaiveDate::from_ymd(2021, 3, 8),
+            NaiveDate::from_ymd(2021, 3, 14),
+        );
+        second.save(&mut tx).await?;
+
+        let mut completed = Issue::new(account.id, String::from("Completed"), None, None, None);
+        completed.iteration_id = Some(first.id);
+        completed.completed_at = Some(Utc::now());
+        completed.save(&mut tx).await?;
+        let mut incomplete = Issue::new(account.id, String::from("Incomplete"), None, None, None);
+        incomplete.iteration_id = Some(first.id);
+        incomplete.save(&mut tx).await?;
+
+        assert_eq!(first.carry_over(Some(second.id), &mut tx).await?, 1);
+        assert!(first.carried_over_at.is_some());
+        let completed = Issue::load_for_update(completed.id, &mut tx).await?;
+        assert_eq!(completed.iteration_id, Some(first.id));
+        assert_eq!(completed.current_revision_id, None);
+        let incomplete = Issue::load_for_update(incomplete.id, &mut tx).await?;
+        assert_eq!(incomplete.iteration_id, Some(second.id));
+
+        // The move is recorded as a revision of the issue without an author
+        let changes: Vec<(i64, Option<i64>, serde_json::Value, serde_json::Value)> = sqlx::query_as(
+            r#"SELECT issue_revisions.id, issue_revisions.author_id, old_value, new_value FROM issue_revision_changes
+               INNER JOIN issue_revisions ON issue_revisions.id = issue_revision_changes.issue_revision_id
+               WHERE issue_revisions.issue_id = $1 AND property = 'iteration_id'"#,
+        )
+        .bind(incomplete.id)
+        .fetch_all(&mut tx)
+        .await?;
+        assert_eq!(
+            changes,
+            vec![(
+                incomplete.current_revision_id.unwrap(),
+                None,
+                serde_json::json!(first.id),
+                serde_json::json!(second.id)
+            )]
+        );
+
+        // Both iterations' plans include the carried over issue
+        let plan: Vec<(i64, Option<i64>)> = sqlx::query_as(
+            "SELECT iteration_id, carried_over_to FROM iteration_issues WHERE issue_id = $1 ORDER BY iteration_id",
+        )
+        .bind(incomplete.id)
+        .fetch_all(&mut tx)
+        .await?;
+        assert_eq!(plan, vec![(first.id, Some(second.id)), (second.id, None)]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,422 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx::{self, FromRow};
+
+use crate::{DatabaseError, SqlxResultExt};
+
+use super::{Resolution, Taxonomy};
+
+/// A version or release that a project's issues are planned toward
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Milestone {
+    pub id: i64,
+    pub project_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub target_date: Option<NaiveDate>,
+    /// When the milestone was closed, or `None` while it's open
+    pub closed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Milestone {
+    pub fn new(project_id: i64, name: String) -> Self {
+        Self {
+            id: 0,
+            project_id,
+            name,
+            description: None,
+            target_date: None,
+            closed_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub async fn load(milestone_id: i64) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Self,
+            "SELECT id, project_id, name, description, target_date, closed_at, created_at FROM milestones WHERE id = $1",
+            milestone_id
+        )
+        .fetch_one(crate::pool())
+        .await
+    }
+
+    /// A project's milestones, open ones first and soonest first
+    pub async fn list_for_project(project_id: i64) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, project_id, name, description, target_date, closed_at, created_at FROM milestones
+               WHERE project_id = $1
+               ORDER BY closed_at IS NOT NULL, target_date NULLS LAST, lower(name)"#,
+            project_id
+        )
+        .fetch_all(crate::pool())
+        .await
+    }
+
+    /// Every open milestone, for choosing an issue's milestone
+    pub async fn list_open() -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, project_id, name, description, target_date, closed_at, created_at FROM milestones
+               WHERE closed_at IS NULL
+               ORDER BY target_date NULLS LAST, lower(name)"#,
+        )
+        .fetch_all(crate::pool())
+        .await
+    }
+
+    /// Every milestone, for showing the milestone changes in issue timelines
+    pub async fn list_as_map() -> sqlx::Result<HashMap<i64, Self>> {
+        Ok(sqlx::query_as!(
+            Self,
+            "SELECT id, project_id, name, description, target_date, closed_at, created_at FROM milestones"
+        )
+        .fetch_all(crate::pool())
+        .await?
+        .into_iter()
+        .map(|milestone| (milestone.id, milestone))
+        .collect())
+    }
+
+    pub async fn save<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        &mut self,
+        executor: E,
+    ) -> Result<(), DatabaseError> {
+        if self.id == 0 {
+            let row = sqlx::query!(
+                r#"INSERT INTO milestones (project_id, name, description, target_date, closed_at)
+                   VALUES ($1, $2, $3, $4, $5) RETURNING id, created_at"#,
+                self.project_id,
+                &self.name,
+                self.description.as_ref(),
+                self.target_date,
+                self.closed_at,
+            )
+            .fetch_one(executor)
+            .await
+            .map_database_error()?;
+
+            self.id = row.id;
+            self.created_at = row.created_at;
+        } else {
+            sqlx::query!(
+                r#"UPDATE milestones SET name = $2, description = $3, target_date = $4, closed_at = $5
+                   WHERE id = $1 RETURNING id"#,
+                self.id,
+                &self.name,
+                self.description.as_ref(),
+                self.target_date,
+                self.closed_at,
+            )
+            .fetch_one(executor)
+            .await
+            .map_database_error()?;
+        }
+
+        Ok(())
+    }
+
+    /// The issues planned for this milestone
+    pub async fn issues(&self) -> sqlx::Result<Vec<MilestoneIssue>> {
+        sqlx::query_as!(
+            MilestoneIssue,
+            r#"SELECT
+                id,
+                number,
+                summary,
+                blocked,
+                completed_at,
+                resolution as "resolution: Resolution",
+                array(SELECT issue_tags.tag_id FROM issue_tags WHERE issue_id = issues.id) as "tag_ids!"
+               FROM issues
+               WHERE milestone_id = $1
+               ORDER BY id"#,
+            self.id
+        )
+        .fetch_all(crate::pool())
+        .await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MilestoneIssue {
+    pub id: i64,
+    pub number: Option<i32>,
+    pub summary: String,
+    pub blocked: bool,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub resolution: Option<Resolution>,
+    pub tag_ids: Vec<i32>,
+}
+
+/// A group of release notes. Issues without a tag in any group are collected
+/// in a section without a name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseNotesSection {
+    pub tag_group_id: Option<i32>,
+    pub name: Option<String>,
+    pub issues: Vec<ReleaseNote>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseNote {
+    pub issue_id: i64,
+    pub number: Option<i32>,
+    pub summary: String,
+    /// The issue's tags from the section's group
+    pub tags: Vec<String>,
+}
+
+/// How far along a milestone is, along with its release notes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MilestoneReport {
+    pub total: usize,
+    pub completed: usize,
+    pub percent_complete: u32,
+    /// Open issues that aren't blocked
+    pub open: Vec<MilestoneIssue>,
+    pub blocked: Vec<MilestoneIssue>,
+    pub release_notes: Vec<ReleaseNotesSection>,
+}
+
+impl MilestoneReport {
+    /// Only issues that were completed as fixed make it into the release
+    /// notes, as duplicates and issues that won't be fixed didn't change
+    /// anything.
+    pub fn new(issues: Vec<MilestoneIssue>, taxonomy: &Taxonomy) -> Self {
+        let total = issues.len();
+        let mut completed = 0;
+        let mut open = Vec::new();
+        let mut blocked = Vec::new();
+        let mut sections: BTreeMap<(bool, String, i32), ReleaseNotesSection> = BTreeMap::new();
+        for issue in issues {
+            if issue.completed_at.is_none() {
+                if issue.blocked {
+                    blocked.push(issue);
+                } else {
+                    open.push(issue);
+                }
+                continue;
+            }
+
+            completed += 1;
+            if !matches!(issue.resolution, None | Some(Resolution::Fixed)) {
+                continue;
+            }
+
+            let mut tags_by_group: BTreeMap<i32, Vec<String>> = BTreeMap::new();
+            for tag_id in &issue.tag_ids {
+                let group_id = taxonomy
+                    .tag_groups
+                    .iter()
+                    .find(|(_, group)| group.tags.contains(tag_id))
+                    .map(|(group_id, _)| *group_id);
+                if let (Some(group_id), Some(tag)) = (group_id, taxonomy.tags.get(tag_id)) {
+                    tags_by_group
+                        .entry(group_id)
+                        .or_default()
+                        .push(tag.name.clone());
+                }
+            }
+
+            if tags_by_group.is_empty() {
+                // Sorting on `true` places the unnamed section last
+                sections
+                    .entry((true, String::new(), 0))
+                    .or_insert_with(|| ReleaseNotesSection {
+                        tag_group_id: None,
+                        name: None,
+                        issues: Vec::new(),
+                    })
+                    .issues
+                    .push(ReleaseNote::new(&issue, Vec::new()));
+            }
+            for (group_id, mut tags) in tags_by_group {
+                tags.sort();
+                let name = taxonomy.tag_groups[&group_id].name.clone();
+                sections
+                    .entry((false, name.to_lowercase(), group_id))
+                    .or_insert_with(|| ReleaseNotesSection {
+                        tag_group_id: Some(group_id),
+                        name: Some(name),
+                        issues: Vec::new(),
+                    })
+                    .issues
+                    .push(ReleaseNote::new(&issue, tags));
+            }
+        }
+
+        let percent_complete = if total > 0 {
+            (completed * 100 / total) as u32
+        } else {
+            0
+        };
+
+        Self {
+            total,
+            completed,
+            percent_complete,
+            open,
+            blocked,
+            release_notes: sections.into_iter().map(|(_, section)| section).collect(),
+        }
+    }
+}
+
+impl ReleaseNote {
+    fn new(issue: &MilestoneIssue, tags: Vec<String>) -> Self {
+        Self {
+            issue_id: issue.id,
+            number: issue.number,
+            summary: issue.summary.clone(),
+            tags,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::issues::{TaxonomyGroup, TaxonomyTag};
+
+    fn taxonomy() -> Taxonomy {
+        let mut taxonomy = Taxonomy::default();
+        for (id, name) in &[(1, "bug"), (2, "feature"), (3, "ui"), (4, "loose")] {
+            taxonomy.tags.insert(
+                *id,
+                TaxonomyTag {
+                    name: name.to_string(),
+                    color: None,
+                },
+            );
+        }
+        taxonomy.tag_groups.insert(
+            10,
+            TaxonomyGroup {
+                name: String::from("Kind"),
+                single_select: true,
+                color: None,
+                tags: vec![1, 2].into_iter().collect(),
+            },
+        );
+        taxonomy.tag_groups.insert(
+            20,
+            TaxonomyGroup {
+                name: String::from("Area"),
+                single_select: false,
+                color: None,
+                tags: vec![3].into_iter().collect(),
+            },
+        );
+        taxonomy.ungrouped_tags.insert(4);
+        taxonomy
+    }
+
+    #[test]
+    fn report_counts_progress_and_groups_release_notes() {
+        let fixed = MilestoneIssue {
+            id: 1,
+            number: Some(1),
+            summary: String::from("Crash when saving"),
+            blocked: false,
+            completed_at: Some(Utc::now()),
+            resolution: Some(Resolution::Fixed),
+            tag_ids: vec![1, 3],
+        };
+        let open = MilestoneIssue {
+            id: 3,
+            number: Some(3),
+            summary: String::from("Dark mode"),
+            completed_at: None,
+            resolution: None,
+            tag_ids: Vec::new(),
+            ..fixed.clone()
+        };
+        let report = MilestoneReport::new(
+            vec![
+                fixed.clone(),
+                MilestoneIssue {
+                    id: 2,
+                    number: Some(2),
+                    summary: String::from("Export to CSV"),
+                    tag_ids: vec![2],
+                    ..fixed.clone()
+                },
+                open.clone(),
+                MilestoneIssue {
+                    id: 4,
+                    number: Some(4),
+                    summary: String::from("Translations"),
+                    blocked: true,
+                    ..open
+                },
+                MilestoneIssue {
+                    id: 5,
+                    number: Some(5),
+                    summary: String::from("Crash on save"),
+                    resolution: Some(Resolution::Duplicate),
+                    tag_ids: vec![1],
+                    ..fixed.clone()
+                },
+                MilestoneIssue {
+                    id: 6,
+                    number: Some(6),
+                    summary: String::from("Faster startup"),
+                    tag_ids: vec![4],
+                    ..fixed
+                },
+            ],
+            &taxonomy(),
+        );
+
+        assert_eq!(report.total, 6);
+        assert_eq!(report.completed, 4);
+        assert_eq!(report.percent_complete, 66);
+        assert_eq!(
+            report.open.iter().map(|i| i.id).collect::<Vec<_>>(),
+            vec![3]
+        );
+        assert_eq!(
+            report.blocked.iter().map(|i| i.id).collect::<Vec<_>>(),
+            vec![4]
+        );
+
+        let sections = report
+            .release_notes
+            .iter()
+            .map(|section| {
+                (
+                    section.name.clone(),
+                    section
+                        .issues
+                        .iter()
+                        .map(|note| (note.issue_id, note.tags.join(",")))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sections,
+            vec![
+                (Some(String::from("Area")), vec![(1, String::from("ui"))]),
+                (
+                    Some(String::from("Kind")),
+                    vec![(1, String::from("bug")), (2, String::from("feature"))]
+                ),
+                (None, vec![(6, String::new())]),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_milestone_is_not_complete() {
+        let report = MilestoneReport::new(Vec::new(), &taxonomy());
+        assert_eq!(report.percent_complete, 0);
+        assert!(report.release_notes.is_empty());
+    }
+}
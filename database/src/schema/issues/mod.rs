@@ -1,10 +1,21 @@
 pub use self::{
-    issue::*, issue_relationship::*, issue_revision::*, project::*, tag::*, tag_group::*,
+    commit::*, dependency_graph::*, issue::*, issue_relationship::*, issue_revision::*,
+    iteration::*, milestone::*, project::*, resolution::*, schedule::*, tag::*, tag_group::*,
+    tree::*, watcher::*, workflow::*,
 };
 
+mod commit;
+mod dependency_graph;
 mod issue;
 mod issue_relationship;
 mod issue_revision;
+mod iteration;
+mod milestone;
 mod project;
+mod resolution;
+mod schedule;
 mod tag;
 mod tag_group;
+mod tree;
+mod watcher;
+mod workflow;
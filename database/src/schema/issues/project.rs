@@ -6,10 +6,14 @@ use migrations::sqlx::{self, Done, FromRow, Transaction};
 use crate::{DatabaseError, SqlxResultExt};
 use std::collections::HashMap;
 
+pub const MAXIMUM_PROJECT_KEY_LENGTH: usize = 10;
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct Project {
     pub id: i64,
     pub slug: String,
+    /// Prefixes the numbers of the project's issues, e.g. `GOOEY` in `GOOEY-42`
+    pub key: String,
     pub name: String,
     pub description: Option<String>,
     pub owner_id: i64,
@@ -24,6 +28,10 @@ pub enum ProjectError {
     SlugInvalidCharacter(char),
     #[error("slug already in use")]
     SlugConflict,
+    #[error("invalid key '{0}'")]
+    KeyInvalid(String),
+    #[error("key already in use")]
+    KeyConflict,
     #[error("sql error: {0}")]
     Sql(#[from] sqlx::Error),
 }
@@ -39,9 +47,16 @@ impl From<DatabaseError> for ProjectError {
 }
 
 impl Project {
-    pub fn new(slug: String, name: String, description: Option<String>, owner_id: i64) -> Self {
+    pub fn new(
+        slug: String,
+        key: String,
+        name: String,
+        description: Option<String>,
+        owner_id: i64,
+    ) -> Self {
         Self {
             slug,
+            key,
             name,
             description,
             owner_id,
@@ -57,22 +72,47 @@ impl Project {
     pub async fn load(project_id: i64) -> sqlx::Result<Self> {
         sqlx::query_as!(
             Self,
-            "SELECT id, slug, name, description, owner_id, created_at FROM projects WHERE id = $1",
+            "SELECT id, slug, key, name, description, owner_id, created_at FROM projects WHERE id = $1",
             project_id
         )
         .fetch_one(crate::pool())
         .await
     }
 
-    pub async fn find_by_slug(slug: &str) -> Result<Self, ProjectError> {
+    pub async fn find_by_slug<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        slug: &str,
+        executor: E,
+    ) -> Result<Self, ProjectError> {
         let slug = Self::cleanup_and_validate_slug(slug)?;
         let project = sqlx::query_as!(
             Self,
-            "SELECT id, slug, name, description, owner_id, created_at FROM projects WHERE slug = $1",
+            "SELECT id, slug, key, name, description, owner_id, created_at FROM projects WHERE slug = $1",
             slug
         )
-            .fetch_one(crate::pool())
-            .await?;
+            .fetch_one(executor)
+            .await
+            .map_database_error()?;
+
+        Ok(project)
+    }
+
+    /// Finds the project that used to have `slug` before being renamed
+    pub async fn find_by_former_slug<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        slug: &str,
+        executor: E,
+    ) -> Result<Self, ProjectError> {
+        let slug = Self::cleanup_and_validate_slug(slug)?;
+        let project = sqlx::query_as!(
+            Self,
+            r#"SELECT projects.id, projects.slug, key, name, description, owner_id, projects.created_at
+               FROM project_slug_history
+               INNER JOIN projects ON projects.id = project_slug_history.project_id
+               WHERE project_slug_history.slug = $1"#,
+            slug
+        )
+        .fetch_one(executor)
+        .await
+        .map_database_error()?;
 
         Ok(project)
     }
@@ -81,23 +121,47 @@ impl Project {
         project_id: i64,
         transaction: &mut Transaction<'_, sqlx::Postgres>,
     ) -> sqlx::Result<Self> {
-        sqlx::query_as!(Self, "SELECT id, slug, name, description, owner_id, created_at FROM projects WHERE id = $1 FOR UPDATE", project_id).fetch_one(transaction).await
+        sqlx::query_as!(Self, "SELECT id, slug, key, name, description, owner_id, created_at FROM projects WHERE id = $1 FOR UPDATE", project_id).fetch_one(transaction).await
     }
 
     pub async fn list() -> sqlx::Result<Vec<Self>> {
-        sqlx::query_as!(Self, "SELECT id, slug, name, description, owner_id, created_at FROM projects ORDER BY lower(name)")
+        sqlx::query_as!(Self, "SELECT id, slug, key, name, description, owner_id, created_at FROM projects ORDER BY lower(name)")
             .fetch_all(crate::pool()).await
     }
 
+    /// The keys of every project, including keys projects have changed from
+    pub async fn list_keys() -> sqlx::Result<Vec<String>> {
+        let rows = sqlx::query!(
+            r#"SELECT key as "key!" FROM projects UNION SELECT key FROM project_key_history"#
+        )
+        .fetch_all(crate::pool())
+        .await?;
+        Ok(rows.into_iter().map(|row| row.key).collect())
+    }
+
     pub async fn list_as_map() -> sqlx::Result<HashMap<i64, Self>> {
         Ok(Self::list().await?.into_iter().map(|p| (p.id, p)).collect())
     }
 
-    pub async fn save<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+    pub async fn save(
         &mut self,
-        executor: E,
+        transaction: &mut Transaction<'_, sqlx::Postgres>,
     ) -> Result<(), ProjectError> {
         self.slug = Self::cleanup_and_validate_slug(&self.slug)?;
+        self.key = Self::cleanup_and_validate_key(&self.key)?;
+        // Slugs and keys are both unique, so a key conflict is checked for
+        // up front to tell the two apart
+        let key_taken = sqlx::query!(
+            r#"SELECT EXISTS(SELECT 1 FROM projects WHERE key = $1 AND id <> $2) as "taken!""#,
+            &self.key,
+            self.id,
+        )
+        .fetch_one(&mut *transaction)
+        .await?
+        .taken;
+        if key_taken {
+            return Err(ProjectError::KeyConflict);
+        }
 
         if self.id == 0 {
             let row = sqlx::query!(
@@ -105,14 +169,16 @@ impl Project {
                     slug, 
                     name, 
                     description, 
-                    owner_id
-                   ) VALUES ($1, $2, $3, $4) RETURNING id, created_at"#,
+                    owner_id,
+                    key
+                   ) VALUES ($1, $2, $3, $4, $5) RETURNING id, created_at"#,
                 &self.slug,
                 &self.name,
                 self.description.as_ref(),
                 self.owner_id,
+                &self.key,
             )
-            .fetch_one(executor)
+            .fetch_one(&mut *transaction)
             .await
             .map_database_error()?;
 
@@ -124,15 +190,17 @@ impl Project {
                     slug = $2,
                     name = $3,
                     description = $4,
-                    owner_id = $5
+                    owner_id = $5,
+                    key = $6
                    WHERE id = $1"#,
                 self.id,
                 &self.slug,
                 &self.name,
                 self.description.as_ref(),
                 self.owner_id,
+                &self.key,
             )
-            .execute(executor)
+            .execute(&mut *transaction)
             .await
             .map_database_error()?;
             if result.rows_affected() == 0 {
@@ -154,4 +222,157 @@ impl Project {
 
         Ok(cleaned)
     }
+
+    /// Keys are uppercased, must start with a letter and may only contain
+    /// letters and numbers, so that references like `GOOEY-42` are unambiguous
+    pub fn cleanup_and_validate_key(key: &str) -> Result<String, ProjectError> {
+        let cleaned = key.trim().to_ascii_uppercase();
+        let valid = cleaned.len() <= MAXIMUM_PROJECT_KEY_LENGTH
+            && cleaned
+                .chars()
+                .next()
+                .map_or(false, |c| c.is_ascii_alphabetic())
+            && cleaned.chars().all(|c| c.is_ascii_alphanumeric());
+        if valid {
+            Ok(cleaned)
+        } else {
+            Err(ProjectError::KeyInvalid(key.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{schema::issues::Issue, test_helpers};
+
+    #[test]
+    fn keys_are_uppercased_and_validated() {
+        assert_eq!(
+            Project::cleanup_and_validate_key(" gooey ").unwrap(),
+            "GOOEY"
+        );
+        assert_eq!(Project::cleanup_and_validate_key("KB2").unwrap(), "KB2");
+        for invalid in &["", "2KB", "K-B", "KÖ", "ABCDEFGHIJK"] {
+            assert!(matches!(
+                Project::cleanup_and_validate_key(invalid),
+                Err(ProjectError::KeyInvalid(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn renaming_records_former_slugs() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        let account = test_helpers::setup_test_account(&mut tx).await?;
+        let mut project = Project::new(
+            String::from("slug-history-before"),
+            String::from("SLUGHIST"),
+            String::from("Slug History"),
+            None,
+            account.id,
+        );
+        project.save(&mut tx).await?;
+        assert!(matches!(
+            Project::find_by_former_slug("slug-history-before", &mut tx).await,
+            Err(ProjectError::ProjectNotFound)
+        ));
+
+        project.slug = String::from("slug-history-after");
+        project.save(&mut tx).await?;
+        let renamed = Project::find_by_former_slug("slug-history-before", &mut tx).await?;
+        assert_eq!(renamed.id, project.id);
+        assert_eq!(renamed.slug, "slug-history-after");
+
+        // Renaming the project back claims its old slug from the history
+        project.slug = String::from("slug-history-before");
+        project.save(&mut tx).await?;
+        assert!(matches!(
+            Project::find_by_former_slug("slug-history-before", &mut tx).await,
+            Err(ProjectError::ProjectNotFound)
+        ));
+        let renamed = Project::find_by_former_slug("slug-history-after", &mut tx).await?;
+        assert_eq!(renamed.slug, "slug-history-before");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn new_projects_claim_former_slugs() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        let account = test_helpers::setup_test_account(&mut tx).await?;
+        let mut renamed = Project::new(
+            String::from("slug-claim-before"),
+            String::from("SLUGCLAIMA"),
+            String::from("Renamed"),
+            None,
+            account.id,
+        );
+        renamed.save(&mut tx).await?;
+        renamed.slug = String::from("slug-claim-after");
+        renamed.save(&mut tx).await?;
+
+        let mut claiming = Project::new(
+            String::from("slug-claim-before"),
+            String::from("SLUGCLAIMB"),
+            String::from("Claiming"),
+            None,
+            account.id,
+        );
+        claiming.save(&mut tx).await?;
+        assert!(matches!(
+            Project::find_by_former_slug("slug-claim-before", &mut tx).await,
+            Err(ProjectError::ProjectNotFound)
+        ));
+        assert_eq!(
+            Project::find_by_slug("slug-claim-before", &mut tx)
+                .await?
+                .id,
+            claiming.id
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn former_keys_still_find_issues() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        let account = test_helpers::setup_test_account(&mut tx).await?;
+        let mut project = Project::new(
+            String::from("key-history"),
+            String::from("KEYBEFORE"),
+            String::from("Key History"),
+            None,
+            account.id,
+        );
+        project.save(&mut tx).await?;
+        let mut issue = Issue::new(
+            account.id,
+            String::from("Numbered"),
+            None,
+            None,
+            Some(project.id),
+        );
+        issue.save(&mut tx).await?;
+        let (number,): (i32,) = sqlx::query_as("SELECT number FROM issues WHERE id = $1")
+            .bind(issue.id)
+            .fetch_one(&mut tx)
+            .await?;
+
+        project.key = String::from("KEYAFTER");
+        project.save(&mut tx).await?;
+        assert_eq!(
+            Issue::find_by_key("KEYAFTER", number, &mut tx).await?,
+            issue.id
+        );
+        assert_eq!(
+            Issue::find_by_key("KEYBEFORE", number, &mut tx).await?,
+            issue.id
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,76 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx;
+
+/// Why a completed issue was closed
+#[derive(Clone, Copy, Debug, Eq, PartialEq, sqlx::Type, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[repr(i32)]
+pub enum Resolution {
+    Fixed = 1,
+    Duplicate,
+    WontFix,
+    CantReproduce,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 4] = [
+        Resolution::Fixed,
+        Resolution::Duplicate,
+        Resolution::WontFix,
+        Resolution::CantReproduce,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Fixed => "fixed",
+            Resolution::Duplicate => "duplicate",
+            Resolution::WontFix => "wont-fix",
+            Resolution::CantReproduce => "cant-reproduce",
+        }
+    }
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = ResolutionParseError;
+
+    fn from_str(resolution: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|candidate| candidate.as_str() == resolution)
+            .copied()
+            .ok_or(ResolutionParseError)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("invalid resolution")]
+pub struct ResolutionParseError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolutions_round_trip_through_strings() {
+        for resolution in Resolution::ALL.iter() {
+            assert_eq!(
+                Resolution::from_str(&resolution.to_string()).unwrap(),
+                *resolution
+            );
+            assert_eq!(
+                serde_json::to_value(resolution).unwrap(),
+                serde_json::Value::String(resolution.to_string())
+            );
+        }
+        assert!(Resolution::from_str("resolved").is_err());
+    }
+}
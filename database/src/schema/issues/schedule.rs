@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx;
+
+/// How many recently completed issues are used to judge how long work takes
+const HISTORY_LIMIT: i64 = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleIssue {
+    pub id: i64,
+    pub summary: String,
+    pub estimate_hours: Option<i32>,
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+/// A completed issue's estimate alongside how long it actually took
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CompletedWork {
+    pub estimate_hours: Option<i32>,
+    pub actual_hours: f64,
+}
+
+/// How long work has taken in the past, used to fill in missing estimates
+/// and to correct for estimates being consistently optimistic or pessimistic
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Throughput {
+    /// The median time from starting to completing an issue
+    pub median_hours: Option<f64>,
+    /// Actual time taken for each estimated hour
+    pub estimate_accuracy: f64,
+    pub sample_size: usize,
+}
+
+impl Throughput {
+    pub fn new(history: &[CompletedWork]) -> Self {
+        let mut durations = history
+            .iter()
+            .map(|work| work.actual_hours)
+            .collect::<Vec<_>>();
+        durations.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let median_hours = match durations.len() {
+            0 => None,
+            len if len % 2 == 0 => Some((durations[len / 2 - 1] + durations[len / 2]) / 2.),
+            len => Some(durations[len / 2]),
+        };
+
+        let (estimated, actual) = history
+            .iter()
+            .filter_map(|work| Some((work.estimate_hours? as f64, work.actual_hours)))
+            .fold((0., 0.), |(estimated, actual), (estimate, hours)| {
+                (estimated + estimate, actual + hours)
+            });
+        let estimate_accuracy = if estimated > 0. {
+            actual / estimated
+        } else {
+            1.
+        };
+
+        Self {
+            median_hours,
+            estimate_accuracy,
+            sample_size: history.len(),
+        }
+    }
+
+    /// How long an issue is expected to take from start to finish
+    fn expected_hours(&self, issue: &ScheduleIssue) -> f64 {
+        match issue.estimate_hours {
+            Some(estimate) => estimate as f64 * self.estimate_accuracy,
+            None => self.median_hours.unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledIssue {
+    pub id: i64,
+    pub summary: String,
+    pub estimated: bool,
+    pub remaining_hours: f64,
+    pub start: DateTime<Utc>,
+    pub finish: DateTime<Utc>,
+}
+
+/// Open work laid out as early as its precedence and blocking links allow,
+/// assuming unrelated issues can be worked on at the same time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub issues: Vec<ScheduledIssue>,
+    /// The chain of issues that determines when all the work is done, first to last
+    pub critical_path: Vec<ScheduledIssue>,
+    pub forecast_completion: Option<DateTime<Utc>>,
+    pub unestimated: usize,
+    pub throughput: Throughput,
+}
+
+impl Schedule {
+    /// Schedules `issues`, where each pair in `order` is an issue that must be
+    /// finished before another can start. Links to issues that aren't being
+    /// scheduled, such as completed ones, are ignored.
+    pub fn new(
+        issues: Vec<ScheduleIssue>,
+        order: &[(i64, i64)],
+        throughput: Throughput,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let index = issues
+            .iter()
+            .enumerate()
+            .map(|(index, issue)| (issue.id, index))
+            .collect::<HashMap<_, _>>();
+        let mut predecessors = vec![Vec::new(); issues.len()];
+        let mut successors = vec![Vec::new(); issues.len()];
+        for (before, after) in order {
+            if let (Some(&before), Some(&after)) = (index.get(before), index.get(after)) {
+                if before != after && !predecessors[after].contains(&before) {
+                    predecessors[after].push(before);
+                    successors[before].push(after);
+                }
+            }
+        }
+
+        // Issues are scheduled once everything before them has been. Cycles
+        // are rejected when linking, but any left over from older data are
+        // broken by scheduling their remaining issues in order.
+        let mut waiting_on = predecessors.iter().map(Vec::len).collect::<Vec<_>>();
+        let mut ready = (0..issues.len())
+            .filter(|&index| waiting_on[index] == 0)
+            .collect::<Vec<_>>();
+        let mut scheduled = vec![None; issues.len()];
+        let mut determined_by = vec![None; issues.len()];
+        let mut next = 0;
+        loop {
+            let current = match ready.pop() {
+                Some(current) => current,
+                None => match (next..issues.len()).find(|&index| scheduled[index].is_none()) {
+                    Some(current) => {
+                        next = current;
+                        current
+                    }
+                    None => break,
+                },
+            };
+            if scheduled[current].is_some() {
+                continue;
+            }
+
+            let issue = &issues[current];
+            let mut start = now;
+            for &predecessor in &predecessors[current] {
+                if let Some((_, _, finish)) = scheduled[predecessor] {
+                    if finish > start {
+                        start = finish;
+                        determined_by[current] = Some(predecessor);
+                    }
+                }
+            }
+
+            // Work already underway only needs what's left of it
+            let expected_hours = throughput.expected_hours(issue);
+            let elapsed_hours = issue
+                .started_at
+                .map(|started_at| (now - started_at).num_minutes() as f64 / 60.)
+                .unwrap_or_default();
+            let remaining_hours = (expected_hours - elapsed_hours).max(0.);
+            let finish = start + Duration::minutes((remaining_hours * 60.).round() as i64);
+            scheduled[current] = Some((start, remaining_hours, finish));
+
+            for &successor in &successors[current] {
+                waiting_on[successor] = waiting_on[successor].saturating_sub(1);
+                if waiting_on[successor] == 0 {
+                    ready.push(successor);
+                }
+            }
+        }
+
+        let scheduled_issues = issues
+            .iter()
+            .zip(&scheduled)
+            .map(|(issue, scheduled)| {
+                let (start, remaining_hours, finish) = scheduled.unwrap_or((now, 0., now));
+                ScheduledIssue {
+                    id: issue.id,
+                    summary: issue.summary.clone(),
+                    estimated: issue.estimate_hours.is_some(),
+                    remaining_hours,
+                    start,
+                    finish,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut critical_path = Vec::new();
+        let last = (0..scheduled_issues.len()).max_by_key(|&index| scheduled_issues[index].finish);
+        let mut current = last;
+        while let Some(index) = current {
+            if critical_path.len() > scheduled_issues.len() {
+                break;
+            }
+            critical_path.insert(0, scheduled_issues[index].clone());
+            current = determined_by[index];
+        }
+
+        Self {
+            forecast_completion: last.map(|index| scheduled_issues[index].finish),
+            unestimated: issues
+                .iter()
+                .filter(|issue| issue.estimate_hours.is_none())
+                .count(),
+            issues: scheduled_issues,
+            critical_path,
+            throughput,
+        }
+    }
+
+    /// Schedules the open issues beneath `parent_id`
+    pub async fn for_subtree(parent_id: i64, now: DateTime<Utc>) -> sqlx::Result<Self> {
+        let issues = sqlx::query_as!(
+            ScheduleIssue,
+            r#"WITH RECURSIVE subtree(id) AS (
+                SELECT id FROM issues WHERE parent_id = $1
+                UNION
+                SELECT issues.id FROM issues INNER JOIN subtree ON issues.parent_id = subtree.id
+               )
+               SELECT issues.id, summary, estimate_hours, started_at FROM issues
+               INNER JOIN subtree ON subtree.id = issues.id
+               WHERE completed_at IS NULL
+               ORDER BY issues.id"#,
+            parent_id,
+        )
+        .fetch_all(crate::pool())
+        .await?;
+        let project_id = sqlx::query!("SELECT project_id FROM issues WHERE id = $1", parent_id)
+            .fetch_one(crate::pool())
+            .await?
+            .project_id;
+
+        Self::load(issues, project_id, now).await
+    }
+
+    /// Schedules a project's open issues
+    pub async fn for_project(project_id: i64, now: DateTime<Utc>) -> sqlx::Result<Self> {
+        let issues = sqlx::query_as!(
+            ScheduleIssue,
+            r#"SELECT id, summary, estimate_hours, started_at FROM issues
+               WHERE project_id = $1 AND completed_at IS NULL
+               ORDER BY id"#,
+            project_id,
+        )
+        .fetch_all(crate::pool())
+        .await?;
+
+        Self::load(issues, Some(project_id), now).await
+    }
+
+    async fn load(
+        issues: Vec<ScheduleIssue>,
+        project_id: Option<i64>,
+        now: DateTime<Utc>,
+    ) -> sqlx::Result<Self> {
+        let ids = issues.iter().map(|issue| issue.id).collect::<Vec<_>>();
+        let order = sqlx::query!(
+            r#"SELECT issue_a, issue_b FROM issue_relationships
+               WHERE relationship IN ('blocks', 'precedes')
+                 AND issue_a = ANY($1) AND issue_b = ANY($1)"#,
+            &ids,
+        )
+        .fetch_all(crate::pool())
+        .await?
+        .into_iter()
+        .map(|row| (row.issue_a, row.issue_b))
+        .collect::<Vec<_>>();
+
+        let history = sqlx::query!(
+            r#"SELECT estimate_hours, started_at as "started_at!", completed_at as "completed_at!" FROM issues
+               WHERE started_at IS NOT NULL AND completed_at IS NOT NULL
+                 AND ($1::BIGINT IS NULL OR project_id = $1)
+               ORDER BY completed_at DESC
+               LIMIT $2"#,
+            project_id,
+            HISTORY_LIMIT,
+        )
+        .fetch_all(crate::pool())
+        .await?
+        .into_iter()
+        .map(|row| CompletedWork {
+            estimate_hours: row.estimate_hours,
+            actual_hours: (row.completed_at - row.started_at).num_minutes().max(0) as f64 / 60.,
+        })
+        .collect::<Vec<_>>();
+
+        Ok(Self::new(issues, &order, Throughput::new(&history), now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn ids(issues: &[ScheduledIssue]) -> Vec<i64> {
+        issues.iter().map(|issue| issue.id).collect()
+    }
+
+    #[test]
+    fn throughput_uses_median_and_estimate_accuracy() {
+        let throughput = Throughput::new(&[
+            CompletedWork {
+                estimate_hours: Some(2),
+                actual_hours: 4.,
+            },
+            CompletedWork {
+                estimate_hours: Some(4),
+                actual_hours: 8.,
+            },
+            CompletedWork {
+                estimate_hours: None,
+                actual_hours: 1.,
+            },
+            CompletedWork {
+                estimate_hours: None,
+                actual_hours: 30.,
+            },
+        ]);
+        assert_eq!(throughput.median_hours, Some(6.));
+        assert_eq!(throughput.estimate_accuracy, 2.);
+        assert_eq!(throughput.sample_size, 4);
+
+        let empty = Throughput::new(&[]);
+        assert_eq!(empty.median_hours, None);
+        assert_eq!(empty.estimate_accuracy, 1.);
+    }
+
+    #[test]
+    fn critical_path_follows_longest_chain() {
+        let now = Utc.ymd(2021, 1, 4).and_hms(9, 0, 0);
+        let throughput = Throughput::new(&[CompletedWork {
+            estimate_hours: None,
+            actual_hours: 3.,
+        }]);
+        let design = ScheduleIssue {
+            id: 1,
+            summary: String::from("Design"),
+            estimate_hours: Some(2),
+            started_at: None,
+        };
+        // 1 -> 2 -> 4 takes 2 + 3 (median) + 1 hours, while 1 -> 3 -> 4 takes 2 + 1 + 1
+        let schedule = Schedule::new(
+            vec![
+                design.clone(),
+                ScheduleIssue {
+                    id: 2,
+                    summary: String::from("Backend"),
+                    estimate_hours: None,
+                    ..design.clone()
+                },
+                ScheduleIssue {
+                    id: 3,
+                    summary: String::from("Frontend"),
+                    estimate_hours: Some(1),
+                    ..design.clone()
+                },
+                ScheduleIssue {
+                    id: 4,
+                    summary: String::from("Release"),
+                    estimate_hours: Some(1),
+                    ..design.clone()
+                },
+                ScheduleIssue {
+                    id: 5,
+                    summary: String::from("Documentation"),
+                    estimate_hours: Some(4),
+                    ..design
+                },
+            ],
+            &[(1, 2), (1, 3), (2, 4), (3, 4), (9, 4)],
+            throughput,
+            now,
+        );
+
+        assert_eq!(ids(&schedule.critical_path), vec![1, 2, 4]);
+        assert_eq!(schedule.forecast_completion, Some(now + Duration::hours(6)));
+        assert_eq!(schedule.unestimated, 1);
+
+        let third = &schedule.issues[2];
+        assert_eq!(third.start, now + Duration::hours(2));
+        assert_eq!(third.finish, now + Duration::hours(3));
+    }
+
+    #[test]
+    fn started_work_only_needs_what_is_left() {
+        let now = Utc.ymd(2021, 1, 4).and_hms(9, 0, 0);
+        let started = ScheduleIssue {
+            id: 1,
+            summary: String::from("Started"),
+            estimate_hours: Some(5),
+            started_at: Some(now - Duration::hours(2)),
+        };
+        let overdue = ScheduleIssue {
+            id: 2,
+            summary: String::from("Overdue"),
+            estimate_hours: Some(1),
+            started_at: Some(now - Duration::hours(4)),
+        };
+
+        let schedule = Schedule::new(vec![started, overdue], &[], Throughput::new(&[]), now);
+        assert_eq!(schedule.issues[0].remaining_hours, 3.);
+        assert_eq!(schedule.issues[1].remaining_hours, 0.);
+        assert_eq!(ids(&schedule.critical_path), vec![1]);
+    }
+
+    #[test]
+    fn cycles_are_still_scheduled() {
+        let now = Utc.ymd(2021, 1, 4).and_hms(9, 0, 0);
+        let first = ScheduleIssue {
+            id: 1,
+            summary: String::from("First"),
+            estimate_hours: Some(1),
+            started_at: None,
+        };
+        let second = ScheduleIssue {
+            id: 2,
+            summary: String::from("Second"),
+            ..first.clone()
+        };
+        let schedule = Schedule::new(
+            vec![first, second],
+            &[(1, 2), (2, 1)],
+            Throughput::new(&[]),
+            now,
+        );
+        assert_eq!(schedule.issues.len(), 2);
+        assert_eq!(schedule.forecast_completion, Some(now + Duration::hours(2)));
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueTreeRow {
+    pub id: i64,
+    pub parent_id: i64,
+    pub project_key: Option<String>,
+    pub number: Option<i32>,
+    pub summary: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub blocked: bool,
+    pub estimate_hours: Option<i32>,
+}
+
+/// Totals over everything beneath an issue
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TreeRollup {
+    pub total: usize,
+    pub completed: usize,
+    pub blocked: usize,
+    /// Estimates of the issue and everything beneath it
+    pub estimate_hours: i64,
+    /// Estimates of whatever is still open, including the issue itself
+    pub remaining_estimate_hours: i64,
+}
+
+impl TreeRollup {
+    fn add(&mut self, node: &IssueTreeNode) {
+        self.total += node.rollup.total + 1;
+        self.completed += node.rollup.completed + node.issue.completed_at.is_some() as usize;
+        self.blocked += node.rollup.blocked
+            + (node.issue.blocked && node.issue.completed_at.is_none()) as usize;
+        self.estimate_hours += node.rollup.estimate_hours;
+        self.remaining_estimate_hours += node.rollup.remaining_estimate_hours;
+    }
+
+    fn add_own_estimate(&mut self, estimate_hours: Option<i32>, completed: bool) {
+        let estimate_hours = estimate_hours.unwrap_or_default() as i64;
+        self.estimate_hours += estimate_hours;
+        if !completed {
+            self.remaining_estimate_hours += estimate_hours;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueTreeNode {
+    pub issue: IssueTreeRow,
+    pub children: Vec<IssueTreeNode>,
+    pub rollup: TreeRollup,
+}
+
+/// Every issue beneath an issue, however deeply nested
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueTree {
+    pub issue_id: i64,
+    pub children: Vec<IssueTreeNode>,
+    /// Totals over the subtree, not counting the issue itself
+    pub rollup: TreeRollup,
+}
+
+impl IssueTree {
+    pub async fn load(issue_id: i64) -> sqlx::Result<Self> {
+        // The path guards against parent loops, which would otherwise recurse forever
+        let rows = sqlx::query_as!(
+            IssueTreeRow,
+            r#"WITH RECURSIVE subtree(id, path) AS (
+                SELECT id, ARRAY[$1::BIGINT, id] FROM issues WHERE parent_id = $1
+                UNION ALL
+                SELECT issues.id, subtree.path || issues.id
+                FROM issues INNER JOIN subtree ON issues.parent_id = subtree.id
+                WHERE NOT issues.id = ANY(subtree.path)
+               )
+               SELECT issues.id, parent_id as "parent_id!", projects.key as "project_key?", number, summary,
+                   started_at, completed_at, blocked, estimate_hours
+               FROM issues INNER JOIN subtree ON subtree.id = issues.id
+               LEFT OUTER JOIN projects ON projects.id = issues.project_id
+               ORDER BY issues.rank, issues.id"#,
+            issue_id,
+        )
+        .fetch_all(crate::pool())
+        .await?;
+
+        Ok(Self::build(issue_id, rows))
+    }
+
+    pub fn build(issue_id: i64, rows: Vec<IssueTreeRow>) -> Self {
+        let mut children_of: HashMap<i64, Vec<IssueTreeRow>> = HashMap::new();
+        for row in rows.into_iter().filter(|row| row.id != issue_id) {
+            children_of.entry(row.parent_id).or_default().push(row);
+        }
+
+        let children = build_children(issue_id, &mut children_of);
+        let mut rollup = TreeRollup::default();
+        for child in &children {
+            rollup.add(child);
+        }
+
+        Self {
+            issue_id,
+            children,
+            rollup,
+        }
+    }
+}
+
+/// Removing each row from the map as it's placed means an issue can only
+/// appear once, even if the rows somehow describe a loop
+fn build_children(
+    parent_id: i64,
+    children_of: &mut HashMap<i64, Vec<IssueTreeRow>>,
+) -> Vec<IssueTreeNode> {
+    let rows = children_of.remove(&parent_id).unwrap_or_default();
+    rows.into_iter()
+        .map(|issue| {
+            let children = build_children(issue.id, children_of);
+            let mut rollup = TreeRollup::default();
+            for child in &children {
+                rollup.add(child);
+            }
+            rollup.add_own_estimate(issue.estimate_hours, issue.completed_at.is_some());
+
+            IssueTreeNode {
+                issue,
+                children,
+                rollup,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolls_up_every_level() {
+        // 1
+        // ├── 2 (completed)
+        // │   ├── 4 (blocked)
+        // │   └── 5 (completed)
+        // │       └── 6
+        // └── 3
+        // Each issue is estimated at its id in hours
+        let open = IssueTreeRow {
+            id: 3,
+            parent_id: 1,
+            project_key: None,
+            number: None,
+            summary: String::from("Open"),
+            started_at: None,
+            completed_at: None,
+            blocked: false,
+            estimate_hours: Some(3),
+        };
+        let completed = IssueTreeRow {
+            summary: String::from("Completed"),
+            completed_at: Some(Utc::now()),
+            ..open.clone()
+        };
+        let tree = IssueTree::build(
+            1,
+            vec![
+                IssueTreeRow {
+                    id: 2,
+                    estimate_hours: Some(2),
+                    ..completed.clone()
+                },
+                open.clone(),
+                IssueTreeRow {
+                    id: 4,
+                    parent_id: 2,
+                    summary: String::from("Blocked"),
+                    blocked: true,
+                    estimate_hours: Some(4),
+                    ..open.clone()
+                },
+                IssueTreeRow {
+                    id: 5,
+                    parent_id: 2,
+                    estimate_hours: Some(5),
+                    ..completed
+                },
+                IssueTreeRow {
+                    id: 6,
+                    parent_id: 5,
+                    estimate_hours: Some(6),
+                    ..open
+                },
+            ],
+        );
+
+        assert_eq!(
+            tree.rollup,
+            TreeRollup {
+                total: 5,
+                completed: 2,
+                blocked: 1,
+                estimate_hours: 2 + 3 + 4 + 5 + 6,
+                remaining_estimate_hours: 3 + 4 + 6,
+            }
+        );
+
+        let second = &tree.children[0];
+        assert_eq!(second.issue.id, 2);
+        assert_eq!(
+            second.rollup,
+            TreeRollup {
+                total: 3,
+                completed: 1,
+                blocked: 1,
+                estimate_hours: 2 + 4 + 5 + 6,
+                remaining_estimate_hours: 4 + 6,
+            }
+        );
+        assert_eq!(second.children[1].children[0].issue.id, 6);
+        assert_eq!(tree.children[1].rollup.total, 0);
+    }
+
+    #[test]
+    fn loops_are_not_followed() {
+        let child = IssueTreeRow {
+            id: 2,
+            parent_id: 1,
+            project_key: None,
+            number: None,
+            summary: String::from("Child"),
+            started_at: None,
+            completed_at: None,
+            blocked: false,
+            estimate_hours: None,
+        };
+        let looped = IssueTreeRow {
+            id: 1,
+            parent_id: 2,
+            summary: String::from("Parent"),
+            ..child.clone()
+        };
+        let tree = IssueTree::build(1, vec![child, looped]);
+        assert_eq!(tree.rollup.total, 1);
+        assert!(tree.children[0].children.is_empty());
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::{HashMap, HashSet};
+
+use migrations::sqlx;
+
+use super::{Issue, IssueRevision};
+
+pub struct IssueWatcher;
+
+impl IssueWatcher {
+    /// Explicitly starts watching an issue
+    pub async fn watch<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_id: i64,
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        Self::set_watching(issue_id, account_id, true, executor).await
+    }
+
+    /// Explicitly stops watching an issue. This also prevents automatic subscriptions.
+    pub async fn unwatch<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_id: i64,
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        Self::set_watching(issue_id, account_id, false, executor).await
+    }
+
+    async fn set_watching<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_id: i64,
+        account_id: i64,
+        watching: bool,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"INSERT INTO issue_watchers (issue_id, account_id, watching) VALUES ($1, $2, $3)
+               ON CONFLICT (issue_id, account_id) DO UPDATE SET watching = $3"#,
+            issue_id,
+            account_id,
+            watching,
+        )
+        .execute(executor)
+        .await
+        .map(|_| ())
+    }
+
+    /// Subscribes an account to an issue unless they have previously chosen to stop watching it
+    pub async fn subscribe<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_id: i64,
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"INSERT INTO issue_watchers (issue_id, account_id, watching) VALUES ($1, $2, true)
+               ON CONFLICT (issue_id, account_id) DO NOTHING"#,
+            issue_id,
+            account_id,
+        )
+        .execute(executor)
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn is_watching<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        issue_id: i64,
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<bool> {
+        let row = sqlx::query!(
+            "SELECT watching FROM issue_watchers WHERE issue_id = $1 AND account_id = $2",
+            issue_id,
+            account_id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(row.map(|row| row.watching).unwrap_or_default())
+    }
+}
+
+pub struct ProjectWatcher;
+
+impl ProjectWatcher {
+    pub async fn watch<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        project_id: i64,
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        Self::set_watching(project_id, account_id, true, executor).await
+    }
+
+    pub async fn unwatch<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        project_id: i64,
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        Self::set_watching(project_id, account_id, false, executor).await
+    }
+
+    async fn set_watching<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        project_id: i64,
+        account_id: i64,
+        watching: bool,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"INSERT INTO project_watchers (project_id, account_id, watching) VALUES ($1, $2, $3)
+               ON CONFLICT (project_id, account_id) DO UPDATE SET watching = $3"#,
+            project_id,
+            account_id,
+            watching,
+        )
+        .execute(executor)
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn is_watching<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        project_id: i64,
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<bool> {
+        let row = sqlx::query!(
+            "SELECT watching FROM project_watchers WHERE project_id = $1 AND account_id = $2",
+            project_id,
+            account_id
+        )
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(row.map(|row| row.watching).unwrap_or_default())
+    }
+}
+
+pub struct Subscription;
+
+impl Subscription {
+    /// Returns the ids of the accounts that should be told about `revision`,
+    /// excluding the revision's author. Watchers of the issue, of any of its
+    /// parents, and of the projects those issues belong to are interested. When
+    /// someone has a watch setting at multiple levels, the setting closest to
+    /// the issue wins, so unwatching an issue silences a watched parent.
+    pub async fn interested_in(revision: &IssueRevision) -> sqlx::Result<Vec<i64>> {
+        Self::interested_in_issue(revision.issue_id, revision.author_id).await
+    }
+
+    /// Returns the ids of the accounts interested in changes to `issue_id`, excluding `actor_id`
+    pub async fn interested_in_issue(
+        issue_id: i64,
+        actor_id: Option<i64>,
+    ) -> sqlx::Result<Vec<i64>> {
+        let issue = Issue::load(issue_id).await?;
+        let mut hierarchy = Issue::all_parents(issue_id).await?;
+        // all_parents is ordered from the root to the direct parent
+        hierarchy.reverse();
+        hierarchy.insert(0, issue);
+
+        let issue_ids = hierarchy.iter().map(|i| i.id).collect::<Vec<_>>();
+        let project_ids = hierarchy
+            .iter()
+            .filter_map(|i| i.project_id)
+            .collect::<Vec<_>>();
+
+        let mut issue_settings = HashMap::<i64, Vec<(i64, bool)>>::new();
+        for row in sqlx::query!(
+            "SELECT issue_id, account_id, watching FROM issue_watchers WHERE issue_id = ANY($1)",
+            &issue_ids
+        )
+        .fetch_all(crate::pool())
+        .await?
+        {
+            issue_settings
+                .entry(row.issue_id)
+                .or_default()
+                .push((row.account_id, row.watching));
+        }
+
+        let mut project_settings = HashMap::<i64, Vec<(i64, bool)>>::new();
+        for row in sqlx::query!(
+            "SELECT project_id, account_id, watching FROM project_watchers WHERE project_id = ANY($1)",
+            &project_ids
+        )
+        .fetch_all(crate::pool())
+        .await?
+        {
+            project_settings
+                .entry(row.project_id)
+                .or_default()
+                .push((row.account_id, row.watching));
+        }
+
+        // Build the levels from closest to furthest: the issue, each parent, then the projects
+        let mut levels = Vec::new();
+        for id in &issue_ids {
+            levels.push(issue_settings.remove(id).unwrap_or_default());
+        }
+        let mut seen_projects = HashSet::new();
+        for id in project_ids {
+            if seen_projects.insert(id) {
+                levels.push(project_settings.remove(&id).unwrap_or_default());
+            }
+        }
+
+        Ok(resolve_interest(&levels, actor_id))
+    }
+}
+
+/// Given watch settings ordered from closest to furthest from an issue,
+/// returns the accounts whose closest setting is to watch.
+fn resolve_interest(levels: &[Vec<(i64, bool)>], actor_id: Option<i64>) -> Vec<i64> {
+    let mut decided = HashSet::new();
+    let mut interested = Vec::new();
+    for level in levels {
+        for &(account_id, watching) in level {
+            if decided.insert(account_id) && watching && Some(account_id) != actor_id {
+                interested.push(account_id);
+            }
+        }
+    }
+
+    interested.sort_unstable();
+    interested
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_interest;
+
+    #[test]
+    fn closest_setting_wins() {
+        let levels = vec![
+            // the issue
+            vec![(1, false), (2, true)],
+            // its parent
+            vec![(1, true), (3, true)],
+            // the project
+            vec![(3, false), (4, true)],
+        ];
+
+        assert_eq!(resolve_interest(&levels, None), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn actor_is_excluded() {
+        let levels = vec![vec![(1, true), (2, true)]];
+
+        assert_eq!(resolve_interest(&levels, Some(2)), vec![1]);
+    }
+}
@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx::{self, Done, FromRow, Transaction};
+
+use crate::{schema::issues::Resolution, DatabaseError, SqlxResultExt};
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct WorkflowState {
+    pub id: i64,
+    /// None for states in the site's default workflow
+    pub project_id: Option<i64>,
+    pub name: String,
+    pub position: i32,
+    /// New issues start in the initial state
+    pub initial: bool,
+    /// Issues in this state are being worked on
+    pub started: bool,
+    /// Issues in this state count as complete
+    pub completes: bool,
+}
+
+impl WorkflowState {
+    pub fn new(project_id: Option<i64>, name: String, position: i32) -> Self {
+        Self {
+            project_id,
+            name,
+            position,
+            id: 0,
+            initial: false,
+            started: false,
+            completes: false,
+        }
+    }
+
+    pub async fn load<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        state_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Self,
+            "SELECT id, project_id, name, position, initial, started, completes FROM workflow_states WHERE id = $1",
+            state_id
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    /// Loads every state of every workflow, for showing the state changes of
+    /// issues that have moved between projects
+    pub async fn list_as_map<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        executor: E,
+    ) -> sqlx::Result<HashMap<i64, Self>> {
+        Ok(sqlx::query_as!(
+            Self,
+            "SELECT id, project_id, name, position, initial, started, completes FROM workflow_states"
+        )
+        .fetch_all(executor)
+        .await?
+        .into_iter()
+        .map(|state| (state.id, state))
+        .collect())
+    }
+
+    pub async fn save<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        &mut self,
+        executor: E,
+    ) -> Result<(), DatabaseError> {
+        if self.id == 0 {
+            let row = sqlx::query!(
+                r#"INSERT INTO workflow_states (project_id, name, position, initial, started, completes)
+                   VALUES ($1, $2, $3, $4, $5, $6) RETURNING id"#,
+                self.project_id,
+                &self.name,
+                self.position,
+                self.initial,
+                self.started,
+                self.completes,
+            )
+            .fetch_one(executor)
+            .await
+            .map_database_error()?;
+            self.id = row.id;
+        } else {
+            let result = sqlx::query!(
+                r#"UPDATE workflow_states SET name = $2, position = $3, initial = $4, started = $5, completes = $6
+                   WHERE id = $1"#,
+                self.id,
+                &self.name,
+                self.position,
+                self.initial,
+                self.started,
+                self.completes,
+            )
+            .execute(executor)
+            .await
+            .map_database_error()?;
+            if result.rows_affected() == 0 {
+                return Err(DatabaseError::RowNotFound);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether any issues are in a state
+    pub async fn in_use<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        state_id: i64,
+        executor: E,
+    ) -> sqlx::Result<bool> {
+        let row = sqlx::query!(
+            r#"SELECT EXISTS (SELECT 1 FROM issues WHERE workflow_state_id = $1) as "in_use!""#,
+            state_id
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(row.in_use)
+    }
+
+    /// Deletes a state, returning false if any issues are still in it
+    pub async fn delete_if_unused<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        state_id: i64,
+        executor: E,
+    ) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"DELETE FROM workflow_states WHERE id = $1
+               AND NOT EXISTS (SELECT 1 FROM issues WHERE workflow_state_id = $1)"#,
+            state_id
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn set_transition<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        from_state_id: i64,
+        to_state_id: i64,
+        allowed: bool,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        if allowed {
+            sqlx::query!(
+                r#"INSERT INTO workflow_transitions (from_state_id, to_state_id) VALUES ($1, $2)
+                   ON CONFLICT DO NOTHING"#,
+                from_state_id,
+                to_state_id
+            )
+            .execute(executor)
+            .await?;
+        } else {
+            sqlx::query!(
+                "DELETE FROM workflow_transitions WHERE from_state_id = $1 AND to_state_id = $2",
+                from_state_id,
+                to_state_id
+            )
+            .execute(executor)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// The states an issue moves between, and which moves are allowed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workflow {
+    /// None if this is the site's default workflow
+    pub project_id: Option<i64>,
+    /// Ordered by position
+    pub states: Vec<WorkflowState>,
+    /// The states each state can move to
+    pub transitions: HashMap<i64, Vec<i64>>,
+}
+
+impl Workflow {
+    /// Loads the workflow used by issues in `project_id`. Projects that
+    /// haven't customized their workflow use the site's default workflow.
+    pub async fn for_project(project_id: Option<i64>) -> sqlx::Result<Self> {
+        if project_id.is_some() {
+            let workflow = Self::load(project_id).await?;
+            if !workflow.states.is_empty() {
+                return Ok(workflow);
+            }
+        }
+
+        Self::load(None).await
+    }
+
+    /// Loads the states defined for exactly `project_id`, which is empty if
+    /// the project uses the default workflow
+    pub async fn load(project_id: Option<i64>) -> sqlx::Result<Self> {
+        let states = sqlx::query_as!(
+            WorkflowState,
+            r#"SELECT id, project_id, name, position, initial, started, completes FROM workflow_states
+               WHERE project_id IS NOT DISTINCT FROM $1
+               ORDER BY position, id"#,
+            project_id
+        )
+        .fetch_all(crate::pool())
+        .await?;
+
+        let mut transitions = HashMap::<i64, Vec<i64>>::new();
+        for row in sqlx::query!(
+            r#"SELECT from_state_id, to_state_id FROM workflow_transitions
+               INNER JOIN workflow_states ON workflow_states.id = from_state_id
+               WHERE workflow_states.project_id IS NOT DISTINCT FROM $1"#,
+            project_id
+        )
+        .fetch_all(crate::pool())
+        .await?
+        {
+            transitions
+                .entry(row.from_state_id)
+                .or_default()
+                .push(row.to_state_id);
+        }
+
+        Ok(Self {
+            project_id,
+            states,
+            transitions,
+        })
+    }
+
+    pub fn state(&self, state_id: i64) -> Option<&WorkflowState> {
+        self.states.iter().find(|state| state.id == state_id)
+    }
+
+    pub fn state_named(&self, name: &str) -> Option<&WorkflowState> {
+        self.states
+            .iter()
+            .find(|state| state.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn initial_state(&self) -> Option<&WorkflowState> {
+        self.states
+            .iter()
+            .find(|state| state.initial)
+            .or_else(|| self.states.first())
+    }
+
+    /// Returns whether an issue in `from` may move to `to`. Issues without a
+    /// state in this workflow, such as those that just moved from another
+    /// project, may move to any state.
+    pub fn can_transition(&self, from: Option<i64>, to: i64) -> bool {
+        match from.and_then(|from| self.state(from)) {
+            Some(from) if from.id == to => true,
+            Some(from) => self
+                .transitions
+                .get(&from.id)
+                .map(|allowed| allowed.contains(&to))
+                .unwrap_or_default(),
+            None => self.state(to).is_some(),
+        }
+    }
+
+    /// The states an issue in `from` may be moved to, including `from` itself
+    pub fn next_states(&self, from: Option<i64>) -> Vec<WorkflowState> {
+        self.states
+            .iter()
+            .filter(|state| self.can_transition(from, state.id))
+            .cloned()
+            .collect()
+    }
+
+    /// Finds the state in this workflow that corresponds to `state`, which
+    /// may belong to another project's workflow. States are matched by name,
+    /// falling back to the first state with the same completion semantics.
+    pub fn corresponding_state(&self, state: &WorkflowState) -> Option<&WorkflowState> {
+        self.state(state.id)
+            .or_else(|| self.state_named(&state.name))
+            .or_else(|| {
+                self.states.iter().find(|candidate| {
+                    candidate.completes == state.completes && candidate.started == state.started
+                })
+            })
+            .or_else(|| self.initial_state())
+    }
+
+    /// Gives a project its own copy of the default workflow. Issues in the
+    /// project are moved to the copies of their states.
+    pub async fn customize(
+        project_id: i64,
+        tx: &mut Transaction<'_, sqlx::Postgres>,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"INSERT INTO workflow_states (project_id, name, position, initial, started, completes)
+               SELECT $1, name, position, initial, started, completes FROM workflow_states
+               WHERE project_id IS NULL"#,
+            project_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"INSERT INTO workflow_transitions (from_state_id, to_state_id)
+               SELECT project_from.id, project_to.id FROM workflow_transitions
+               INNER JOIN workflow_states default_from ON default_from.id = from_state_id
+               INNER JOIN workflow_states default_to ON default_to.id = to_state_id
+               INNER JOIN workflow_states project_from ON project_from.project_id = $1 AND project_from.name = default_from.name
+               INNER JOIN workflow_states project_to ON project_to.project_id = $1 AND project_to.name = default_to.name
+               WHERE default_from.project_id IS NULL"#,
+            project_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        Self::move_project_issues(project_id, Some(project_id), tx).await
+    }
+
+    /// Removes a project's custom workflow. Issues in the project are moved to
+    /// the default workflow's states with the same names, or its initial state.
+    pub async fn reset_to_default(
+        project_id: i64,
+        tx: &mut Transaction<'_, sqlx::Postgres>,
+    ) -> sqlx::Result<()> {
+        Self::move_project_issues(project_id, None, tx).await?;
+        sqlx::query!(
+            "DELETE FROM workflow_states WHERE project_id = $1",
+            project_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        Ok(())
+    }
+
+    async fn move_project_issues(
+        project_id: i64,
+        workflow_project_id: Option<i64>,
+        tx: &mut Transaction<'_, sqlx::Postgres>,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"UPDATE issues SET workflow_state_id = COALESCE(
+                (SELECT target.id FROM workflow_states target
+                 INNER JOIN workflow_states current ON current.id = issues.workflow_state_id
+                 WHERE target.project_id IS NOT DISTINCT FROM $2 AND lower(target.name) = lower(current.name)),
+                (SELECT target.id FROM workflow_states target
+                 WHERE target.project_id IS NOT DISTINCT FROM $2
+                 ORDER BY target.completes = (issues.completed_at IS NOT NULL) DESC,
+                          target.initial DESC, target.position, target.id LIMIT 1)
+               )
+               WHERE issues.project_id = $1"#,
+            project_id,
+            workflow_project_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // A workflow without a matching state can still change whether an
+        // issue counts as started or complete, as editing the issue would
+        sqlx::query!(
+            r#"UPDATE issues SET
+                 started_at = CASE WHEN state.started THEN COALESCE(issues.started_at, now())
+                                   WHEN state.completes THEN issues.started_at END,
+                 completed_at = CASE WHEN state.completes THEN COALESCE(issues.completed_at, now()) END,
+                 resolution = CASE WHEN state.completes THEN COALESCE(issues.resolution, $2) END
+               FROM workflow_states state
+               WHERE state.id = issues.workflow_state_id AND issues.project_id = $1"#,
+            project_id,
+            Resolution::Fixed as i32,
+        )
+        .execute(&mut *tx)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        schema::issues::{Issue, Project},
+        test_helpers,
+    };
+    use chrono::Utc;
+
+    fn state(id: i64, name: &str, started: bool, completes: bool) -> WorkflowState {
+        WorkflowState {
+            id,
+            project_id: None,
+            name: name.to_string(),
+            position: id as i32,
+            initial: id == 1,
+            started,
+            completes,
+        }
+    }
+
+    fn workflow() -> Workflow {
+        let mut transitions = HashMap::new();
+        transitions.insert(1, vec![2]);
+        transitions.insert(2, vec![3]);
+        Workflow {
+            project_id: None,
+            states: vec![
+                state(1, "Triage", false, false),
+                state(2, "In Progress", true, false),
+                state(3, "Done", false, true),
+            ],
+            transitions,
+        }
+    }
+
+    #[test]
+    fn transitions_are_restricted() {
+        let workflow = workflow();
+        assert!(workflow.can_transition(Some(1), 2));
+        assert!(workflow.can_transition(Some(1), 1));
+        assert!(!workflow.can_transition(Some(1), 3));
+        assert!(!workflow.can_transition(Some(3), 1));
+        // Issues coming from another workflow can enter any state
+        assert!(workflow.can_transition(Some(42), 3));
+        assert!(workflow.can_transition(None, 3));
+        assert!(!workflow.can_transition(None, 42));
+        assert_eq!(
+            workflow
+                .next_states(Some(1))
+                .iter()
+                .map(|s| s.id)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn states_correspond_by_name_then_semantics() {
+        let workflow = workflow();
+        assert_eq!(
+            workflow
+                .corresponding_state(&state(10, "in progress", true, false))
+                .unwrap()
+                .id,
+            2
+        );
+        assert_eq!(
+            workflow
+                .corresponding_state(&state(11, "Won't Fix", false, true))
+                .unwrap()
+                .id,
+            3
+        );
+        assert_eq!(
+            workflow
+                .corresponding_state(&state(12, "Icebox", false, false))
+                .unwrap()
+                .id,
+            1
+        );
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn resetting_keeps_completed_issues_complete() -> anyhow::Result<()> {
+        let mut tx = test_helpers::begin().await?;
+        let account = test_helpers::setup_test_account(&mut tx).await?;
+        let mut project = Project::new(
+            String::from("workflow-reset"),
+            String::from("WFRESET"),
+            String::from("Workflow Reset"),
+            None,
+            account.id,
+        );
+        project.save(&mut tx).await?;
+        Workflow::customize(project.id, &mut tx).await?;
+
+        // A completing state the default workflow has no counterpart for
+        let mut shipped = WorkflowState::new(Some(project.id), String::from("Shipped"), 100);
+        shipped.completes = true;
+        shipped.save(&mut tx).await?;
+        let mut issue = Issue::new(
+            account.id,
+            String::from("Shipped"),
+            None,
+            None,
+            Some(project.id),
+        );
+        issue.workflow_state_id = Some(shipped.id);
+        issue.completed_at = Some(Utc::now());
+        issue.save(&mut tx).await?;
+
+        Workflow::reset_to_default(project.id, &mut tx).await?;
+        let issue = Issue::load_for_update(issue.id, &mut tx).await?;
+        let state = WorkflowState::load(issue.workflow_state_id.unwrap(), &mut tx).await?;
+        assert_eq!(state.project_id, None);
+        assert!(state.completes);
+        assert!(issue.completed_at.is_some());
+
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
 pub mod accounts;
+pub mod attachments;
 pub mod cms;
 pub mod issues;
+pub mod notifications;
+pub mod webhooks;
@@ -0,0 +1,223 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DigestSchedule {
+    None,
+    Daily,
+    Weekly,
+}
+
+impl DigestSchedule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestSchedule::None => "none",
+            DigestSchedule::Daily => "daily",
+            DigestSchedule::Weekly => "weekly",
+        }
+    }
+}
+
+impl FromStr for DigestSchedule {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(DigestSchedule::None),
+            "daily" => Ok(DigestSchedule::Daily),
+            "weekly" => Ok(DigestSchedule::Weekly),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DigestSettings {
+    pub account_id: i64,
+    pub email: Option<String>,
+    pub schedule: DigestSchedule,
+    pub timezone: Option<String>,
+    pub last_sent_at: Option<DateTime<Utc>>,
+}
+
+impl DigestSettings {
+    pub async fn load<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Self> {
+        let row = sqlx::query!(
+            "SELECT id, email, digest_schedule, timezone, digest_last_sent_at FROM accounts WHERE id = $1",
+            account_id
+        )
+        .fetch_one(executor)
+        .await?;
+
+        Ok(Self {
+            account_id: row.id,
+            email: row.email,
+            schedule: DigestSchedule::from_str(&row.digest_schedule)
+                .unwrap_or(DigestSchedule::None),
+            timezone: row.timezone,
+            last_sent_at: row.digest_last_sent_at,
+        })
+    }
+
+    /// Lists the accounts that have chosen to receive digests and have an email address
+    pub async fn list_subscribed<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        executor: E,
+    ) -> sqlx::Result<Vec<Self>> {
+        Ok(sqlx::query!(
+            r#"SELECT id, email, digest_schedule, timezone, digest_last_sent_at FROM accounts
+               WHERE digest_schedule <> 'none' AND email IS NOT NULL"#
+        )
+        .fetch_all(executor)
+        .await?
+        .into_iter()
+        .filter_map(|row| {
+            Some(Self {
+                account_id: row.id,
+                email: row.email,
+                schedule: DigestSchedule::from_str(&row.digest_schedule).ok()?,
+                timezone: row.timezone,
+                last_sent_at: row.digest_last_sent_at,
+            })
+        })
+        .collect())
+    }
+
+    pub async fn update<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        account_id: i64,
+        schedule: DigestSchedule,
+        timezone: Option<&str>,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE accounts SET digest_schedule = $2, timezone = $3 WHERE id = $1",
+            account_id,
+            schedule.as_str(),
+            timezone,
+        )
+        .execute(executor)
+        .await
+        .map(|_| ())
+    }
+
+    /// Records when a digest was sent. This should happen in the same
+    /// transaction that queues the digest so that it's never sent twice.
+    pub async fn mark_sent<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        account_id: i64,
+        sent_at: DateTime<Utc>,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE accounts SET digest_last_sent_at = $2 WHERE id = $1",
+            account_id,
+            sent_at,
+        )
+        .execute(executor)
+        .await
+        .map(|_| ())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestIssue {
+    pub id: i64,
+    pub project_key: Option<String>,
+    pub number: Option<i32>,
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DigestContents {
+    /// Issues created in projects the account watches
+    pub new_issues: Vec<DigestIssue>,
+    /// Watched issues that someone else revised
+    pub changed_issues: Vec<DigestIssue>,
+    /// Watched, open issues whose blocking issues were completed
+    pub unblocked_issues: Vec<DigestIssue>,
+}
+
+impl DigestContents {
+    pub fn is_empty(&self) -> bool {
+        self.new_issues.is_empty()
+            && self.changed_issues.is_empty()
+            && self.unblocked_issues.is_empty()
+    }
+
+    /// Gathers the activity between `since` (exclusive) and `until` (inclusive)
+    pub async fn load(
+        account_id: i64,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> sqlx::Result<Self> {
+        let new_issues = sqlx::query_as!(
+            DigestIssue,
+            r#"SELECT issues.id, projects.key as "project_key?", issues.number, issues.summary FROM issues
+               LEFT OUTER JOIN projects ON projects.id = issues.project_id
+               INNER JOIN project_watchers ON project_watchers.project_id = issues.project_id
+               WHERE project_watchers.account_id = $1 AND project_watchers.watching
+                 AND issues.author_id <> $1
+                 AND issues.created_at > $2 AND issues.created_at <= $3
+               ORDER BY issues.id"#,
+            account_id,
+            since,
+            until,
+        )
+        .fetch_all(crate::pool());
+
+        let changed_issues = sqlx::query_as!(
+            DigestIssue,
+            r#"SELECT issues.id, projects.key as "project_key?", issues.number, issues.summary FROM issues
+               LEFT OUTER JOIN projects ON projects.id = issues.project_id
+               INNER JOIN issue_watchers ON issue_watchers.issue_id = issues.id
+               WHERE issue_watchers.account_id = $1 AND issue_watchers.watching
+                 AND EXISTS (
+                    SELECT 1 FROM issue_revisions
+                    WHERE issue_revisions.issue_id = issues.id
+                      AND issue_revisions.author_id IS DISTINCT FROM $1
+                      AND issue_revisions.created_at > $2 AND issue_revisions.created_at <= $3
+                 )
+               ORDER BY issues.id"#,
+            account_id,
+            since,
+            until,
+        )
+        .fetch_all(crate::pool());
+
+        let unblocked_issues = sqlx::query_as!(
+            DigestIssue,
+            r#"SELECT issues.id, projects.key as "project_key?", issues.number, issues.summary FROM issues
+               LEFT OUTER JOIN projects ON projects.id = issues.project_id
+               INNER JOIN issue_watchers ON issue_watchers.issue_id = issues.id
+               WHERE issue_watchers.account_id = $1 AND issue_watchers.watching
+                 AND NOT issues.blocked AND issues.completed_at IS NULL
+                 AND EXISTS (
+                    SELECT 1 FROM issue_relationships
+                    INNER JOIN issues blocker ON blocker.id = issue_relationships.issue_a
+                    WHERE issue_relationships.issue_b = issues.id
+                      AND issue_relationships.relationship = 'blocks'
+                      AND blocker.completed_at > $2 AND blocker.completed_at <= $3
+                 )
+               ORDER BY issues.id"#,
+            account_id,
+            since,
+            until,
+        )
+        .fetch_all(crate::pool());
+
+        let (new_issues, changed_issues, unblocked_issues) =
+            futures::try_join!(new_issues, changed_issues, unblocked_issues)?;
+
+        Ok(Self {
+            new_issues,
+            changed_issues,
+            unblocked_issues,
+        })
+    }
+}
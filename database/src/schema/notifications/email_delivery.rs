@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx;
+
+/// An email queued for delivery. Rows are retained after sending so that failures can be audited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailDelivery {
+    pub id: i64,
+    pub account_id: Option<i64>,
+    pub to_address: String,
+    pub subject: String,
+    pub body: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EmailDelivery {
+    pub async fn enqueue<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        account_id: Option<i64>,
+        to_address: &str,
+        subject: &str,
+        body: &str,
+        executor: E,
+    ) -> sqlx::Result<i64> {
+        let row = sqlx::query!(
+            r#"INSERT INTO email_deliveries (account_id, to_address, subject, body)
+               VALUES ($1, $2, $3, $4) RETURNING id"#,
+            account_id,
+            to_address,
+            subject,
+            body,
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(row.id)
+    }
+
+    /// Lists the unsent deliveries that are due to be attempted, oldest first
+    pub async fn list_pending<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        maximum_attempts: i32,
+        limit: i64,
+        executor: E,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                id,
+                account_id,
+                to_address,
+                subject,
+                body,
+                attempts,
+                last_error,
+                next_attempt_at,
+                sent_at,
+                created_at
+               FROM email_deliveries
+               WHERE sent_at IS NULL AND attempts < $1 AND next_attempt_at <= now()
+               ORDER BY next_attempt_at
+               LIMIT $2"#,
+            maximum_attempts,
+            limit,
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn mark_sent<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        id: i64,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE email_deliveries SET sent_at = now(), attempts = attempts + 1, last_error = NULL WHERE id = $1",
+            id
+        )
+        .execute(executor)
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn mark_failed<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        id: i64,
+        error: &str,
+        next_attempt_at: DateTime<Utc>,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "UPDATE email_deliveries SET attempts = attempts + 1, last_error = $2, next_attempt_at = $3 WHERE id = $1",
+            id,
+            error,
+            next_attempt_at,
+        )
+        .execute(executor)
+        .await
+        .map(|_| ())
+    }
+}
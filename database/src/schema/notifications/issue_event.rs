@@ -0,0 +1,58 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// The kinds of issue activity that watchers can be notified about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueEventKind {
+    Created,
+    Updated,
+    Commented,
+    Linked,
+    Unlinked,
+    Started,
+    Completed,
+    Reopened,
+    Mentioned,
+}
+
+impl IssueEventKind {
+    pub const ALL: [IssueEventKind; 9] = [
+        IssueEventKind::Created,
+        IssueEventKind::Updated,
+        IssueEventKind::Commented,
+        IssueEventKind::Linked,
+        IssueEventKind::Unlinked,
+        IssueEventKind::Started,
+        IssueEventKind::Completed,
+        IssueEventKind::Reopened,
+        IssueEventKind::Mentioned,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueEventKind::Created => "created",
+            IssueEventKind::Updated => "updated",
+            IssueEventKind::Commented => "commented",
+            IssueEventKind::Linked => "linked",
+            IssueEventKind::Unlinked => "unlinked",
+            IssueEventKind::Started => "started",
+            IssueEventKind::Completed => "completed",
+            IssueEventKind::Reopened => "reopened",
+            IssueEventKind::Mentioned => "mentioned",
+        }
+    }
+}
+
+impl FromStr for IssueEventKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|kind| kind.as_str() == s)
+            .copied()
+            .ok_or(())
+    }
+}
@@ -0,0 +1,7 @@
+mod digest;
+mod email_delivery;
+mod issue_event;
+mod notification;
+mod preference;
+
+pub use self::{digest::*, email_delivery::*, issue_event::*, notification::*, preference::*};
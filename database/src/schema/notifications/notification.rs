@@ -0,0 +1,142 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx::{self, Done};
+
+use super::IssueEventKind;
+use crate::schema::accounts::User;
+
+pub struct Notification;
+
+impl Notification {
+    pub async fn create<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        account_id: i64,
+        issue_id: i64,
+        actor_id: i64,
+        kind: IssueEventKind,
+        related_issue_id: Option<i64>,
+        executor: E,
+    ) -> sqlx::Result<i64> {
+        let row = sqlx::query!(
+            r#"INSERT INTO notifications (account_id, issue_id, actor_id, event_kind, related_issue_id)
+               VALUES ($1, $2, $3, $4, $5) RETURNING id"#,
+            account_id,
+            issue_id,
+            actor_id,
+            kind.as_str(),
+            related_issue_id,
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(row.id)
+    }
+
+    pub async fn unread_count<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<i64> {
+        let row = sqlx::query!(
+            r#"SELECT COUNT(*) as "count!" FROM notifications WHERE account_id = $1 AND read_at IS NULL"#,
+            account_id
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(row.count)
+    }
+
+    /// Marks a notification as read, returning the issue it refers to. Returns
+    /// RowNotFound if the notification doesn't belong to `account_id`.
+    pub async fn mark_read<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        id: i64,
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<i64> {
+        let row = sqlx::query!(
+            r#"UPDATE notifications SET read_at = COALESCE(read_at, now())
+               WHERE id = $1 AND account_id = $2 RETURNING issue_id"#,
+            id,
+            account_id,
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(row.issue_id)
+    }
+
+    pub async fn mark_all_read<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<u64> {
+        let result = sqlx::query!(
+            "UPDATE notifications SET read_at = now() WHERE account_id = $1 AND read_at IS NULL",
+            account_id,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationView {
+    pub id: i64,
+    pub issue_id: i64,
+    pub issue_summary: String,
+    pub actor: User,
+    pub event_kind: String,
+    pub related_issue_id: Option<i64>,
+    pub related_issue_summary: Option<String>,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl NotificationView {
+    /// Lists the most recent notifications for an account, newest first
+    pub async fn list_for<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        account_id: i64,
+        limit: i64,
+        executor: E,
+    ) -> sqlx::Result<Vec<Self>> {
+        Ok(sqlx::query!(
+            r#"SELECT
+                notifications.id,
+                notifications.issue_id,
+                issues.summary as issue_summary,
+                accounts.id as actor_id,
+                accounts.username as actor_username,
+                accounts.display_name as actor_display_name,
+                notifications.event_kind,
+                notifications.related_issue_id,
+                related.summary as "related_issue_summary?",
+                notifications.read_at,
+                notifications.created_at
+               FROM notifications
+               INNER JOIN issues ON issues.id = notifications.issue_id
+               INNER JOIN accounts ON accounts.id = notifications.actor_id
+               LEFT OUTER JOIN issues related ON related.id = notifications.related_issue_id
+               WHERE notifications.account_id = $1
+               ORDER BY notifications.created_at DESC
+               LIMIT $2"#,
+            account_id,
+            limit,
+        )
+        .fetch_all(executor)
+        .await?
+        .into_iter()
+        .map(|row| Self {
+            id: row.id,
+            issue_id: row.issue_id,
+            issue_summary: row.issue_summary,
+            actor: User {
+                id: row.actor_id,
+                username: row.actor_username,
+                display_name: row.actor_display_name,
+            },
+            event_kind: row.event_kind,
+            related_issue_id: row.related_issue_id,
+            related_issue_summary: row.related_issue_summary,
+            read_at: row.read_at,
+            created_at: row.created_at,
+        })
+        .collect())
+    }
+}
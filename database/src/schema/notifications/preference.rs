@@ -0,0 +1,80 @@
+use std::{collections::HashMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx;
+
+use super::IssueEventKind;
+
+/// Where notifications for an event kind are delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub inbox: bool,
+    pub email: bool,
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        Self {
+            inbox: true,
+            email: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationPreferences {
+    pub deliveries: HashMap<IssueEventKind, Delivery>,
+}
+
+impl NotificationPreferences {
+    pub async fn load<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        account_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Self> {
+        let mut deliveries = HashMap::new();
+        for row in sqlx::query!(
+            "SELECT event_kind, inbox, email FROM notification_preferences WHERE account_id = $1",
+            account_id
+        )
+        .fetch_all(executor)
+        .await?
+        {
+            // Rows for event kinds that no longer exist are ignored
+            if let Ok(kind) = IssueEventKind::from_str(&row.event_kind) {
+                deliveries.insert(
+                    kind,
+                    Delivery {
+                        inbox: row.inbox,
+                        email: row.email,
+                    },
+                );
+            }
+        }
+
+        Ok(Self { deliveries })
+    }
+
+    pub fn delivery_for(&self, kind: IssueEventKind) -> Delivery {
+        self.deliveries.get(&kind).copied().unwrap_or_default()
+    }
+
+    pub async fn set<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        account_id: i64,
+        kind: IssueEventKind,
+        delivery: Delivery,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"INSERT INTO notification_preferences (account_id, event_kind, inbox, email) VALUES ($1, $2, $3, $4)
+               ON CONFLICT (account_id, event_kind) DO UPDATE SET inbox = $3, email = $4"#,
+            account_id,
+            kind.as_str(),
+            delivery.inbox,
+            delivery.email,
+        )
+        .execute(executor)
+        .await
+        .map(|_| ())
+    }
+}
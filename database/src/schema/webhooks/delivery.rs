@@ -0,0 +1,147 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub event_kind: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A pending delivery along with where it's being sent
+#[derive(Debug, Clone)]
+pub struct PendingWebhookDelivery {
+    pub id: i64,
+    pub event_kind: String,
+    pub attempts: i32,
+    pub payload: String,
+    pub url: String,
+    pub secret: String,
+}
+
+impl WebhookDelivery {
+    pub async fn enqueue<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        webhook_id: i64,
+        event_kind: &str,
+        payload: &str,
+        executor: E,
+    ) -> sqlx::Result<i64> {
+        let row = sqlx::query!(
+            r#"INSERT INTO webhook_deliveries (webhook_id, event_kind, payload)
+               VALUES ($1, $2, $3) RETURNING id"#,
+            webhook_id,
+            event_kind,
+            payload,
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(row.id)
+    }
+
+    /// Lists the undelivered payloads for active webhooks that are due to be attempted
+    pub async fn list_pending<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        maximum_attempts: i32,
+        limit: i64,
+        executor: E,
+    ) -> sqlx::Result<Vec<PendingWebhookDelivery>> {
+        sqlx::query_as!(
+            PendingWebhookDelivery,
+            r#"SELECT
+                webhook_deliveries.id,
+                webhook_deliveries.event_kind,
+                webhook_deliveries.attempts,
+                webhook_deliveries.payload,
+                webhooks.url,
+                webhooks.secret
+               FROM webhook_deliveries
+               INNER JOIN webhooks ON webhooks.id = webhook_deliveries.webhook_id
+               WHERE webhooks.active
+                 AND webhook_deliveries.delivered_at IS NULL
+                 AND webhook_deliveries.attempts < $1
+                 AND webhook_deliveries.next_attempt_at <= now()
+               ORDER BY webhook_deliveries.next_attempt_at
+               LIMIT $2"#,
+            maximum_attempts,
+            limit,
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Lists the most recent deliveries for a webhook, newest first
+    pub async fn list_recent<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        webhook_id: i64,
+        limit: i64,
+        executor: E,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                id,
+                webhook_id,
+                event_kind,
+                payload,
+                attempts,
+                response_status,
+                last_error,
+                next_attempt_at,
+                delivered_at,
+                created_at
+               FROM webhook_deliveries
+               WHERE webhook_id = $1
+               ORDER BY created_at DESC
+               LIMIT $2"#,
+            webhook_id,
+            limit,
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn mark_delivered<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        id: i64,
+        response_status: i32,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"UPDATE webhook_deliveries
+               SET delivered_at = now(), attempts = attempts + 1, response_status = $2, last_error = NULL
+               WHERE id = $1"#,
+            id,
+            response_status,
+        )
+        .execute(executor)
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn mark_failed<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        id: i64,
+        response_status: Option<i32>,
+        error: &str,
+        next_attempt_at: DateTime<Utc>,
+        executor: E,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"UPDATE webhook_deliveries
+               SET attempts = attempts + 1, response_status = $2, last_error = $3, next_attempt_at = $4
+               WHERE id = $1"#,
+            id,
+            response_status,
+            error,
+            next_attempt_at,
+        )
+        .execute(executor)
+        .await
+        .map(|_| ())
+    }
+}
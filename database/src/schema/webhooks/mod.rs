@@ -0,0 +1,4 @@
+mod delivery;
+mod webhook;
+
+pub use self::{delivery::*, webhook::*};
@@ -0,0 +1,141 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use migrations::sqlx::{self, Done};
+
+use crate::{DatabaseError, SqlxResultExt};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: i64,
+    /// When None, the webhook receives events for issues in every project
+    pub project_id: Option<i64>,
+    pub url: String,
+    pub secret: String,
+    /// The `IssueEventKind`s this webhook is sent
+    pub events: Vec<String>,
+    pub active: bool,
+    pub created_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub fn new(
+        project_id: Option<i64>,
+        url: String,
+        secret: String,
+        events: Vec<String>,
+        created_by: i64,
+    ) -> Self {
+        Self {
+            project_id,
+            url,
+            secret,
+            events,
+            created_by,
+            id: 0,
+            active: true,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub async fn load<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        webhook_id: i64,
+        executor: E,
+    ) -> sqlx::Result<Self> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, project_id, url, secret, events, active, created_by, created_at
+               FROM webhooks WHERE id = $1"#,
+            webhook_id
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    /// Lists the webhooks registered for a project, or the site-wide webhooks if `project_id` is None
+    pub async fn list_for_project<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        project_id: Option<i64>,
+        executor: E,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, project_id, url, secret, events, active, created_by, created_at
+               FROM webhooks WHERE project_id IS NOT DISTINCT FROM $1
+               ORDER BY id"#,
+            project_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Lists the active webhooks that should receive `event_kind` for an issue in `project_id`
+    pub async fn list_subscribed<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        project_id: Option<i64>,
+        event_kind: &str,
+        executor: E,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT id, project_id, url, secret, events, active, created_by, created_at
+               FROM webhooks
+               WHERE active AND $2 = ANY(events) AND (project_id IS NULL OR project_id = $1)
+               ORDER BY id"#,
+            project_id,
+            event_kind,
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn save<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        &mut self,
+        executor: E,
+    ) -> Result<(), DatabaseError> {
+        if self.id == 0 {
+            let row = sqlx::query!(
+                r#"INSERT INTO webhooks (project_id, url, secret, events, active, created_by)
+                   VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, created_at"#,
+                self.project_id,
+                &self.url,
+                &self.secret,
+                &self.events,
+                self.active,
+                self.created_by,
+            )
+            .fetch_one(executor)
+            .await
+            .map_database_error()?;
+
+            self.id = row.id;
+            self.created_at = row.created_at;
+        } else {
+            let result = sqlx::query!(
+                "UPDATE webhooks SET url = $2, secret = $3, events = $4, active = $5 WHERE id = $1",
+                self.id,
+                &self.url,
+                &self.secret,
+                &self.events,
+                self.active,
+            )
+            .execute(executor)
+            .await
+            .map_database_error()?;
+            if result.rows_affected() == 0 {
+                return Err(DatabaseError::RowNotFound);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete<'e, E: sqlx::Executor<'e, Database = sqlx::Postgres>>(
+        webhook_id: i64,
+        executor: E,
+    ) -> sqlx::Result<u64> {
+        let result = sqlx::query!("DELETE FROM webhooks WHERE id = $1", webhook_id)
+            .execute(executor)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,41 @@
+//! Helpers for tests that use the database. Those tests are `#[ignore]`d so
+//! that a plain `cargo test` reports them as skipped rather than passing
+//! without a database. Run them with `cargo test -- --ignored` and
+//! `DATABASE_URL` set.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use sqlx::{Postgres, Transaction};
+
+use crate::{schema::accounts::Account, sqlx};
+
+pub const TEST_ACCOUNT_USERNAME: &str = "testuser";
+pub const TEST_ACCOUNT_PASSWORD: &str = "testpassword";
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+pub async fn initialize() {
+    // Only call the async initialization code the first time through
+    if !INITIALIZED.swap(true, Ordering::SeqCst) {
+        let _ = dotenv::dotenv();
+        crate::initialize().await;
+    }
+}
+
+/// Starts a transaction against the database in `DATABASE_URL`. Nothing is
+/// committed, so each test's changes are rolled back.
+pub async fn begin() -> anyhow::Result<Transaction<'static, Postgres>> {
+    initialize().await;
+    crate::migrations::run_all()
+        .await
+        .expect("error executing database migrations");
+    Ok(crate::pool().begin().await?)
+}
+
+pub async fn setup_test_account(
+    transaction: &mut Transaction<'_, Postgres>,
+) -> anyhow::Result<Account> {
+    let mut account = Account::new(TEST_ACCOUNT_USERNAME, TEST_ACCOUNT_PASSWORD, false)?;
+    account.save(transaction).await?;
+    Ok(account)
+}
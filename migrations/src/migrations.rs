@@ -8,6 +8,27 @@ mod migration_0007_blocking_fix;
 mod migration_0008_started_at;
 mod migration_0009_session_cleanup;
 mod migration_0010_tags;
+mod migration_0011_attachments;
+mod migration_0012_avatars;
+mod migration_0013_watchers;
+mod migration_0014_email;
+mod migration_0015_notifications;
+mod migration_0016_digests;
+mod migration_0017_webhooks;
+mod migration_0018_issue_commits;
+mod migration_0019_workflows;
+mod migration_0020_resolutions;
+mod migration_0021_relationship_kinds;
+mod migration_0022_blocked_triggers;
+mod migration_0023_estimates;
+mod migration_0024_issue_ranks;
+mod migration_0025_project_keys;
+mod migration_0026_slug_history;
+mod migration_0027_milestones;
+mod migration_0028_iterations;
+mod migration_0029_project_key_history;
+mod migration_0030_article_slug_history;
+mod migration_0031_system_revisions;
 
 use crate::connection::pool;
 use sqlx_simple_migrator::{Migration, MigrationError};
@@ -24,6 +45,27 @@ pub fn migrations() -> Vec<Migration> {
         migration_0008_started_at::migration(),
         migration_0009_session_cleanup::migration(),
         migration_0010_tags::migration(),
+        migration_0011_attachments::migration(),
+        migration_0012_avatars::migration(),
+        migration_0013_watchers::migration(),
+        migration_0014_email::migration(),
+        migration_0015_notifications::migration(),
+        migration_0016_digests::migration(),
+        migration_0017_webhooks::migration(),
+        migration_0018_issue_commits::migration(),
+        migration_0019_workflows::migration(),
+        migration_0020_resolutions::migration(),
+        migration_0021_relationship_kinds::migration(),
+        migration_0022_blocked_triggers::migration(),
+        migration_0023_estimates::migration(),
+        migration_0024_issue_ranks::migration(),
+        migration_0025_project_keys::migration(),
+        migration_0026_slug_history::migration(),
+        migration_0027_milestones::migration(),
+        migration_0028_iterations::migration(),
+        migration_0029_project_key_history::migration(),
+        migration_0030_article_slug_history::migration(),
+        migration_0031_system_revisions::migration(),
     ]
 }
 
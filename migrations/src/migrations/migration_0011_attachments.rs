@@ -0,0 +1,25 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(
+            r#"CREATE TABLE attachments (
+            id BIGSERIAL PRIMARY KEY,
+            author_id BIGINT NOT NULL REFERENCES accounts(id),
+            filename TEXT NOT NULL,
+            content_type TEXT NOT NULL,
+            size BIGINT NOT NULL,
+            storage_key TEXT NOT NULL UNIQUE,
+            thumbnail_key TEXT NULL,
+            issue_id BIGINT NULL REFERENCES issues(id) ON DELETE CASCADE,
+            issue_revision_id BIGINT NULL REFERENCES issue_revisions(id) ON DELETE CASCADE,
+            article_slug TEXT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS attachments"#)
+        .with_up(r#"CREATE INDEX attachments_issue_id ON attachments(issue_id)"#)
+        .with_down(r#"DROP INDEX IF EXISTS attachments_issue_id"#)
+        .with_up(r#"CREATE INDEX attachments_article_slug ON attachments(article_slug)"#)
+        .with_down(r#"DROP INDEX IF EXISTS attachments_article_slug"#)
+}
@@ -0,0 +1,9 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(r#"ALTER TABLE accounts ADD COLUMN avatar_key TEXT NULL"#)
+        .with_down(r#"ALTER TABLE accounts DROP COLUMN IF EXISTS avatar_key"#)
+        .with_up(r#"ALTER TABLE accounts ADD COLUMN avatar_updated_at TIMESTAMPTZ NULL"#)
+        .with_down(r#"ALTER TABLE accounts DROP COLUMN IF EXISTS avatar_updated_at"#)
+}
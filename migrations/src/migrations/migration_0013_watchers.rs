@@ -0,0 +1,35 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        // watching is false when someone has explicitly stopped watching, which
+        // prevents them from being automatically subscribed again.
+        .with_up(
+            r#"CREATE TABLE issue_watchers (
+            issue_id BIGINT NOT NULL REFERENCES issues(id) ON DELETE CASCADE,
+            account_id BIGINT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+            watching BOOL NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            PRIMARY KEY (issue_id, account_id)
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS issue_watchers"#)
+        .with_up(
+            r#"CREATE TABLE project_watchers (
+            project_id BIGINT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            account_id BIGINT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+            watching BOOL NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            PRIMARY KEY (project_id, account_id)
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS project_watchers"#)
+        // Existing authors and commenters are subscribed to their issues
+        .with_up(
+            r#"INSERT INTO issue_watchers (issue_id, account_id, watching)
+            SELECT id, author_id, true FROM issues
+            UNION
+            SELECT issue_id, author_id, true FROM issue_revisions WHERE comment IS NOT NULL
+            ON CONFLICT DO NOTHING"#,
+        )
+}
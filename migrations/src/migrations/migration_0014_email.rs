@@ -0,0 +1,26 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(r#"ALTER TABLE accounts ADD COLUMN email TEXT NULL"#)
+        .with_down(r#"ALTER TABLE accounts DROP COLUMN IF EXISTS email"#)
+        .with_up(
+            r#"CREATE TABLE email_deliveries (
+            id BIGSERIAL PRIMARY KEY,
+            account_id BIGINT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+            to_address TEXT NOT NULL,
+            subject TEXT NOT NULL,
+            body TEXT NOT NULL,
+            attempts INT NOT NULL DEFAULT 0,
+            last_error TEXT NULL,
+            next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            sent_at TIMESTAMPTZ NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS email_deliveries"#)
+        .with_up(
+            r#"CREATE INDEX email_deliveries_pending ON email_deliveries(next_attempt_at) WHERE sent_at IS NULL"#,
+        )
+        .with_down(r#"DROP INDEX IF EXISTS email_deliveries_pending"#)
+}
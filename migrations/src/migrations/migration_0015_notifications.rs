@@ -0,0 +1,35 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(
+            r#"CREATE TABLE notifications (
+            id BIGSERIAL PRIMARY KEY,
+            account_id BIGINT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+            issue_id BIGINT NOT NULL REFERENCES issues(id) ON DELETE CASCADE,
+            actor_id BIGINT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+            event_kind TEXT NOT NULL,
+            related_issue_id BIGINT NULL REFERENCES issues(id) ON DELETE SET NULL,
+            read_at TIMESTAMPTZ NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS notifications"#)
+        .with_up(r#"CREATE INDEX notifications_by_account ON notifications(account_id, created_at)"#)
+        .with_down(r#"DROP INDEX IF EXISTS notifications_by_account"#)
+        .with_up(
+            r#"CREATE INDEX notifications_unread ON notifications(account_id) WHERE read_at IS NULL"#,
+        )
+        .with_down(r#"DROP INDEX IF EXISTS notifications_unread"#)
+        // Event kinds without a row use the default of delivering to both the inbox and email
+        .with_up(
+            r#"CREATE TABLE notification_preferences (
+            account_id BIGINT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+            event_kind TEXT NOT NULL,
+            inbox BOOL NOT NULL,
+            email BOOL NOT NULL,
+            PRIMARY KEY (account_id, event_kind)
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS notification_preferences"#)
+}
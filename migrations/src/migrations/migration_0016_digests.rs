@@ -0,0 +1,15 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(
+            r#"ALTER TABLE accounts ADD COLUMN digest_schedule TEXT NOT NULL DEFAULT 'none'
+                CHECK (digest_schedule IN ('none', 'daily', 'weekly'))"#,
+        )
+        .with_down(r#"ALTER TABLE accounts DROP COLUMN IF EXISTS digest_schedule"#)
+        // When null, the site's default time zone is used
+        .with_up(r#"ALTER TABLE accounts ADD COLUMN timezone TEXT NULL"#)
+        .with_down(r#"ALTER TABLE accounts DROP COLUMN IF EXISTS timezone"#)
+        .with_up(r#"ALTER TABLE accounts ADD COLUMN digest_last_sent_at TIMESTAMPTZ NULL"#)
+        .with_down(r#"ALTER TABLE accounts DROP COLUMN IF EXISTS digest_last_sent_at"#)
+}
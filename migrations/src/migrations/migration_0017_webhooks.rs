@@ -0,0 +1,45 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        // Webhooks without a project receive events for every issue on the site
+        .with_up(
+            r#"CREATE TABLE webhooks (
+            id BIGSERIAL PRIMARY KEY,
+            project_id BIGINT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            url TEXT NOT NULL,
+            secret TEXT NOT NULL,
+            events TEXT[] NOT NULL,
+            active BOOL NOT NULL DEFAULT true,
+            created_by BIGINT NOT NULL REFERENCES accounts(id),
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS webhooks"#)
+        .with_up(r#"CREATE INDEX webhooks_by_project ON webhooks(project_id)"#)
+        .with_down(r#"DROP INDEX IF EXISTS webhooks_by_project"#)
+        // The payload is stored exactly as it's signed and sent, so retries are byte-for-byte identical
+        .with_up(
+            r#"CREATE TABLE webhook_deliveries (
+            id BIGSERIAL PRIMARY KEY,
+            webhook_id BIGINT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+            event_kind TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            attempts INT NOT NULL DEFAULT 0,
+            response_status INT NULL,
+            last_error TEXT NULL,
+            next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            delivered_at TIMESTAMPTZ NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS webhook_deliveries"#)
+        .with_up(
+            r#"CREATE INDEX webhook_deliveries_pending ON webhook_deliveries(next_attempt_at) WHERE delivered_at IS NULL"#,
+        )
+        .with_down(r#"DROP INDEX IF EXISTS webhook_deliveries_pending"#)
+        .with_up(
+            r#"CREATE INDEX webhook_deliveries_by_webhook ON webhook_deliveries(webhook_id, created_at)"#,
+        )
+        .with_down(r#"DROP INDEX IF EXISTS webhook_deliveries_by_webhook"#)
+}
@@ -0,0 +1,23 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        // The same commit can be pushed to several branches, so each is only linked once per issue
+        .with_up(
+            r#"CREATE TABLE issue_commits (
+            id BIGSERIAL PRIMARY KEY,
+            issue_id BIGINT NOT NULL REFERENCES issues(id) ON DELETE CASCADE,
+            repository TEXT NOT NULL,
+            sha TEXT NOT NULL,
+            message TEXT NOT NULL,
+            url TEXT NULL,
+            author_name TEXT NOT NULL,
+            author_email TEXT NULL,
+            author_id BIGINT NULL REFERENCES accounts(id) ON DELETE SET NULL,
+            committed_at TIMESTAMPTZ NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            UNIQUE(issue_id, sha)
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS issue_commits"#)
+}
@@ -0,0 +1,58 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        // States without a project make up the site's default workflow, used
+        // by issues without a project and by projects that haven't customized theirs
+        .with_up(
+            r#"CREATE TABLE workflow_states (
+            id BIGSERIAL PRIMARY KEY,
+            project_id BIGINT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            position INT NOT NULL DEFAULT 0,
+            initial BOOL NOT NULL DEFAULT false,
+            started BOOL NOT NULL DEFAULT false,
+            completes BOOL NOT NULL DEFAULT false
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS workflow_states"#)
+        .with_up(
+            r#"CREATE UNIQUE INDEX workflow_states_unique_name ON workflow_states(COALESCE(project_id, 0), lower(name))"#,
+        )
+        .with_down(r#"DROP INDEX IF EXISTS workflow_states_unique_name"#)
+        .with_up(
+            r#"CREATE TABLE workflow_transitions (
+            from_state_id BIGINT NOT NULL REFERENCES workflow_states(id) ON DELETE CASCADE,
+            to_state_id BIGINT NOT NULL REFERENCES workflow_states(id) ON DELETE CASCADE,
+            PRIMARY KEY (from_state_id, to_state_id)
+        )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS workflow_transitions"#)
+        .with_up(
+            r#"INSERT INTO workflow_states (project_id, name, position, initial, started, completes) VALUES
+                (NULL, 'Triage', 0, true, false, false),
+                (NULL, 'In Progress', 1, false, true, false),
+                (NULL, 'In Review', 2, false, true, false),
+                (NULL, 'Done', 3, false, false, true),
+                (NULL, 'Won''t Fix', 4, false, false, true)"#,
+        )
+        // The started and completed checkboxes allowed moving between any states, so the default workflow does too
+        .with_up(
+            r#"INSERT INTO workflow_transitions (from_state_id, to_state_id)
+                SELECT from_state.id, to_state.id FROM workflow_states from_state, workflow_states to_state
+                WHERE from_state.id <> to_state.id"#,
+        )
+        .with_up(
+            r#"ALTER TABLE issues ADD COLUMN workflow_state_id BIGINT NULL REFERENCES workflow_states(id) ON DELETE SET NULL"#,
+        )
+        .with_down(r#"ALTER TABLE issues DROP COLUMN IF EXISTS workflow_state_id"#)
+        .with_up(
+            r#"UPDATE issues SET workflow_state_id = (
+                SELECT id FROM workflow_states WHERE project_id IS NULL AND name = CASE
+                    WHEN completed_at IS NOT NULL THEN 'Done'
+                    WHEN started_at IS NOT NULL THEN 'In Progress'
+                    ELSE 'Triage'
+                END
+            )"#,
+        )
+}
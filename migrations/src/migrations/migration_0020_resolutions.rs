@@ -0,0 +1,9 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(r#"ALTER TABLE issues ADD COLUMN resolution INTEGER NULL"#)
+        .with_down(r#"ALTER TABLE issues DROP COLUMN IF EXISTS resolution"#)
+        // Issues closed before resolutions existed are assumed to have been fixed
+        .with_up(r#"UPDATE issues SET resolution = 1 WHERE completed_at IS NOT NULL"#)
+}
@@ -0,0 +1,73 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(
+            r#"CREATE TYPE relationship_kind AS ENUM (
+            'blocks',
+            'precedes',
+            'causes',
+            'duplicates',
+            'relates_to',
+            'split_from'
+        )"#,
+        )
+        .with_down(r#"DROP TYPE IF EXISTS relationship_kind"#)
+        // The blocked status view depends on the column, so it's recreated around the type change
+        .with_up(r#"DROP VIEW issue_blocked_statuses"#)
+        .with_down(
+            r#"
+            CREATE OR REPLACE VIEW issue_blocked_statuses AS
+            SELECT
+                issues.id,
+                CASE WHEN COUNT(blocker.id) = 0 THEN FALSE ELSE bool_or(blocker.completed_at IS NULL) END AS blocked
+            FROM issues
+            LEFT OUTER JOIN issue_relationships ON (issue_relationships.relationship = 1 AND issue_relationships.issue_b = issues.id)
+            LEFT OUTER JOIN issues blocker ON blocker.id = issue_relationships.issue_a
+            GROUP BY issues.id
+            "#,
+        )
+        // Untyped links become plain related links
+        .with_up(
+            r#"ALTER TABLE issue_relationships ALTER COLUMN relationship TYPE relationship_kind USING (
+            CASE relationship
+                WHEN 1 THEN 'blocks'
+                WHEN 2 THEN 'precedes'
+                WHEN 3 THEN 'causes'
+                ELSE 'relates_to'
+            END
+        )::relationship_kind"#,
+        )
+        .with_down(
+            r#"ALTER TABLE issue_relationships ALTER COLUMN relationship TYPE INT USING (
+            CASE relationship
+                WHEN 'blocks' THEN 1
+                WHEN 'precedes' THEN 2
+                WHEN 'causes' THEN 3
+            END
+        )"#,
+        )
+        .with_up(
+            r#"ALTER TABLE issue_relationships
+            ALTER COLUMN relationship SET DEFAULT 'relates_to',
+            ALTER COLUMN relationship SET NOT NULL"#,
+        )
+        .with_down(
+            r#"ALTER TABLE issue_relationships
+            ALTER COLUMN relationship DROP DEFAULT,
+            ALTER COLUMN relationship DROP NOT NULL"#,
+        )
+        .with_up(
+            r#"
+            CREATE VIEW issue_blocked_statuses AS
+            SELECT
+                issues.id,
+                CASE WHEN COUNT(blocker.id) = 0 THEN FALSE ELSE bool_or(blocker.completed_at IS NULL) END AS blocked
+            FROM issues
+            LEFT OUTER JOIN issue_relationships ON (issue_relationships.relationship = 'blocks' AND issue_relationships.issue_b = issues.id)
+            LEFT OUTER JOIN issues blocker ON blocker.id = issue_relationships.issue_a
+            GROUP BY issues.id
+            "#,
+        )
+        .with_down(r#"DROP VIEW IF EXISTS issue_blocked_statuses"#)
+}
@@ -0,0 +1,83 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        // New issues can't have blockers yet
+        .with_up(r#"ALTER TABLE issues ALTER COLUMN blocked SET DEFAULT false"#)
+        .with_down(r#"ALTER TABLE issues ALTER COLUMN blocked DROP DEFAULT"#)
+        .with_up(
+            r#"
+            CREATE OR REPLACE FUNCTION refresh_blocked_issues(issue_ids BIGINT[]) RETURNS void AS $$
+            BEGIN
+                UPDATE issues SET blocked = issue_blocked_statuses.blocked
+                FROM issue_blocked_statuses
+                WHERE issues.id = issue_blocked_statuses.id
+                  AND issues.id = ANY(issue_ids)
+                  AND issues.blocked <> issue_blocked_statuses.blocked;
+            END
+            $$ LANGUAGE plpgsql
+            "#,
+        )
+        .with_down(r#"DROP FUNCTION IF EXISTS refresh_blocked_issues(BIGINT[])"#)
+        // Completing or reopening an issue changes whether the issues it blocks are blocked
+        .with_up(
+            r#"
+            CREATE OR REPLACE FUNCTION issue_completion_changed() RETURNS trigger AS $$
+            BEGIN
+                PERFORM refresh_blocked_issues(ARRAY(
+                    SELECT issue_b FROM issue_relationships
+                    WHERE issue_a = NEW.id AND relationship = 'blocks'
+                ));
+                RETURN NULL;
+            END
+            $$ LANGUAGE plpgsql
+            "#,
+        )
+        .with_down(r#"DROP FUNCTION IF EXISTS issue_completion_changed()"#)
+        .with_up(
+            r#"
+            CREATE TRIGGER issues_refresh_blocked
+            AFTER UPDATE OF completed_at ON issues
+            FOR EACH ROW WHEN (OLD.completed_at IS DISTINCT FROM NEW.completed_at)
+            EXECUTE PROCEDURE issue_completion_changed()
+            "#,
+        )
+        .with_down(r#"DROP TRIGGER IF EXISTS issues_refresh_blocked ON issues"#)
+        // Linking, unlinking and changing relationships, including the
+        // cascading deletes when an issue is deleted, refresh the blocked side
+        .with_up(
+            r#"
+            CREATE OR REPLACE FUNCTION issue_relationship_changed() RETURNS trigger AS $$
+            BEGIN
+                IF TG_OP IN ('UPDATE', 'DELETE') THEN
+                    PERFORM refresh_blocked_issues(ARRAY[OLD.issue_b]);
+                END IF;
+                IF TG_OP IN ('INSERT', 'UPDATE') THEN
+                    PERFORM refresh_blocked_issues(ARRAY[NEW.issue_b]);
+                END IF;
+                RETURN NULL;
+            END
+            $$ LANGUAGE plpgsql
+            "#,
+        )
+        .with_down(r#"DROP FUNCTION IF EXISTS issue_relationship_changed()"#)
+        .with_up(
+            r#"
+            CREATE TRIGGER issue_relationships_refresh_blocked
+            AFTER INSERT OR UPDATE OR DELETE ON issue_relationships
+            FOR EACH ROW EXECUTE PROCEDURE issue_relationship_changed()
+            "#,
+        )
+        .with_down(
+            r#"DROP TRIGGER IF EXISTS issue_relationships_refresh_blocked ON issue_relationships"#,
+        )
+        // Repair any flags left stale by code paths that missed a recomputation
+        .with_up(
+            r#"
+            UPDATE issues SET blocked = issue_blocked_statuses.blocked
+            FROM issue_blocked_statuses
+            WHERE issues.id = issue_blocked_statuses.id
+              AND issues.blocked <> issue_blocked_statuses.blocked
+            "#,
+        )
+}
@@ -0,0 +1,9 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(
+            r#"ALTER TABLE issues ADD COLUMN estimate_hours INTEGER NULL CHECK (estimate_hours >= 0)"#,
+        )
+        .with_down(r#"ALTER TABLE issues DROP COLUMN IF EXISTS estimate_hours"#)
+}
@@ -0,0 +1,16 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(r#"ALTER TABLE issues ADD COLUMN rank INTEGER NOT NULL DEFAULT 0"#)
+        .with_down(r#"ALTER TABLE issues DROP COLUMN IF EXISTS rank"#)
+        // Existing children keep the order they were created in
+        .with_up(
+            r#"UPDATE issues SET rank = ranked.rank FROM (
+                SELECT id, row_number() OVER (PARTITION BY parent_id ORDER BY id) - 1 as rank
+                FROM issues WHERE parent_id IS NOT NULL
+            ) ranked WHERE ranked.id = issues.id"#,
+        )
+        .with_up(r#"CREATE INDEX issues_parent_rank ON issues(parent_id, rank)"#)
+        .with_down(r#"DROP INDEX IF EXISTS issues_parent_rank"#)
+}
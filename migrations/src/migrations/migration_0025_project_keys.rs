@@ -0,0 +1,113 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(
+            r#"ALTER TABLE projects
+                ADD COLUMN key TEXT NULL,
+                ADD COLUMN last_issue_number INTEGER NOT NULL DEFAULT 0"#,
+        )
+        .with_down(
+            r#"ALTER TABLE projects DROP COLUMN IF EXISTS key, DROP COLUMN IF EXISTS last_issue_number"#,
+        )
+        // Existing projects get a key derived from their slug, falling back
+        // to one based on their id when that isn't usable or is taken
+        .with_up(
+            r#"UPDATE projects SET key = left(upper(regexp_replace(slug, '[^a-zA-Z0-9]', '', 'g')), 10)"#,
+        )
+        .with_up(
+            r#"UPDATE projects SET key = 'P' || id
+               WHERE key !~ '^[A-Z][A-Z0-9]*$'
+                  OR EXISTS (SELECT 1 FROM projects other WHERE other.key = projects.key AND other.id < projects.id)"#,
+        )
+        .with_up(r#"ALTER TABLE projects ALTER COLUMN key SET NOT NULL"#)
+        .with_up(r#"CREATE UNIQUE INDEX projects_key ON projects(key)"#)
+        .with_down(r#"DROP INDEX IF EXISTS projects_key"#)
+        .with_up(r#"ALTER TABLE issues ADD COLUMN number INTEGER NULL"#)
+        .with_down(r#"ALTER TABLE issues DROP COLUMN IF EXISTS number"#)
+        // Every number an issue has been given, so that keys keep working
+        // after an issue moves to another project
+        .with_up(
+            r#"CREATE TABLE issue_numbers (
+                project_id BIGINT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                number INTEGER NOT NULL,
+                issue_id BIGINT NOT NULL REFERENCES issues(id) ON DELETE CASCADE,
+                PRIMARY KEY (project_id, number)
+            )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS issue_numbers"#)
+        // Existing issues are numbered in the order they were created
+        .with_up(
+            r#"UPDATE issues SET number = numbered.number FROM (
+                SELECT id, row_number() OVER (PARTITION BY project_id ORDER BY id) as number
+                FROM issues WHERE project_id IS NOT NULL
+            ) numbered WHERE numbered.id = issues.id"#,
+        )
+        .with_up(
+            r#"INSERT INTO issue_numbers (project_id, number, issue_id)
+               SELECT project_id, number, id FROM issues WHERE number IS NOT NULL"#,
+        )
+        .with_up(
+            r#"UPDATE projects SET last_issue_number = COALESCE(
+                (SELECT MAX(number) FROM issues WHERE issues.project_id = projects.id), 0
+            )"#,
+        )
+        .with_up(
+            r#"
+            CREATE OR REPLACE FUNCTION issue_assign_number() RETURNS trigger AS $$
+            BEGIN
+                IF NEW.project_id IS NULL THEN
+                    NEW.number := NULL;
+                ELSE
+                    UPDATE projects SET last_issue_number = last_issue_number + 1
+                    WHERE id = NEW.project_id
+                    RETURNING last_issue_number INTO NEW.number;
+                END IF;
+                RETURN NEW;
+            END
+            $$ LANGUAGE plpgsql
+            "#,
+        )
+        .with_down(r#"DROP FUNCTION IF EXISTS issue_assign_number()"#)
+        .with_up(
+            r#"
+            CREATE TRIGGER issues_assign_number_on_insert
+            BEFORE INSERT ON issues
+            FOR EACH ROW EXECUTE PROCEDURE issue_assign_number()
+            "#,
+        )
+        .with_down(r#"DROP TRIGGER IF EXISTS issues_assign_number_on_insert ON issues"#)
+        .with_up(
+            r#"
+            CREATE TRIGGER issues_assign_number_on_move
+            BEFORE UPDATE OF project_id ON issues
+            FOR EACH ROW WHEN (OLD.project_id IS DISTINCT FROM NEW.project_id)
+            EXECUTE PROCEDURE issue_assign_number()
+            "#,
+        )
+        .with_down(r#"DROP TRIGGER IF EXISTS issues_assign_number_on_move ON issues"#)
+        .with_up(
+            r#"
+            CREATE OR REPLACE FUNCTION issue_record_number() RETURNS trigger AS $$
+            BEGIN
+                IF TG_OP = 'UPDATE' AND OLD.number IS NOT DISTINCT FROM NEW.number THEN
+                    RETURN NULL;
+                END IF;
+                INSERT INTO issue_numbers (project_id, number, issue_id)
+                VALUES (NEW.project_id, NEW.number, NEW.id);
+                RETURN NULL;
+            END
+            $$ LANGUAGE plpgsql
+            "#,
+        )
+        .with_down(r#"DROP FUNCTION IF EXISTS issue_record_number()"#)
+        .with_up(
+            r#"
+            CREATE TRIGGER issues_record_number
+            AFTER INSERT OR UPDATE OF project_id ON issues
+            FOR EACH ROW WHEN (NEW.number IS NOT NULL)
+            EXECUTE PROCEDURE issue_record_number()
+            "#,
+        )
+        .with_down(r#"DROP TRIGGER IF EXISTS issues_record_number ON issues"#)
+}
@@ -0,0 +1,38 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        // Slugs projects used to have, so that old links can be redirected
+        .with_up(
+            r#"CREATE TABLE project_slug_history (
+                slug TEXT PRIMARY KEY,
+                project_id BIGINT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                replaced_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS project_slug_history"#)
+        // A project taking a slug claims it back from the history, and renaming
+        // a project records the slug it had
+        .with_up(
+            r#"
+            CREATE OR REPLACE FUNCTION project_slug_changed() RETURNS trigger AS $$
+            BEGIN
+                DELETE FROM project_slug_history WHERE slug = NEW.slug;
+                IF TG_OP = 'UPDATE' AND OLD.slug <> NEW.slug THEN
+                    INSERT INTO project_slug_history (slug, project_id) VALUES (OLD.slug, NEW.id);
+                END IF;
+                RETURN NULL;
+            END
+            $$ LANGUAGE plpgsql
+            "#,
+        )
+        .with_down(r#"DROP FUNCTION IF EXISTS project_slug_changed()"#)
+        .with_up(
+            r#"
+            CREATE TRIGGER projects_slug_history
+            AFTER INSERT OR UPDATE OF slug ON projects
+            FOR EACH ROW EXECUTE PROCEDURE project_slug_changed()
+            "#,
+        )
+        .with_down(r#"DROP TRIGGER IF EXISTS projects_slug_history ON projects"#)
+}
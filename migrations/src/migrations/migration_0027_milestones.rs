@@ -0,0 +1,25 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(
+            r#"CREATE TABLE milestones (
+                id BIGSERIAL PRIMARY KEY,
+                project_id BIGINT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                description TEXT NULL,
+                target_date DATE NULL,
+                closed_at TIMESTAMPTZ NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS milestones"#)
+        .with_up(r#"CREATE UNIQUE INDEX milestones_project_name ON milestones(project_id, lower(name))"#)
+        .with_down(r#"DROP INDEX IF EXISTS milestones_project_name"#)
+        .with_up(
+            r#"ALTER TABLE issues ADD COLUMN milestone_id BIGINT NULL REFERENCES milestones(id) ON DELETE SET NULL"#,
+        )
+        .with_down(r#"ALTER TABLE issues DROP COLUMN IF EXISTS milestone_id"#)
+        .with_up(r#"CREATE INDEX issues_milestone ON issues(milestone_id)"#)
+        .with_down(r#"DROP INDEX IF EXISTS issues_milestone"#)
+}
@@ -0,0 +1,74 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        .with_up(
+            r#"CREATE TABLE iterations (
+                id BIGSERIAL PRIMARY KEY,
+                name TEXT NOT NULL,
+                starts_on DATE NOT NULL,
+                ends_on DATE NOT NULL,
+                capacity_hours INT NULL,
+                carried_over_at TIMESTAMPTZ NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                CHECK (ends_on >= starts_on)
+            )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS iterations"#)
+        .with_up(r#"CREATE INDEX iterations_ends_on ON iterations(ends_on)"#)
+        .with_down(r#"DROP INDEX IF EXISTS iterations_ends_on"#)
+        .with_up(
+            r#"ALTER TABLE issues ADD COLUMN iteration_id BIGINT NULL REFERENCES iterations(id) ON DELETE SET NULL"#,
+        )
+        .with_down(r#"ALTER TABLE issues DROP COLUMN IF EXISTS iteration_id"#)
+        .with_up(r#"CREATE INDEX issues_iteration ON issues(iteration_id)"#)
+        .with_down(r#"DROP INDEX IF EXISTS issues_iteration"#)
+        // Every issue that was planned into an iteration, so the iteration's
+        // report still covers issues that were carried over to a later one
+        .with_up(
+            r#"CREATE TABLE iteration_issues (
+                iteration_id BIGINT NOT NULL REFERENCES iterations(id) ON DELETE CASCADE,
+                issue_id BIGINT NOT NULL REFERENCES issues(id) ON DELETE CASCADE,
+                planned_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                carried_over_to BIGINT NULL REFERENCES iterations(id) ON DELETE SET NULL,
+                PRIMARY KEY (iteration_id, issue_id)
+            )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS iteration_issues"#)
+        .with_up(r#"CREATE INDEX iteration_issues_issue ON iteration_issues(issue_id)"#)
+        .with_down(r#"DROP INDEX IF EXISTS iteration_issues_issue"#)
+        // Issues taken out of an iteration by hand are no longer part of its
+        // plan. Carried over issues keep their entry, which is marked with
+        // the iteration they moved to before the issue itself is updated.
+        .with_up(
+            r#"CREATE FUNCTION issue_iteration_changed() RETURNS TRIGGER AS $$
+            BEGIN
+                IF TG_OP = 'UPDATE' AND OLD.iteration_id IS NOT NULL THEN
+                    DELETE FROM iteration_issues
+                    WHERE iteration_id = OLD.iteration_id
+                      AND issue_id = OLD.id
+                      AND carried_over_to IS NULL;
+                END IF;
+                IF NEW.iteration_id IS NOT NULL THEN
+                    INSERT INTO iteration_issues (iteration_id, issue_id)
+                    VALUES (NEW.iteration_id, NEW.id)
+                    ON CONFLICT (iteration_id, issue_id) DO NOTHING;
+                END IF;
+                RETURN NULL;
+            END;
+            $$ LANGUAGE plpgsql"#,
+        )
+        .with_down(r#"DROP FUNCTION IF EXISTS issue_iteration_changed"#)
+        .with_up(
+            r#"CREATE TRIGGER issue_iteration_inserted AFTER INSERT ON issues
+            FOR EACH ROW WHEN (NEW.iteration_id IS NOT NULL)
+            EXECUTE PROCEDURE issue_iteration_changed()"#,
+        )
+        .with_down(r#"DROP TRIGGER IF EXISTS issue_iteration_inserted ON issues"#)
+        .with_up(
+            r#"CREATE TRIGGER issue_iteration_updated AFTER UPDATE OF iteration_id ON issues
+            FOR EACH ROW WHEN (OLD.iteration_id IS DISTINCT FROM NEW.iteration_id)
+            EXECUTE PROCEDURE issue_iteration_changed()"#,
+        )
+        .with_down(r#"DROP TRIGGER IF EXISTS issue_iteration_updated ON issues"#)
+}
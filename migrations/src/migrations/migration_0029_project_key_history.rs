@@ -0,0 +1,38 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        // Keys projects used to have, so that references like `OLD-42` keep
+        // finding their issue after the project's key changes
+        .with_up(
+            r#"CREATE TABLE project_key_history (
+                key TEXT PRIMARY KEY,
+                project_id BIGINT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                replaced_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS project_key_history"#)
+        // As with slugs, a project taking a key claims it back from the history
+        .with_up(
+            r#"
+            CREATE OR REPLACE FUNCTION project_key_changed() RETURNS trigger AS $$
+            BEGIN
+                DELETE FROM project_key_history WHERE key = NEW.key;
+                IF TG_OP = 'UPDATE' AND OLD.key <> NEW.key THEN
+                    INSERT INTO project_key_history (key, project_id) VALUES (OLD.key, NEW.id);
+                END IF;
+                RETURN NULL;
+            END
+            $$ LANGUAGE plpgsql
+            "#,
+        )
+        .with_down(r#"DROP FUNCTION IF EXISTS project_key_changed()"#)
+        .with_up(
+            r#"
+            CREATE TRIGGER projects_key_history
+            AFTER INSERT OR UPDATE OF key ON projects
+            FOR EACH ROW EXECUTE PROCEDURE project_key_changed()
+            "#,
+        )
+        .with_down(r#"DROP TRIGGER IF EXISTS projects_key_history ON projects"#)
+}
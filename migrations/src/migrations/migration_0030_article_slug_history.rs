@@ -0,0 +1,15 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        // Articles aren't stored in the database, so renames are recorded by
+        // the `rename-article` command rather than a trigger
+        .with_up(
+            r#"CREATE TABLE article_slug_history (
+                slug TEXT PRIMARY KEY,
+                current_slug TEXT NOT NULL,
+                replaced_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            )"#,
+        )
+        .with_down(r#"DROP TABLE IF EXISTS article_slug_history"#)
+}
@@ -0,0 +1,9 @@
+use sqlx_simple_migrator::{migration_name, Migration};
+
+pub fn migration() -> Migration {
+    Migration::new(migration_name!())
+        // Revisions without an author were made by the tracker itself, such as
+        // carrying issues over to the next iteration
+        .with_up(r#"ALTER TABLE issue_revisions ALTER COLUMN author_id DROP NOT NULL"#)
+        .with_down(r#"ALTER TABLE issue_revisions ALTER COLUMN author_id SET NOT NULL"#)
+}
@@ -0,0 +1,252 @@
+use std::path::Path;
+
+use fluent_templates::once_cell::sync::OnceCell;
+use image::{GenericImageView, ImageOutputFormat};
+use uuid::Uuid;
+
+use database::schema::attachments::Attachment;
+
+pub use self::{
+    local::LocalStorage,
+    s3::S3Storage,
+    storage::{Storage, StorageError},
+};
+use crate::configuration::{
+    AttachmentLocalPath, AttachmentS3Bucket, AttachmentS3Endpoint, AttachmentS3Region,
+    AttachmentStorageBackend, Configuration,
+};
+
+mod local;
+mod s3;
+mod storage;
+
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+static SHARED_STORAGE: OnceCell<Box<dyn Storage>> = OnceCell::new();
+
+/// Sets up the storage backend selected by `AttachmentStorageBackend`. This
+/// runs at startup so that a misconfigured backend is reported before any
+/// requests are handled.
+pub fn initialize_storage() -> Result<(), StorageError> {
+    let misconfigured =
+        |key: &str| StorageError::Misconfigured(format!("{} must be configured", key));
+    let storage: Box<dyn Storage> = match AttachmentStorageBackend::get().as_deref() {
+        Some("s3") => Box::new(S3Storage::new(
+            AttachmentS3Bucket::get().ok_or_else(|| misconfigured("attachment-s3-bucket"))?,
+            AttachmentS3Region::get().ok_or_else(|| misconfigured("attachment-s3-region"))?,
+            AttachmentS3Endpoint::get(),
+        )),
+        Some("local") | None => Box::new(LocalStorage::new(
+            AttachmentLocalPath::get().ok_or_else(|| misconfigured("attachment-local-path"))?,
+        )),
+        Some(other) => {
+            return Err(StorageError::Misconfigured(format!(
+                "unknown attachment-storage-backend '{}'",
+                other
+            )))
+        }
+    };
+
+    // The backend can't change while the server is running, so initializing
+    // again keeps the existing one
+    let _ = SHARED_STORAGE.set(storage);
+    Ok(())
+}
+
+/// Returns the storage backend set up by `initialize_storage`
+pub fn storage() -> Result<&'static dyn Storage, StorageError> {
+    SHARED_STORAGE
+        .get()
+        .map(|storage| storage.as_ref())
+        .ok_or_else(|| StorageError::Misconfigured(String::from("storage wasn't initialized")))
+}
+
+/// Determines the content type of the uploaded data by inspecting its contents.
+/// The type the client claims isn't trusted.
+pub fn sniff_content_type(data: &[u8]) -> String {
+    if let Some(kind) = infer::get(data) {
+        kind.mime_type().to_string()
+    } else if std::str::from_utf8(data).is_ok() {
+        String::from("text/plain")
+    } else {
+        String::from("application/octet-stream")
+    }
+}
+
+/// Returns true for image types that are safe to display inline and that we can generate thumbnails for
+pub fn is_previewable_image(content_type: &str) -> bool {
+    matches!(
+        content_type,
+        "image/png" | "image/jpeg" | "image/gif" | "image/webp" | "image/bmp"
+    )
+}
+
+/// Strips any path components and characters that would cause problems in a url
+pub fn clean_filename(filename: &str) -> String {
+    let filename = Path::new(filename.trim())
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let cleaned = filename
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    if cleaned.is_empty() {
+        String::from("attachment")
+    } else {
+        cleaned
+    }
+}
+
+fn generate_thumbnail(data: &[u8]) -> Option<Vec<u8>> {
+    let image = image::load_from_memory(data).ok()?;
+    let thumbnail = if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+        image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+    } else {
+        image
+    };
+
+    let mut encoded = Vec::new();
+    thumbnail
+        .write_to(&mut encoded, ImageOutputFormat::Png)
+        .ok()?;
+    Some(encoded)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AttachmentError {
+    #[error("attachment too large")]
+    TooLarge,
+    #[error("storage error: {0}")]
+    Storage(#[from] StorageError),
+}
+
+/// Sniffs the content type of `data`, generates a thumbnail if it's an image,
+/// and writes both to storage. The returned attachment hasn't been saved to
+/// the database yet.
+pub async fn ingest(
+    author_id: i64,
+    filename: &str,
+    data: Vec<u8>,
+    maximum_bytes: u64,
+) -> Result<Attachment, AttachmentError> {
+    if data.len() as u64 > maximum_bytes {
+        return Err(AttachmentError::TooLarge);
+    }
+
+    let content_type = sniff_content_type(&data);
+    let storage_key = format!("attachments/{}", Uuid::new_v4());
+    let mut attachment = Attachment::new(
+        author_id,
+        clean_filename(filename),
+        content_type,
+        data.len() as i64,
+        storage_key,
+    );
+
+    if is_previewable_image(&attachment.content_type) {
+        let source = data.clone();
+        if let Ok(Some(thumbnail)) =
+            tokio::task::spawn_blocking(move || generate_thumbnail(&source)).await
+        {
+            let thumbnail_key = format!("{}-thumbnail", attachment.storage_key);
+            storage()?
+                .store(&thumbnail_key, "image/png", thumbnail)
+                .await?;
+            attachment.thumbnail_key = Some(thumbnail_key);
+        }
+    }
+
+    storage()?
+        .store(&attachment.storage_key, &attachment.content_type, data)
+        .await?;
+
+    Ok(attachment)
+}
+
+/// Finds the ids of attachments linked from markdown, e.g. `![screenshot](/attachment/42/file/screenshot.png)`
+pub fn attachment_ids_in(markdown: &str) -> Vec<i64> {
+    let mut ids = Vec::new();
+    for part in markdown.split("/attachment/").skip(1) {
+        let number_length = part.chars().take_while(|c| c.is_ascii_digit()).count();
+        if number_length > 0 && part[number_length..].starts_with('/') {
+            if let Ok(id) = part[0..number_length].parse() {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+    }
+
+    ids
+}
+
+/// Removes the stored data for an attachment. Missing objects are ignored.
+pub async fn remove_stored_data(attachment: &Attachment) -> Result<(), StorageError> {
+    for key in std::iter::once(&attachment.storage_key).chain(attachment.thumbnail_key.iter()) {
+        match storage()?.delete(key).await {
+            Ok(_) | Err(StorageError::NotFound) => {}
+            Err(other) => return Err(other),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        attachment_ids_in, clean_filename, generate_thumbnail, sniff_content_type, THUMBNAIL_SIZE,
+    };
+    use image::{DynamicImage, GenericImageView, ImageOutputFormat};
+
+    #[test]
+    fn sniffing() {
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(4, 4)
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        assert_eq!(sniff_content_type(&png), "image/png");
+        assert_eq!(sniff_content_type(b"just some text"), "text/plain");
+        assert_eq!(
+            sniff_content_type(&[0xff, 0x00, 0xfe, 0x01]),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn thumbnails_fit_within_bounds() {
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(1024, 512)
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        let thumbnail = image::load_from_memory(&generate_thumbnail(&png).unwrap()).unwrap();
+        assert_eq!(thumbnail.width(), THUMBNAIL_SIZE);
+        assert_eq!(thumbnail.height(), THUMBNAIL_SIZE / 2);
+    }
+
+    #[test]
+    fn filenames() {
+        assert_eq!(clean_filename("../../etc/passwd"), "passwd");
+        assert_eq!(clean_filename("my screenshot.png"), "my_screenshot.png");
+        assert_eq!(clean_filename(""), "attachment");
+    }
+
+    #[test]
+    fn linked_attachments() {
+        assert_eq!(
+            attachment_ids_in(
+                "![a](/attachment/1/file/a.png) and [b](/attachment/22/file/b.txt) and ![a again](/attachment/1/file/a.png)"
+            ),
+            vec![1, 22]
+        );
+        assert!(attachment_ids_in("/attachment/abc/file/x /attachment/3").is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::{Component, Path, PathBuf};
+
+use super::{Storage, StorageError};
+
+/// Stores attachments in a directory on the local filesystem
+#[derive(Debug)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf, StorageError> {
+        let relative = Path::new(key);
+        // Keys are generated by us, but we still don't want to allow a key to escape the root
+        if key.is_empty()
+            || relative
+                .components()
+                .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(StorageError::InvalidKey(key.to_string()));
+        }
+
+        Ok(self.root.join(relative))
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for LocalStorage {
+    async fn store(
+        &self,
+        key: &str,
+        _content_type: &str,
+        data: Vec<u8>,
+    ) -> Result<(), StorageError> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, data).await?;
+
+        Ok(())
+    }
+
+    async fn retrieve(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        let path = self.path_for(key)?;
+        match tokio::fs::read(path).await {
+            Ok(data) => Ok(data),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(StorageError::NotFound),
+            Err(err) => Err(StorageError::Io(err)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.path_for(key)?;
+        match tokio::fs::remove_file(path).await {
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(StorageError::NotFound),
+            Err(err) => Err(StorageError::Io(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LocalStorage, Storage, StorageError};
+
+    #[rocket::async_test]
+    async fn round_trip() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("khonsubase-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&root);
+
+        storage
+            .store("attachments/test", "text/plain", b"hello".to_vec())
+            .await?;
+        assert_eq!(storage.retrieve("attachments/test").await?, b"hello");
+
+        storage.delete("attachments/test").await?;
+        assert!(matches!(
+            storage.retrieve("attachments/test").await,
+            Err(StorageError::NotFound)
+        ));
+
+        tokio::fs::remove_dir_all(root).await?;
+        Ok(())
+    }
+
+    #[rocket::async_test]
+    async fn rejects_escaping_keys() {
+        let storage = LocalStorage::new(std::env::temp_dir());
+
+        assert!(matches!(
+            storage.retrieve("../etc/passwd").await,
+            Err(StorageError::InvalidKey(_))
+        ));
+        assert!(matches!(
+            storage.retrieve("/etc/passwd").await,
+            Err(StorageError::InvalidKey(_))
+        ));
+    }
+}
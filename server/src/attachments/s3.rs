@@ -0,0 +1,132 @@
+use futures::TryStreamExt;
+use rusoto_core::{Region, RusotoError};
+use rusoto_s3::{
+    DeleteObjectRequest, GetObjectError, GetObjectRequest, PutObjectRequest, S3Client, S3,
+};
+
+use super::{Storage, StorageError};
+
+/// Stores attachments in an Amazon S3-compatible bucket. Credentials are loaded
+/// using the default AWS credential chain (e.g., `AWS_ACCESS_KEY_ID` and
+/// `AWS_SECRET_ACCESS_KEY`).
+pub struct S3Storage {
+    client: S3Client,
+    bucket: String,
+}
+
+impl std::fmt::Debug for S3Storage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Storage")
+            .field("bucket", &self.bucket)
+            .finish()
+    }
+}
+
+impl S3Storage {
+    /// Creates a new instance. If `endpoint` is provided, requests will be sent
+    /// to it instead of AWS, which allows using services like MinIO.
+    pub fn new(bucket: String, region: String, endpoint: Option<String>) -> Self {
+        let region = match endpoint {
+            Some(endpoint) => Region::Custom {
+                name: region,
+                endpoint,
+            },
+            None => region.parse().unwrap_or_default(),
+        };
+
+        Self {
+            client: S3Client::new(region),
+            bucket,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for S3Storage {
+    async fn store(
+        &self,
+        key: &str,
+        content_type: &str,
+        data: Vec<u8>,
+    ) -> Result<(), StorageError> {
+        self.client
+            .put_object(PutObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_string(),
+                content_type: Some(content_type.to_string()),
+                content_length: Some(data.len() as i64),
+                body: Some(data.into()),
+                ..Default::default()
+            })
+            .await
+            .map_err(|err| StorageError::Remote(err.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn retrieve(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        let output = self
+            .client
+            .get_object(GetObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_string(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|err| match err {
+                RusotoError::Service(GetObjectError::NoSuchKey(_)) => StorageError::NotFound,
+                other => StorageError::Remote(other.to_string()),
+            })?;
+
+        match output.body {
+            Some(body) => Ok(body.map_ok(|chunk| chunk.to_vec()).try_concat().await?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.client
+            .delete_object(DeleteObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_string(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|err| StorageError::Remote(err.to_string()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{S3Storage, Storage, StorageError};
+
+    /// Runs against a local S3-compatible server such as MinIO when
+    /// `TEST_S3_ENDPOINT` and `TEST_S3_BUCKET` are set. The bucket must already
+    /// exist, and credentials are read from the standard AWS environment variables.
+    #[rocket::async_test]
+    async fn round_trip() -> anyhow::Result<()> {
+        let _ = dotenv::dotenv();
+        let (endpoint, bucket) = match (
+            std::env::var("TEST_S3_ENDPOINT"),
+            std::env::var("TEST_S3_BUCKET"),
+        ) {
+            (Ok(endpoint), Ok(bucket)) => (endpoint, bucket),
+            _ => return Ok(()),
+        };
+        let storage = S3Storage::new(bucket, String::from("us-east-1"), Some(endpoint));
+        let key = format!("test/{}", uuid::Uuid::new_v4());
+
+        storage.store(&key, "text/plain", b"hello".to_vec()).await?;
+        assert_eq!(storage.retrieve(&key).await?, b"hello");
+
+        storage.delete(&key).await?;
+        assert!(matches!(
+            storage.retrieve(&key).await,
+            Err(StorageError::NotFound)
+        ));
+
+        Ok(())
+    }
+}
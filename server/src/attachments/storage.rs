@@ -0,0 +1,23 @@
+use std::fmt::Debug;
+
+#[rocket::async_trait]
+pub trait Storage: Send + Sync + Debug {
+    async fn store(&self, key: &str, content_type: &str, data: Vec<u8>)
+        -> Result<(), StorageError>;
+    async fn retrieve(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum StorageError {
+    #[error("object not found")]
+    NotFound,
+    #[error("invalid storage key '{0}'")]
+    InvalidKey(String),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("remote storage error: {0}")]
+    Remote(String),
+    #[error("attachment storage is misconfigured: {0}")]
+    Misconfigured(String),
+}
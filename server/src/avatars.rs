@@ -0,0 +1,185 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::{Arc, RwLock},
+};
+
+use fluent_templates::once_cell::sync::OnceCell;
+use image::{imageops::FilterType, DynamicImage, GenericImageView, ImageOutputFormat};
+
+use database::schema::accounts::UserAvatar;
+
+use crate::attachments::{self, StorageError};
+
+pub const SUPPORTED_SIZES: [u32; 6] = [16, 32, 64, 128, 256, 512];
+
+/// The largest width or height an uploaded avatar may have
+const MAXIMUM_UPLOAD_DIMENSION: u32 = 4096;
+
+/// The most rendered avatars kept in memory before the cache is reset
+const MAXIMUM_CACHED_AVATARS: usize = 2048;
+
+static CACHE: OnceCell<RwLock<HashMap<String, Arc<Vec<u8>>>>> = OnceCell::new();
+
+fn cache() -> &'static RwLock<HashMap<String, Arc<Vec<u8>>>> {
+    CACHE.get_or_init(Default::default)
+}
+
+/// A value that changes whenever the rendered image would change. An uploaded
+/// avatar changes when it's replaced, and an identicon changes with the username.
+pub fn etag(avatar: &UserAvatar, size: u32) -> String {
+    match (&avatar.avatar_key, avatar.avatar_updated_at) {
+        (Some(_), Some(updated_at)) => format!(
+            "\"{}-{}-{}\"",
+            avatar.user_id,
+            updated_at.timestamp_millis(),
+            size
+        ),
+        _ => format!(
+            "\"{}-identicon-{}-{}\"",
+            avatar.user_id, avatar.username, size
+        ),
+    }
+}
+
+/// Returns the rendered JPEG for `avatar` at `size`, rendering it only if it isn't already cached
+pub async fn render(avatar: &UserAvatar, size: u32) -> anyhow::Result<Arc<Vec<u8>>> {
+    let etag = etag(avatar, size);
+    if let Some(cached) = cache().read().ok().and_then(|c| c.get(&etag).cloned()) {
+        return Ok(cached);
+    }
+
+    let rendered = match &avatar.avatar_key {
+        Some(key) => match retrieve(key).await {
+            Ok(source) => tokio::task::spawn_blocking(move || render_uploaded(&source, size))
+                .await?
+                .ok(),
+            Err(err) => {
+                error!("error loading avatar {}: {:?}", key, err);
+                None
+            }
+        },
+        None => None,
+    };
+    let rendered = match rendered {
+        Some(rendered) => rendered,
+        None => render_identicon(&avatar.username, size)?,
+    };
+
+    let rendered = Arc::new(rendered);
+    if let Ok(mut cache) = cache().write() {
+        if cache.len() >= MAXIMUM_CACHED_AVATARS {
+            cache.clear();
+        }
+        cache.insert(etag, rendered.clone());
+    }
+
+    Ok(rendered)
+}
+
+async fn retrieve(key: &str) -> Result<Vec<u8>, StorageError> {
+    attachments::storage()?.retrieve(key).await
+}
+
+fn render_identicon(username: &str, size: u32) -> anyhow::Result<Vec<u8>> {
+    let identicon = identicon_rs::new(username).size(8).unwrap();
+    let identicon = match size {
+        512 => identicon.scale(384).unwrap().border(64), // + 32
+        256 => identicon.scale(192).unwrap().border(32), // 224 + 16
+        128 => identicon.scale(96).unwrap().border(16),  // 112 + 8
+        64 => identicon.scale(48).unwrap().border(8),    // 52 + 6
+        32 => identicon.scale(24).unwrap().border(4),    // 24 + 4
+        16 => identicon.scale(12).unwrap().border(2),    // 12 + 2
+        _ => anyhow::bail!("invalid avatar size requested"),
+    };
+    Ok(identicon.export_jpeg_data().unwrap())
+}
+
+/// Crops the largest centered square out of `image` and scales it to `size`
+pub fn crop_and_resize(image: &DynamicImage, size: u32) -> DynamicImage {
+    let side = image.width().min(image.height());
+    let x = (image.width() - side) / 2;
+    let y = (image.height() - side) / 2;
+    image
+        .crop_imm(x, y, side, side)
+        .resize_exact(size, size, FilterType::Lanczos3)
+}
+
+fn render_uploaded(source: &[u8], size: u32) -> anyhow::Result<Vec<u8>> {
+    let image = image::load_from_memory(source)?;
+    // JPEG doesn't support transparency, so flatten the image before encoding it
+    let flattened = DynamicImage::ImageRgb8(crop_and_resize(&image, size).to_rgb8());
+    let mut encoded = Vec::new();
+    flattened.write_to(&mut encoded, ImageOutputFormat::Jpeg(90))?;
+    Ok(encoded)
+}
+
+/// Checks that an uploaded avatar can be decoded before it's stored. The
+/// dimensions are read from the header first, so that a small file claiming
+/// to be a huge image is rejected before it's decoded.
+pub fn validate_upload(source: &[u8]) -> bool {
+    if !attachments::is_previewable_image(&attachments::sniff_content_type(source)) {
+        return false;
+    }
+
+    let dimensions = image::io::Reader::new(Cursor::new(source))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok());
+    match dimensions {
+        Some((width, height))
+            if width <= MAXIMUM_UPLOAD_DIMENSION && height <= MAXIMUM_UPLOAD_DIMENSION =>
+        {
+            image::load_from_memory(source).is_ok()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crop_and_resize, etag, validate_upload, MAXIMUM_UPLOAD_DIMENSION};
+    use chrono::Utc;
+    use database::schema::accounts::UserAvatar;
+    use image::{DynamicImage, GenericImageView, ImageOutputFormat};
+
+    #[test]
+    fn crops_to_square() {
+        let resized = crop_and_resize(&DynamicImage::new_rgb8(300, 100), 64);
+        assert_eq!(resized.dimensions(), (64, 64));
+
+        let resized = crop_and_resize(&DynamicImage::new_rgb8(10, 40), 32);
+        assert_eq!(resized.dimensions(), (32, 32));
+    }
+
+    #[test]
+    fn etags_change_with_avatar() {
+        let mut avatar = UserAvatar {
+            user_id: 1,
+            username: String::from("ecton"),
+            avatar_key: None,
+            avatar_updated_at: None,
+        };
+        let identicon = etag(&avatar, 64);
+        assert_ne!(identicon, etag(&avatar, 32));
+
+        avatar.avatar_key = Some(String::from("avatars/1/a"));
+        avatar.avatar_updated_at = Some(Utc::now());
+        assert_ne!(identicon, etag(&avatar, 64));
+    }
+
+    #[test]
+    fn oversized_uploads_are_rejected() {
+        let png = |width, height| {
+            let mut encoded = Vec::new();
+            DynamicImage::new_rgb8(width, height)
+                .write_to(&mut encoded, ImageOutputFormat::Png)
+                .unwrap();
+            encoded
+        };
+
+        assert!(validate_upload(&png(64, 64)));
+        assert!(!validate_upload(&png(MAXIMUM_UPLOAD_DIMENSION + 1, 1)));
+        assert!(!validate_upload(b"not an image"));
+    }
+}
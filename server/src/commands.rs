@@ -0,0 +1,14 @@
+mod rename_article;
+mod repair_hierarchy;
+
+/// Runs a maintenance command instead of the server. Returns false if
+/// `command` isn't recognized.
+pub async fn run(command: &str, args: &[String]) -> anyhow::Result<bool> {
+    match command {
+        "rename-article" => rename_article::run(args).await?,
+        "repair-hierarchy" => repair_hierarchy::run().await?,
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,23 @@
+use database::schema::cms::Article;
+
+use crate::webserver::articles::find_article;
+
+/// Records that an article's slug changed, so that links to the old slug
+/// redirect to the new one. Articles are part of the server, so this is run
+/// after deploying a version that renames one.
+pub async fn run(args: &[String]) -> anyhow::Result<()> {
+    let (old_slug, new_slug) = match args {
+        [old_slug, new_slug] => (old_slug.to_lowercase(), new_slug.to_lowercase()),
+        _ => anyhow::bail!("usage: rename-article <old-slug> <new-slug>"),
+    };
+    if find_article(&new_slug).is_none() {
+        anyhow::bail!("no article has the slug {}", new_slug);
+    }
+
+    let mut tx = database::pool().begin().await?;
+    Article::record_slug_change(&old_slug, &new_slug, &mut tx).await?;
+    tx.commit().await?;
+    println!("/{} now redirects to /{}", old_slug, new_slug);
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::collections::{HashMap, HashSet};
+
+use database::schema::issues::Issue;
+
+/// Reports issues whose parents lead back to themselves. Editing issues
+/// prevents new loops, but they could have been created before it did.
+pub async fn run() -> anyhow::Result<()> {
+    let links = Issue::parent_links(database::pool()).await?;
+    let cycles = find_parent_cycles(&links);
+
+    if cycles.is_empty() {
+        println!("No parent cycles found among {} subtasks", links.len());
+    } else {
+        println!("Found {} parent cycles:", cycles.len());
+        for cycle in &cycles {
+            let path = cycle
+                .iter()
+                .chain(cycle.first())
+                .map(|id| format!("#{}", id))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            println!("  {}", path);
+        }
+        println!("Change the parent of one issue in each cycle to repair it.");
+    }
+
+    Ok(())
+}
+
+/// Finds each loop of issues that are each other's parents. Each cycle starts
+/// with its lowest issue id and is followed in parent order.
+fn find_parent_cycles(links: &[(i64, i64)]) -> Vec<Vec<i64>> {
+    let parents = links.iter().copied().collect::<HashMap<_, _>>();
+    let mut visited = HashSet::new();
+    let mut cycles = Vec::new();
+
+    let mut starts = parents.keys().copied().collect::<Vec<_>>();
+    starts.sort_unstable();
+    for start in starts {
+        let mut path = Vec::new();
+        let mut current = Some(start);
+        while let Some(id) = current {
+            if let Some(index) = path.iter().position(|&visiting| visiting == id) {
+                let mut cycle = path[index..].to_vec();
+                let lowest = cycle
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, &id)| id)
+                    .map(|(index, _)| index)
+                    .unwrap_or_default();
+                cycle.rotate_left(lowest);
+                cycles.push(cycle);
+                break;
+            }
+            if !visited.insert(id) {
+                break;
+            }
+            path.push(id);
+            current = parents.get(&id).copied();
+        }
+    }
+
+    cycles.sort();
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_each_cycle_once() {
+        // 1 -> 2 -> 3 -> 1 is a loop, 4 and 5 lead into it, 6 -> 6 is its own
+        // parent, and 7 -> 8 is an ordinary subtask
+        let cycles = find_parent_cycles(&[(2, 3), (1, 2), (3, 1), (4, 5), (5, 2), (6, 6), (7, 8)]);
+        assert_eq!(cycles, vec![vec![1, 2, 3], vec![6]]);
+    }
+
+    #[test]
+    fn no_cycles_in_a_tree() {
+        assert!(find_parent_cycles(&[(2, 1), (3, 1), (4, 3)]).is_empty());
+    }
+}
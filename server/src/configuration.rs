@@ -7,16 +7,36 @@ use fluent_templates::once_cell::sync::OnceCell;
 use serde::{de::DeserializeOwned, Serialize};
 
 pub use self::{
-    session_maximum_days::SessionMaximumDays, site_default_timezone::SiteDefaultTimezone,
+    attachment_local_path::AttachmentLocalPath, attachment_maximum_bytes::AttachmentMaximumBytes,
+    attachment_s3_bucket::AttachmentS3Bucket, attachment_s3_endpoint::AttachmentS3Endpoint,
+    attachment_s3_region::AttachmentS3Region, attachment_storage_backend::AttachmentStorageBackend,
+    git_push_secret::GitPushSecret, session_maximum_days::SessionMaximumDays,
+    site_base_url::SiteBaseUrl, site_default_timezone::SiteDefaultTimezone,
     site_issue_prefix::SiteIssuePrefix, site_name::SiteName,
-    site_primary_locale::SitePrimaryLocale,
+    site_primary_locale::SitePrimaryLocale, smtp_from_address::SmtpFromAddress,
+    smtp_host::SmtpHost, smtp_password::SmtpPassword, smtp_port::SmtpPort, smtp_tls::SmtpTls,
+    smtp_username::SmtpUsername,
 };
 
+mod attachment_local_path;
+mod attachment_maximum_bytes;
+mod attachment_s3_bucket;
+mod attachment_s3_endpoint;
+mod attachment_s3_region;
+mod attachment_storage_backend;
+mod git_push_secret;
 mod session_maximum_days;
+mod site_base_url;
 mod site_default_timezone;
 mod site_issue_prefix;
 mod site_name;
 mod site_primary_locale;
+mod smtp_from_address;
+mod smtp_host;
+mod smtp_password;
+mod smtp_port;
+mod smtp_tls;
+mod smtp_username;
 
 pub trait Configuration {
     type Type: Serialize + DeserializeOwned;
@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct AttachmentLocalPath;
+
+impl Configuration for AttachmentLocalPath {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        Some(String::from("attachments"))
+    }
+
+    fn key() -> &'static str {
+        "attachment-local-path"
+    }
+}
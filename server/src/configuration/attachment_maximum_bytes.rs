@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct AttachmentMaximumBytes;
+
+impl Configuration for AttachmentMaximumBytes {
+    type Type = u64;
+
+    fn default() -> Option<Self::Type> {
+        Some(10 * 1024 * 1024)
+    }
+
+    fn key() -> &'static str {
+        "attachment-maximum-bytes"
+    }
+}
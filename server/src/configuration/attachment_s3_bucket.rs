@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct AttachmentS3Bucket;
+
+impl Configuration for AttachmentS3Bucket {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        None
+    }
+
+    fn key() -> &'static str {
+        "attachment-s3-bucket"
+    }
+}
@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct AttachmentS3Endpoint;
+
+impl Configuration for AttachmentS3Endpoint {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        None
+    }
+
+    fn key() -> &'static str {
+        "attachment-s3-endpoint"
+    }
+}
@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct AttachmentS3Region;
+
+impl Configuration for AttachmentS3Region {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        Some(String::from("us-east-1"))
+    }
+
+    fn key() -> &'static str {
+        "attachment-s3-region"
+    }
+}
@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct AttachmentStorageBackend;
+
+impl Configuration for AttachmentStorageBackend {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        Some(String::from("local"))
+    }
+
+    fn key() -> &'static str {
+        "attachment-storage-backend"
+    }
+}
@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct GitPushSecret;
+
+impl Configuration for GitPushSecret {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        None
+    }
+
+    fn key() -> &'static str {
+        "git-push-secret"
+    }
+}
@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct SiteBaseUrl;
+
+impl Configuration for SiteBaseUrl {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        Some(String::from("http://localhost:8000"))
+    }
+
+    fn key() -> &'static str {
+        "site-base-url"
+    }
+}
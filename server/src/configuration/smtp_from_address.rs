@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct SmtpFromAddress;
+
+impl Configuration for SmtpFromAddress {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        None
+    }
+
+    fn key() -> &'static str {
+        "smtp-from-address"
+    }
+}
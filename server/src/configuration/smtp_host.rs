@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct SmtpHost;
+
+impl Configuration for SmtpHost {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        None
+    }
+
+    fn key() -> &'static str {
+        "smtp-host"
+    }
+}
@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct SmtpPassword;
+
+impl Configuration for SmtpPassword {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        None
+    }
+
+    fn key() -> &'static str {
+        "smtp-password"
+    }
+}
@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct SmtpPort;
+
+impl Configuration for SmtpPort {
+    type Type = u16;
+
+    fn default() -> Option<Self::Type> {
+        Some(587)
+    }
+
+    fn key() -> &'static str {
+        "smtp-port"
+    }
+}
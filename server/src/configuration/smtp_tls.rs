@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct SmtpTls;
+
+impl Configuration for SmtpTls {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        Some(String::from("starttls"))
+    }
+
+    fn key() -> &'static str {
+        "smtp-tls"
+    }
+}
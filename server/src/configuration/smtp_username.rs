@@ -0,0 +1,15 @@
+use super::Configuration;
+
+pub struct SmtpUsername;
+
+impl Configuration for SmtpUsername {
+    type Type = String;
+
+    fn default() -> Option<Self::Type> {
+        None
+    }
+
+    fn key() -> &'static str {
+        "smtp-username"
+    }
+}
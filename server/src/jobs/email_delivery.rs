@@ -0,0 +1,73 @@
+use chrono::Utc;
+use database::schema::notifications::EmailDelivery;
+use tokio::time::Duration;
+
+use crate::{
+    jobs::{retry_delay, Job, JobInstance},
+    notifications::Mailer,
+};
+
+/// After this many failures a delivery is abandoned. It remains in the table
+/// with its last error for an administrator to inspect.
+const MAXIMUM_ATTEMPTS: i32 = 8;
+const BATCH_SIZE: i64 = 50;
+
+#[derive(Debug)]
+struct EmailDeliveryJob;
+
+#[rocket::async_trait]
+impl Job for EmailDeliveryJob {
+    fn period(&self) -> Duration {
+        Duration::from_secs(30)
+    }
+
+    async fn execute(&mut self) -> anyhow::Result<()> {
+        let mailer = match Mailer::from_configuration() {
+            Ok(Some(mailer)) => mailer,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                error!("EmailDeliveryJob could not configure SMTP: {}", err);
+                return Ok(());
+            }
+        };
+
+        let pending =
+            EmailDelivery::list_pending(MAXIMUM_ATTEMPTS, BATCH_SIZE, database::pool()).await?;
+        let mut sent = 0;
+
+        for delivery in pending {
+            match mailer
+                .send(&delivery.to_address, &delivery.subject, &delivery.body)
+                .await
+            {
+                Ok(_) => {
+                    EmailDelivery::mark_sent(delivery.id, database::pool()).await?;
+                    sent += 1;
+                }
+                Err(err) => {
+                    error!(
+                        "EmailDeliveryJob failed to deliver email {}: {}",
+                        delivery.id, err
+                    );
+                    EmailDelivery::mark_failed(
+                        delivery.id,
+                        &err.to_string(),
+                        Utc::now() + retry_delay(delivery.attempts),
+                        database::pool(),
+                    )
+                    .await?;
+                }
+            }
+        }
+
+        if sent > 0 {
+            info!("EmailDeliveryJob sent {} emails", sent);
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn job() -> JobInstance {
+    EmailDeliveryJob.instance()
+}
@@ -0,0 +1,48 @@
+use chrono::Utc;
+use database::schema::notifications::DigestSettings;
+use tokio::time::Duration;
+
+use crate::{
+    configuration::{Configuration, SmtpHost},
+    jobs::{Job, JobInstance},
+    notifications::digest,
+};
+
+#[derive(Debug)]
+struct EmailDigest;
+
+#[rocket::async_trait]
+impl Job for EmailDigest {
+    fn period(&self) -> Duration {
+        Duration::from_secs(5 * 60)
+    }
+
+    async fn execute(&mut self) -> anyhow::Result<()> {
+        if SmtpHost::get().is_none() {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let mut queued = 0;
+        for settings in DigestSettings::list_subscribed(database::pool()).await? {
+            match digest::send_if_due(&settings, now).await {
+                Ok(true) => queued += 1,
+                Ok(false) => {}
+                Err(err) => error!(
+                    "EmailDigest failed to send digest for account {}: {:?}",
+                    settings.account_id, err
+                ),
+            }
+        }
+
+        if queued > 0 {
+            info!("EmailDigest queued {} digests", queued);
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn job() -> JobInstance {
+    EmailDigest.instance()
+}
@@ -0,0 +1,46 @@
+use chrono::Utc;
+use database::schema::issues::Iteration;
+use tokio::time::Duration;
+
+use crate::jobs::{Job, JobInstance};
+
+#[derive(Debug)]
+struct IterationCarryOver;
+
+#[rocket::async_trait]
+impl Job for IterationCarryOver {
+    fn period(&self) -> Duration {
+        Duration::from_secs(15 * 60)
+    }
+
+    async fn execute(&mut self) -> anyhow::Result<()> {
+        let today = Utc::now().date().naive_utc();
+        for mut iteration in Iteration::list_awaiting_carry_over(today).await? {
+            let next = iteration.next(today).await?;
+            // Incomplete issues wait in the iteration until the next one has
+            // been scheduled
+            if next.is_none() && iteration.incomplete_issue_count().await? > 0 {
+                continue;
+            }
+
+            let mut tx = database::pool().begin().await?;
+            let moved = iteration
+                .carry_over(next.as_ref().map(|next| next.id), &mut tx)
+                .await?;
+            tx.commit().await?;
+
+            if let Some(next) = next {
+                info!(
+                    "IterationCarryOver moved {} issues from iteration {} to {}",
+                    moved, iteration.id, next.id
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn job() -> JobInstance {
+    IterationCarryOver.instance()
+}
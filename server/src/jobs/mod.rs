@@ -3,7 +3,12 @@ use std::{
     time::{Duration, Instant},
 };
 
+mod email_delivery;
+mod email_digest;
+mod iteration_carry_over;
+mod orphaned_attachment_cleanup;
 mod session_cleanup;
+mod webhook_delivery;
 
 #[rocket::async_trait]
 trait Job: Send + Sync + Debug + 'static {
@@ -47,8 +52,22 @@ impl JobInstance {
     }
 }
 
+/// Exponential backoff for failed deliveries, starting at one minute and
+/// capped at six hours
+pub(crate) fn retry_delay(previous_attempts: i32) -> chrono::Duration {
+    let minutes = 1i64 << previous_attempts.clamp(0, 10);
+    chrono::Duration::minutes(minutes.min(6 * 60))
+}
+
 pub async fn run() -> anyhow::Result<()> {
-    let mut jobs = vec![session_cleanup::job()];
+    let mut jobs = vec![
+        session_cleanup::job(),
+        orphaned_attachment_cleanup::job(),
+        email_delivery::job(),
+        email_digest::job(),
+        webhook_delivery::job(),
+        iteration_carry_over::job(),
+    ];
 
     loop {
         let now = Instant::now();
@@ -0,0 +1,48 @@
+use chrono::Utc;
+use database::schema::attachments::Attachment;
+use tokio::time::Duration;
+
+use crate::{
+    attachments,
+    jobs::{Job, JobInstance},
+};
+
+/// How long an upload may remain unassociated before it's removed. This gives
+/// someone editing an issue plenty of time to save their changes.
+const ORPHAN_GRACE_PERIOD_HOURS: i64 = 24;
+
+#[derive(Debug)]
+struct OrphanedAttachmentCleanup;
+
+#[rocket::async_trait]
+impl Job for OrphanedAttachmentCleanup {
+    fn period(&self) -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+
+    async fn execute(&mut self) -> anyhow::Result<()> {
+        let created_before = Utc::now() - chrono::Duration::hours(ORPHAN_GRACE_PERIOD_HOURS);
+        let orphaned = Attachment::list_orphaned(created_before, database::pool()).await?;
+        let mut removed = 0;
+
+        for attachment in orphaned {
+            if let Err(err) = attachments::remove_stored_data(&attachment).await {
+                error!(
+                    "OrphanedAttachmentCleanup failed to remove data for attachment {}: {:?}",
+                    attachment.id, err
+                );
+                continue;
+            }
+
+            removed += Attachment::delete(attachment.id, database::pool()).await?;
+        }
+
+        info!("OrphanedAttachmentCleanup removed {} attachments", removed);
+
+        Ok(())
+    }
+}
+
+pub(crate) fn job() -> JobInstance {
+    OrphanedAttachmentCleanup.instance()
+}
@@ -0,0 +1,78 @@
+use chrono::Utc;
+use database::schema::webhooks::WebhookDelivery;
+use tokio::time::Duration;
+
+use crate::{
+    jobs::{retry_delay, Job, JobInstance},
+    webhooks::WebhookClient,
+};
+
+/// After this many failures a delivery is abandoned. It remains in the
+/// webhook's delivery log with its last response.
+const MAXIMUM_ATTEMPTS: i32 = 8;
+const BATCH_SIZE: i64 = 50;
+
+#[derive(Debug)]
+struct WebhookDeliveryJob;
+
+#[rocket::async_trait]
+impl Job for WebhookDeliveryJob {
+    fn period(&self) -> Duration {
+        Duration::from_secs(15)
+    }
+
+    async fn execute(&mut self) -> anyhow::Result<()> {
+        let pending =
+            WebhookDelivery::list_pending(MAXIMUM_ATTEMPTS, BATCH_SIZE, database::pool()).await?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let client = WebhookClient::new()?;
+        let mut delivered = 0;
+        for delivery in pending {
+            let result = client
+                .send(
+                    &delivery.url,
+                    &delivery.secret,
+                    delivery.id,
+                    &delivery.event_kind,
+                    &delivery.payload,
+                )
+                .await;
+            let (status, error) = match result {
+                Ok(status) if (200..300).contains(&status) => {
+                    WebhookDelivery::mark_delivered(delivery.id, status as i32, database::pool())
+                        .await?;
+                    delivered += 1;
+                    continue;
+                }
+                Ok(status) => (Some(status as i32), format!("HTTP status {}", status)),
+                Err(err) => (None, err.to_string()),
+            };
+
+            error!(
+                "WebhookDeliveryJob failed to deliver {} to {}: {}",
+                delivery.id, delivery.url, error
+            );
+            WebhookDelivery::mark_failed(
+                delivery.id,
+                status,
+                &error,
+                Utc::now() + retry_delay(delivery.attempts),
+                database::pool(),
+            )
+            .await?;
+        }
+
+        if delivered > 0 {
+            info!("WebhookDeliveryJob delivered {} webhooks", delivered);
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn job() -> JobInstance {
+    WebhookDeliveryJob.instance()
+}
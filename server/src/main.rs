@@ -3,14 +3,16 @@
 #[macro_use]
 extern crate rocket;
 
+mod attachments;
+mod avatars;
+mod commands;
 mod configuration;
+mod notifications;
 mod setup;
+mod webhooks;
 mod webserver;
 
 mod jobs;
-#[cfg(test)]
-#[allow(dead_code)]
-mod test_helpers;
 
 #[rocket::main]
 async fn main() -> Result<(), anyhow::Error> {
@@ -24,6 +26,18 @@ async fn main() -> Result<(), anyhow::Error> {
 
     setup::run().await.expect("error executing setup");
 
+    if let Some(command) = std::env::args().nth(1) {
+        let args = std::env::args().skip(2).collect::<Vec<_>>();
+        if commands::run(&command, &args).await? {
+            return Ok(());
+        }
+        anyhow::bail!("unknown command: {}", command);
+    }
+
+    // Commands don't use attachments, so a misconfigured storage backend
+    // only stops the server
+    attachments::initialize_storage()?;
+
     tokio::spawn(jobs::run());
 
     webserver::main().await?;
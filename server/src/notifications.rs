@@ -0,0 +1,400 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use fluent_templates::fluent_bundle::FluentValue;
+
+use database::{
+    schema::{
+        accounts::{Account, User},
+        issues::{IssueView, Subscription},
+        notifications::{EmailDelivery, IssueEventKind, Notification, NotificationPreferences},
+    },
+    sqlx,
+};
+
+use crate::{
+    configuration::{
+        Configuration, SiteBaseUrl, SiteIssuePrefix, SiteName, SitePrimaryLocale, SmtpHost,
+    },
+    webhooks,
+    webserver::localization::localize,
+};
+
+pub mod digest;
+mod email;
+
+pub use self::email::Mailer;
+
+/// Something that happened to an issue that its watchers may want to know about
+#[derive(Debug, Clone)]
+pub struct IssueEvent {
+    pub issue_id: i64,
+    pub actor_id: i64,
+    pub kind: IssueEventKind,
+    pub related_issue_id: Option<i64>,
+    pub comment: Option<String>,
+    /// When set, only this account is notified rather than the issue's watchers
+    pub recipient_id: Option<i64>,
+}
+
+impl IssueEvent {
+    pub fn new(issue_id: i64, actor_id: i64, kind: IssueEventKind) -> Self {
+        Self {
+            issue_id,
+            actor_id,
+            kind,
+            related_issue_id: None,
+            comment: None,
+            recipient_id: None,
+        }
+    }
+
+    pub fn related_to(mut self, issue_id: i64) -> Self {
+        self.related_issue_id = Some(issue_id);
+        self
+    }
+
+    pub fn with_comment(mut self, comment: Option<String>) -> Self {
+        self.comment = comment;
+        self
+    }
+
+    pub fn for_recipient(mut self, account_id: i64) -> Self {
+        self.recipient_id = Some(account_id);
+        self
+    }
+}
+
+/// Notifies the watchers of the issues referenced by `events`, delivering to
+/// their inbox and email according to their preferences, and queues the
+/// events for any subscribed webhooks. This should be
+/// called after the changes have been committed. Failures are logged rather
+/// than returned, as the change itself has already succeeded.
+pub async fn publish(events: Vec<IssueEvent>) {
+    if events.is_empty() {
+        return;
+    }
+
+    deliver(&events).await;
+
+    if let Err(err) = webhooks::enqueue(&events).await {
+        error!("error queueing webhooks: {:?}", err);
+    }
+}
+
+/// Delivers to each recipient separately, so that a failure for one recipient
+/// doesn't keep the others from being notified
+async fn deliver(events: &[IssueEvent]) {
+    let mut events_by_recipient = HashMap::<i64, Vec<&IssueEvent>>::new();
+    for event in events {
+        let recipients = match event.recipient_id {
+            Some(account_id) => vec![account_id],
+            None => {
+                match Subscription::interested_in_issue(event.issue_id, Some(event.actor_id)).await
+                {
+                    Ok(recipients) => recipients,
+                    Err(err) => {
+                        error!(
+                            "error finding recipients for issue {}: {:?}",
+                            event.issue_id, err
+                        );
+                        continue;
+                    }
+                }
+            }
+        };
+        for account_id in recipients {
+            events_by_recipient
+                .entry(account_id)
+                .or_default()
+                .push(event);
+        }
+    }
+
+    let email_enabled = SmtpHost::get().is_some();
+    let mut names = NameCache::default();
+    for (account_id, events) in events_by_recipient {
+        if let Err(err) = deliver_to(account_id, events, email_enabled, &mut names).await {
+            error!(
+                "error delivering notifications to account {}: {:?}",
+                account_id, err
+            );
+        }
+    }
+}
+
+async fn deliver_to(
+    account_id: i64,
+    events: Vec<&IssueEvent>,
+    email_enabled: bool,
+    names: &mut NameCache,
+) -> anyhow::Result<()> {
+    let preferences = NotificationPreferences::load(account_id, database::pool()).await?;
+
+    let mut tx = database::pool().begin().await?;
+    for event in events
+        .iter()
+        .filter(|event| preferences.delivery_for(event.kind).inbox)
+    {
+        Notification::create(
+            account_id,
+            event.issue_id,
+            event.actor_id,
+            event.kind,
+            event.related_issue_id,
+            &mut tx,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    if !email_enabled {
+        return Ok(());
+    }
+
+    let email_events = events
+        .into_iter()
+        .filter(|event| preferences.delivery_for(event.kind).email)
+        .collect::<Vec<_>>();
+    if email_events.is_empty() {
+        return Ok(());
+    }
+
+    let account = Account::load(account_id, database::pool()).await?;
+    let address = match account.email {
+        Some(address) => address,
+        None => return Ok(()),
+    };
+
+    let mut rendered = Vec::with_capacity(email_events.len());
+    for event in email_events {
+        rendered.push(RenderedEvent {
+            kind: event.kind,
+            issue_id: event.issue_id,
+            actor: names.user(event.actor_id).await?,
+            issue: names.issue(event.issue_id).await?,
+            related: match event.related_issue_id {
+                Some(related_id) => Some(names.issue(related_id).await?),
+                None => None,
+            },
+            comment: event.comment.clone(),
+        });
+    }
+
+    let message = compose(
+        &SitePrimaryLocale::get().unwrap(),
+        &SiteName::get().unwrap(),
+        &SiteBaseUrl::get().unwrap(),
+        account_id,
+        &rendered,
+    );
+
+    EmailDelivery::enqueue(
+        Some(account_id),
+        &address,
+        &message.subject,
+        &message.body,
+        database::pool(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Refers to an issue in plain text as the `issue_key` template macro does,
+/// except that issues outside of projects use the site's issue prefix
+fn issue_key(
+    issue_prefix: &str,
+    issue_id: i64,
+    project_key: Option<&str>,
+    number: Option<i32>,
+) -> String {
+    match (project_key, number) {
+        (Some(project_key), Some(number)) => format!("{}-{}", project_key, number),
+        _ => format!("{}{}", issue_prefix, issue_id),
+    }
+}
+
+#[derive(Default)]
+struct NameCache {
+    users: HashMap<i64, String>,
+    issues: HashMap<i64, String>,
+}
+
+impl NameCache {
+    async fn user(&mut self, id: i64) -> sqlx::Result<String> {
+        if let Some(name) = self.users.get(&id) {
+            return Ok(name.clone());
+        }
+
+        let user = User::load(id, database::pool()).await?;
+        let name = user.display_name.unwrap_or(user.username);
+        self.users.insert(id, name.clone());
+        Ok(name)
+    }
+
+    async fn issue(&mut self, id: i64) -> sqlx::Result<String> {
+        if let Some(name) = self.issues.get(&id) {
+            return Ok(name.clone());
+        }
+
+        let issue = IssueView::load(id).await?;
+        let name = format!(
+            "{} {}",
+            issue_key(
+                &SiteIssuePrefix::get().unwrap(),
+                issue.id,
+                issue.project_key.as_deref(),
+                issue.number
+            ),
+            issue.summary
+        );
+        self.issues.insert(id, name.clone());
+        Ok(name)
+    }
+}
+
+struct RenderedEvent {
+    kind: IssueEventKind,
+    issue_id: i64,
+    actor: String,
+    issue: String,
+    related: Option<String>,
+    comment: Option<String>,
+}
+
+#[derive(Debug)]
+struct EmailMessage {
+    subject: String,
+    body: String,
+}
+
+fn compose(
+    language: &str,
+    site: &str,
+    base_url: &str,
+    recipient_id: i64,
+    events: &[RenderedEvent],
+) -> EmailMessage {
+    let base_url = base_url.trim_end_matches('/');
+    let mut args = HashMap::new();
+    args.insert(String::from("site"), FluentValue::from(site));
+
+    let subject = match events {
+        [event] => {
+            args.insert(
+                String::from("issue"),
+                FluentValue::from(event.issue.as_str()),
+            );
+            localize(language, "email-subject-single", &args)
+        }
+        events => {
+            args.insert(String::from("count"), FluentValue::from(events.len()));
+            localize(language, "email-subject-multiple", &args)
+        }
+    };
+
+    let mut paragraphs = Vec::new();
+    for event in events {
+        let mut args = HashMap::new();
+        args.insert(
+            String::from("actor"),
+            FluentValue::from(event.actor.as_str()),
+        );
+        args.insert(
+            String::from("issue"),
+            FluentValue::from(event.issue.as_str()),
+        );
+        if let Some(related) = &event.related {
+            args.insert(String::from("related"), FluentValue::from(related.as_str()));
+        }
+        let mut paragraph = localize(
+            language,
+            &format!("email-event-{}", event.kind.as_str()),
+            &args,
+        );
+
+        if let Some(comment) = &event.comment {
+            paragraph.push_str("\n\n");
+            paragraph.push_str(comment.trim());
+        }
+
+        let mut args = HashMap::new();
+        args.insert(
+            String::from("url"),
+            FluentValue::String(Cow::Owned(format!("{}/issue/{}", base_url, event.issue_id))),
+        );
+        paragraph.push_str("\n\n");
+        paragraph.push_str(&localize(language, "email-view-issue", &args));
+
+        paragraphs.push(paragraph);
+    }
+
+    let mut args = HashMap::new();
+    args.insert(String::from("site"), FluentValue::from(site));
+    args.insert(
+        String::from("url"),
+        FluentValue::String(Cow::Owned(format!(
+            "{}/user/{}/edit",
+            base_url, recipient_id
+        ))),
+    );
+    paragraphs.push(format!(
+        "-- \n{}",
+        localize(language, "email-footer", &args)
+    ));
+
+    EmailMessage {
+        subject,
+        body: paragraphs.join("\n\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: IssueEventKind, issue_id: i64) -> RenderedEvent {
+        RenderedEvent {
+            kind,
+            issue_id,
+            actor: String::from("Alice"),
+            issue: format!("#{} Summary", issue_id),
+            related: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn compose_groups_events_into_one_message() {
+        let single = compose(
+            "en-US",
+            "Khonsubase",
+            "https://example.com/",
+            7,
+            &[event(IssueEventKind::Completed, 1)],
+        );
+        assert!(single.subject.contains("#1 Summary"));
+        assert!(single.body.contains("https://example.com/issue/1"));
+        assert!(single.body.contains("https://example.com/user/7/edit"));
+
+        let mut commented = event(IssueEventKind::Commented, 2);
+        commented.comment = Some(String::from("Looks good to me"));
+        let multiple = compose(
+            "en-US",
+            "Khonsubase",
+            "https://example.com",
+            7,
+            &[event(IssueEventKind::Created, 1), commented],
+        );
+        assert!(multiple.subject.contains('2'));
+        assert!(multiple.body.contains("Looks good to me"));
+        assert!(multiple.body.contains("https://example.com/issue/1"));
+        assert!(multiple.body.contains("https://example.com/issue/2"));
+    }
+
+    #[test]
+    fn issues_are_named_by_their_key() {
+        assert_eq!(issue_key("#", 12, Some("GOOEY"), Some(3)), "GOOEY-3");
+        assert_eq!(issue_key("#", 12, None, None), "#12");
+    }
+}
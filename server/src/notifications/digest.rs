@@ -0,0 +1,253 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use fluent_templates::fluent_bundle::FluentValue;
+
+use database::schema::notifications::{
+    DigestContents, DigestSchedule, DigestSettings, EmailDelivery,
+};
+
+use super::{issue_key, EmailMessage};
+use crate::{
+    configuration::{
+        Configuration, SiteBaseUrl, SiteDefaultTimezone, SiteIssuePrefix, SiteName,
+        SitePrimaryLocale,
+    },
+    webserver::localization::localize,
+};
+
+/// The local hour at which digests are sent
+const DIGEST_HOUR: u32 = 8;
+
+fn period(schedule: DigestSchedule) -> Option<Duration> {
+    match schedule {
+        DigestSchedule::None => None,
+        DigestSchedule::Daily => Some(Duration::days(1)),
+        DigestSchedule::Weekly => Some(Duration::weeks(1)),
+    }
+}
+
+/// Returns the most recent time at or before `now` that a digest was scheduled.
+/// Daily digests go out every morning and weekly digests on Monday mornings,
+/// in the recipient's time zone.
+fn most_recent_schedule<Tz: TimeZone>(
+    schedule: DigestSchedule,
+    timezone: &Tz,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let period = period(schedule)?;
+    let local = now.with_timezone(timezone);
+    let mut date = local.date();
+    if schedule == DigestSchedule::Weekly {
+        date = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+    }
+
+    let mut scheduled = date.and_hms_opt(DIGEST_HOUR, 0, 0)?;
+    if scheduled > local {
+        scheduled = (date - period).and_hms_opt(DIGEST_HOUR, 0, 0)?;
+    }
+
+    Some(scheduled.with_timezone(&Utc))
+}
+
+/// Returns the time range a digest sent at `now` should cover, or None if no
+/// digest is due. A digest is due once per scheduled time. Because the range
+/// starts when the previous digest was sent, a digest delayed by downtime
+/// still covers everything since the last one.
+pub fn due_window<Tz: TimeZone>(
+    schedule: DigestSchedule,
+    timezone: &Tz,
+    last_sent_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let scheduled = most_recent_schedule(schedule, timezone, now)?;
+    match last_sent_at {
+        Some(last_sent_at) if last_sent_at >= scheduled => None,
+        Some(last_sent_at) => Some((last_sent_at, now)),
+        None => Some((scheduled - period(schedule)?, now)),
+    }
+}
+
+/// Queues a digest for `settings`'s account if one is due, returning whether
+/// one was queued. The send time is recorded in the same transaction as the
+/// email is queued, so a restart can't cause a digest to be sent twice.
+pub async fn send_if_due(settings: &DigestSettings, now: DateTime<Utc>) -> anyhow::Result<bool> {
+    let address = match &settings.email {
+        Some(address) => address,
+        None => return Ok(false),
+    };
+
+    let timezone = settings
+        .timezone
+        .as_ref()
+        .and_then(|name| name.parse::<chrono_tz::Tz>().ok())
+        .unwrap_or_else(SiteDefaultTimezone::get_for_chrono);
+    let (since, until) = match due_window(settings.schedule, &timezone, settings.last_sent_at, now)
+    {
+        Some(window) => window,
+        None => return Ok(false),
+    };
+
+    let contents = DigestContents::load(settings.account_id, since, until).await?;
+
+    let mut tx = database::pool().begin().await?;
+    // Quiet periods don't generate an email, but still count as a sent digest
+    let queued = if contents.is_empty() {
+        false
+    } else {
+        let message = compose(
+            &SitePrimaryLocale::get().unwrap(),
+            &SiteName::get().unwrap(),
+            &SiteBaseUrl::get().unwrap(),
+            &SiteIssuePrefix::get().unwrap(),
+            settings.account_id,
+            settings.schedule,
+            &contents,
+        );
+        EmailDelivery::enqueue(
+            Some(settings.account_id),
+            address,
+            &message.subject,
+            &message.body,
+            &mut tx,
+        )
+        .await?;
+        true
+    };
+    DigestSettings::mark_sent(settings.account_id, until, &mut tx).await?;
+    tx.commit().await?;
+
+    Ok(queued)
+}
+
+fn compose(
+    language: &str,
+    site: &str,
+    base_url: &str,
+    issue_prefix: &str,
+    recipient_id: i64,
+    schedule: DigestSchedule,
+    contents: &DigestContents,
+) -> EmailMessage {
+    let base_url = base_url.trim_end_matches('/');
+    let mut args = HashMap::new();
+    args.insert(String::from("site"), FluentValue::from(site));
+    let subject = localize(
+        language,
+        &format!("email-digest-subject-{}", schedule.as_str()),
+        &args,
+    );
+
+    let mut sections = Vec::new();
+    for (key, issues) in &[
+        ("email-digest-new-issues", &contents.new_issues),
+        ("email-digest-changed-issues", &contents.changed_issues),
+        ("email-digest-unblocked-issues", &contents.unblocked_issues),
+    ] {
+        if issues.is_empty() {
+            continue;
+        }
+
+        let mut section = localize(language, key, &HashMap::new());
+        for issue in issues.iter() {
+            section.push_str(&format!(
+                "\n  {} {} - {}/issue/{}",
+                issue_key(
+                    issue_prefix,
+                    issue.id,
+                    issue.project_key.as_deref(),
+                    issue.number
+                ),
+                issue.summary,
+                base_url,
+                issue.id
+            ));
+        }
+        sections.push(section);
+    }
+
+    args.insert(
+        String::from("url"),
+        FluentValue::String(Cow::Owned(format!(
+            "{}/user/{}/edit",
+            base_url, recipient_id
+        ))),
+    );
+    sections.push(format!(
+        "-- \n{}",
+        localize(language, "email-digest-footer", &args)
+    ));
+
+    EmailMessage {
+        subject,
+        body: sections.join("\n\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.ymd(year, month, day).and_hms(hour, 0, 0)
+    }
+
+    #[test]
+    fn daily_digest_is_due_once_per_morning() {
+        let tz = chrono_tz::US::Pacific;
+        // 2021-01-05 08:00 PST is 16:00 UTC
+        let before = utc(2021, 1, 5, 15);
+        let after = utc(2021, 1, 5, 17);
+
+        let sent_yesterday = Some(utc(2021, 1, 4, 16));
+        assert_eq!(
+            due_window(DigestSchedule::Daily, &tz, sent_yesterday, before),
+            None
+        );
+        assert_eq!(
+            due_window(DigestSchedule::Daily, &tz, sent_yesterday, after),
+            Some((utc(2021, 1, 4, 16), after))
+        );
+
+        // Restarting after sending doesn't send again
+        assert_eq!(
+            due_window(DigestSchedule::Daily, &tz, Some(after), utc(2021, 1, 5, 23)),
+            None
+        );
+
+        // A never-sent digest covers the previous day
+        assert_eq!(
+            due_window(DigestSchedule::Daily, &tz, None, after),
+            Some((utc(2021, 1, 4, 16), after))
+        );
+    }
+
+    #[test]
+    fn weekly_digest_is_due_on_monday() {
+        let tz = chrono_tz::UTC;
+        // 2021-01-04 is a Monday
+        let sent_last_monday = Some(utc(2020, 12, 28, 8));
+        assert_eq!(
+            due_window(
+                DigestSchedule::Weekly,
+                &tz,
+                sent_last_monday,
+                utc(2021, 1, 3, 12)
+            ),
+            None
+        );
+        assert_eq!(
+            due_window(
+                DigestSchedule::Weekly,
+                &tz,
+                sent_last_monday,
+                utc(2021, 1, 6, 12)
+            ),
+            Some((utc(2020, 12, 28, 8), utc(2021, 1, 6, 12)))
+        );
+        assert_eq!(
+            due_window(DigestSchedule::None, &tz, None, utc(2021, 1, 6, 12)),
+            None
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use std::str::FromStr;
+
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio02Connector,
+};
+
+use crate::configuration::{
+    Configuration, SmtpFromAddress, SmtpHost, SmtpPassword, SmtpPort, SmtpTls, SmtpUsername,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsMode {
+    /// Plain text. Only appropriate for a relay on the local machine.
+    None,
+    /// Connect in plain text and upgrade using STARTTLS
+    StartTls,
+    /// Connect using TLS from the start
+    Tls,
+}
+
+impl FromStr for TlsMode {
+    type Err = MailerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(TlsMode::None),
+            "starttls" => Ok(TlsMode::StartTls),
+            "tls" => Ok(TlsMode::Tls),
+            other => Err(MailerError::InvalidTlsMode(other.to_string())),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MailerError {
+    #[error("invalid smtp tls mode '{0}'")]
+    InvalidTlsMode(String),
+    #[error("smtp from address not configured")]
+    MissingFromAddress,
+    #[error("invalid address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("error building message: {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("smtp error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct MailerSettings {
+    pub host: String,
+    pub port: u16,
+    pub tls: TlsMode,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from_address: String,
+}
+
+impl MailerSettings {
+    /// Loads the SMTP settings, returning None if no SMTP host is configured
+    pub fn from_configuration() -> Result<Option<Self>, MailerError> {
+        let host = match SmtpHost::get() {
+            Some(host) => host,
+            None => return Ok(None),
+        };
+
+        Ok(Some(Self {
+            host,
+            port: SmtpPort::get().unwrap(),
+            tls: TlsMode::from_str(&SmtpTls::get().unwrap())?,
+            username: SmtpUsername::get(),
+            password: SmtpPassword::get(),
+            from_address: SmtpFromAddress::get().ok_or(MailerError::MissingFromAddress)?,
+        }))
+    }
+}
+
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio02Connector>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub fn new(settings: MailerSettings) -> Result<Self, MailerError> {
+        let builder = match settings.tls {
+            TlsMode::None => {
+                AsyncSmtpTransport::<Tokio02Connector>::builder_dangerous(&settings.host)
+            }
+            TlsMode::StartTls => {
+                AsyncSmtpTransport::<Tokio02Connector>::starttls_relay(&settings.host)?
+            }
+            TlsMode::Tls => AsyncSmtpTransport::<Tokio02Connector>::relay(&settings.host)?,
+        };
+        let builder = builder.port(settings.port);
+        let builder = match (settings.username, settings.password) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username, password))
+            }
+            _ => builder,
+        };
+
+        Ok(Self {
+            transport: builder.build(),
+            from: settings.from_address.parse()?,
+        })
+    }
+
+    pub fn from_configuration() -> Result<Option<Self>, MailerError> {
+        MailerSettings::from_configuration()?
+            .map(Self::new)
+            .transpose()
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), MailerError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .body(body.to_string())?;
+
+        self.transport.send(message).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    /// Accepts a single SMTP session and returns the DATA it received
+    async fn smtp_sink(mut listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = BufReader::new(reader);
+        writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+        let mut data = String::new();
+        let mut in_data = false;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+
+            if in_data {
+                if line == ".\r\n" {
+                    in_data = false;
+                    writer.write_all(b"250 queued\r\n").await.unwrap();
+                } else {
+                    data.push_str(&line);
+                }
+                continue;
+            }
+
+            let command = line.to_ascii_uppercase();
+            let response: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                b"250 localhost\r\n"
+            } else if command.starts_with("DATA") {
+                in_data = true;
+                b"354 end data with <CR><LF>.<CR><LF>\r\n"
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 bye\r\n").await.unwrap();
+                break;
+            } else {
+                b"250 ok\r\n"
+            };
+            writer.write_all(response).await.unwrap();
+        }
+
+        data
+    }
+
+    #[rocket::async_test]
+    async fn sends_to_local_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+
+        let mailer = Mailer::new(MailerSettings {
+            host: String::from("127.0.0.1"),
+            port,
+            tls: TlsMode::None,
+            username: None,
+            password: None,
+            from_address: String::from("khonsubase@example.com"),
+        })
+        .unwrap();
+        mailer
+            .send(
+                "watcher@example.com",
+                "[Khonsubase] #1 Test issue",
+                "Someone commented on #1",
+            )
+            .await
+            .unwrap();
+
+        let data = sink.await.unwrap();
+        assert!(data.contains("Subject: [Khonsubase] #1 Test issue"));
+        assert!(data.contains("To: watcher@example.com"));
+        assert!(data.contains("Someone commented on #1"));
+    }
+}
@@ -0,0 +1,292 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use database::schema::{
+    accounts::User,
+    issues::{Issue, Resolution},
+    notifications::IssueEventKind,
+    webhooks::{Webhook, WebhookDelivery},
+};
+
+use crate::{
+    configuration::{Configuration, SiteBaseUrl},
+    notifications::IssueEvent,
+};
+
+/// The events that can be sent to webhooks
+pub const EVENT_KINDS: [IssueEventKind; 7] = [
+    IssueEventKind::Created,
+    IssueEventKind::Updated,
+    IssueEventKind::Commented,
+    IssueEventKind::Linked,
+    IssueEventKind::Unlinked,
+    IssueEventKind::Completed,
+    IssueEventKind::Reopened,
+];
+
+pub const SIGNATURE_HEADER: &str = "X-Khonsubase-Signature";
+pub const EVENT_HEADER: &str = "X-Khonsubase-Event";
+pub const DELIVERY_HEADER: &str = "X-Khonsubase-Delivery";
+
+/// Returns the hex-encoded HMAC-SHA256 of `body` keyed by `secret`. Receivers
+/// verify a payload by computing the same value and comparing it against the
+/// signature header, which is sent as `sha256=<signature>`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("hmac accepts any key");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Generates a secret for a webhook registered without one
+pub fn generate_secret() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().to_simple(),
+        uuid::Uuid::new_v4().to_simple()
+    )
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    event: &'a str,
+    issue: IssuePayload,
+    actor: User,
+    related_issue: Option<IssuePayload>,
+    comment: Option<&'a str>,
+    occurred_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct IssuePayload {
+    id: i64,
+    url: String,
+    summary: String,
+    project_id: Option<i64>,
+    parent_id: Option<i64>,
+    blocked: bool,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+    resolution: Option<Resolution>,
+}
+
+impl IssuePayload {
+    fn new(issue: Issue, base_url: &str) -> Self {
+        Self {
+            url: format!("{}/issue/{}", base_url, issue.id),
+            id: issue.id,
+            summary: issue.summary,
+            project_id: issue.project_id,
+            parent_id: issue.parent_id,
+            blocked: issue.blocked,
+            created_at: issue.created_at,
+            started_at: issue.started_at,
+            completed_at: issue.completed_at,
+            resolution: issue.resolution,
+        }
+    }
+}
+
+/// Queues a delivery of each event to every webhook subscribed to it. The
+/// payload is rendered now so that it describes the issue as it was when the
+/// event happened, regardless of when it's delivered.
+pub async fn enqueue(events: &[IssueEvent]) -> anyhow::Result<()> {
+    let base_url = SiteBaseUrl::get().unwrap();
+    let base_url = base_url.trim_end_matches('/');
+    let occurred_at = Utc::now();
+
+    for event in events {
+        // Events addressed to a single account, such as mentions, are private
+        if event.recipient_id.is_some() || !EVENT_KINDS.contains(&event.kind) {
+            continue;
+        }
+
+        let issue = Issue::load(event.issue_id).await?;
+        let webhooks =
+            Webhook::list_subscribed(issue.project_id, event.kind.as_str(), database::pool())
+                .await?;
+        if webhooks.is_empty() {
+            continue;
+        }
+
+        let related_issue = match event.related_issue_id {
+            Some(related_id) => Some(IssuePayload::new(Issue::load(related_id).await?, base_url)),
+            None => None,
+        };
+        let payload = serde_json::to_string(&Payload {
+            event: event.kind.as_str(),
+            issue: IssuePayload::new(issue, base_url),
+            actor: User::load(event.actor_id, database::pool()).await?,
+            related_issue,
+            comment: event.comment.as_deref(),
+            occurred_at,
+        })?;
+
+        let mut tx = database::pool().begin().await?;
+        for webhook in webhooks {
+            WebhookDelivery::enqueue(webhook.id, event.kind.as_str(), &payload, &mut tx).await?;
+        }
+        tx.commit().await?;
+    }
+
+    Ok(())
+}
+
+pub struct WebhookClient {
+    client: reqwest::Client,
+}
+
+impl WebhookClient {
+    pub fn new() -> reqwest::Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .user_agent(concat!("khonsubase/", env!("CARGO_PKG_VERSION")))
+                .build()?,
+        })
+    }
+
+    /// POSTs a signed payload, returning the response status. Any response,
+    /// successful or not, is returned as Ok; only transport errors are Err.
+    pub async fn send(
+        &self,
+        url: &str,
+        secret: &str,
+        delivery_id: i64,
+        event_kind: &str,
+        payload: &str,
+    ) -> reqwest::Result<u16> {
+        let response = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(
+                SIGNATURE_HEADER,
+                format!("sha256={}", sign(secret, payload.as_bytes())),
+            )
+            .header(EVENT_HEADER, event_kind)
+            .header(DELIVERY_HEADER, delivery_id.to_string())
+            .body(payload.to_string())
+            .send()
+            .await?;
+
+        Ok(response.status().as_u16())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    #[test]
+    fn signature_matches_rfc_4231() {
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    /// Accepts a single HTTP request, responding with `status`, and returns
+    /// the request's headers and body
+    async fn http_receiver(
+        mut listener: TcpListener,
+        status: u16,
+    ) -> (HashMap<String, String>, String) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = BufReader::new(reader);
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(separator) = line.find(':') {
+                headers.insert(
+                    line[..separator].to_ascii_lowercase(),
+                    line[separator + 1..].trim().to_string(),
+                );
+            }
+        }
+
+        let length = headers["content-length"].parse::<usize>().unwrap();
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await.unwrap();
+
+        writer
+            .write_all(
+                format!(
+                    "HTTP/1.1 {} Test\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+
+        (headers, String::from_utf8(body).unwrap())
+    }
+
+    #[rocket::async_test]
+    async fn sends_signed_payload_to_local_receiver() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let receiver = tokio::spawn(http_receiver(listener, 204));
+
+        let payload = r#"{"event":"commented"}"#;
+        let status = WebhookClient::new()
+            .unwrap()
+            .send(
+                &format!("http://127.0.0.1:{}/hook", port),
+                "s3cret",
+                42,
+                "commented",
+                payload,
+            )
+            .await
+            .unwrap();
+        assert_eq!(status, 204);
+
+        let (headers, body) = receiver.await.unwrap();
+        assert_eq!(body, payload);
+        assert_eq!(
+            headers["x-khonsubase-signature"],
+            format!("sha256={}", sign("s3cret", body.as_bytes()))
+        );
+        assert_eq!(headers["x-khonsubase-event"], "commented");
+        assert_eq!(headers["x-khonsubase-delivery"], "42");
+    }
+
+    #[rocket::async_test]
+    async fn error_responses_are_returned_as_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let receiver = tokio::spawn(http_receiver(listener, 500));
+
+        let status = WebhookClient::new()
+            .unwrap()
+            .send(
+                &format!("http://127.0.0.1:{}/hook", port),
+                "s3cret",
+                1,
+                "created",
+                "{}",
+            )
+            .await
+            .unwrap();
+        assert_eq!(status, 500);
+        receiver.await.unwrap();
+    }
+}
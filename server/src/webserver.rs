@@ -21,6 +21,7 @@ use rocket_contrib::{
 use serde::{Deserialize, Serialize};
 
 use database::{
+    schema::notifications::Notification,
     sqlx,
     sqlx::types::chrono::{DateTime, Utc},
 };
@@ -30,14 +31,25 @@ use crate::configuration::{
     Configuration, ConfigurationManager, SiteDefaultTimezone, SiteIssuePrefix, SiteName,
 };
 
-use self::auth::{SessionData, SessionId};
+use self::{
+    articles::MarkdownLinks,
+    auth::{SessionData, SessionId},
+};
 
-mod articles;
+pub(crate) mod articles;
+mod attachments;
 mod auth;
+mod git_push;
+mod issue_graph;
 mod issues;
-mod localization;
+mod iterations;
+pub(crate) mod localization;
+mod milestones;
+mod notifications;
 mod projects;
 mod users;
+mod webhooks;
+mod workflows;
 
 fn rocket_server() -> rocket::Rocket {
     let root_path = if let Ok(value) = env::var("CARGO_MANIFEST_DIR") {
@@ -89,23 +101,69 @@ fn rocket_server() -> rocket::Rocket {
                 auth::change_password_post,
                 articles::article_by_slug,
                 articles::home,
+                attachments::upload_attachment,
+                attachments::upload_image,
+                attachments::download_attachment,
+                attachments::attachment_thumbnail,
+                attachments::delete_attachment,
+                git_push::receive_push,
+                issue_graph::issue_graph,
                 issues::new_issue,
                 issues::save_issue,
                 issues::edit_issue,
                 issues::view_issue,
+                issues::view_issue_by_key,
+                issues::issue_schedule,
+                issues::edit_issue_tree,
+                issues::move_issue,
+                issues::move_issue_project,
+                issues::move_issue_project_post,
                 issues::list_issues,
                 issues::link_issue,
                 issues::link_issue_post,
                 issues::unlink_issue,
+                issues::watch_issue,
+                issues::unwatch_issue,
+                notifications::list_notifications,
+                notifications::read_notification,
+                notifications::read_all_notifications,
+                notifications::save_notification_preferences,
                 users::view_user,
+                users::autocomplete_users,
                 users::edit_user,
                 users::save_user,
                 users::user_avatar,
+                users::upload_avatar,
+                users::remove_avatar,
                 projects::new_project,
                 projects::view_project,
                 projects::view_project_by_slug,
+                projects::project_schedule,
                 projects::edit_project,
                 projects::save_project,
+                projects::watch_project,
+                projects::unwatch_project,
+                milestones::view_milestone,
+                milestones::new_milestone,
+                milestones::edit_milestone,
+                milestones::save_milestone,
+                iterations::list_iterations,
+                iterations::view_iteration,
+                iterations::new_iteration,
+                iterations::edit_iteration,
+                iterations::save_iteration,
+                webhooks::list_site_webhooks,
+                webhooks::list_project_webhooks,
+                webhooks::view_webhook,
+                webhooks::save_webhook,
+                webhooks::delete_webhook,
+                workflows::view_default_workflow,
+                workflows::view_project_workflow,
+                workflows::customize_workflow,
+                workflows::reset_workflow,
+                workflows::save_workflow_state,
+                workflows::delete_workflow_state,
+                workflows::save_workflow_transitions,
             ],
         )
         .mount("/static", StaticFiles::from(root_path.join("static")))
@@ -175,6 +233,7 @@ pub struct RequestData {
     pub current_path_and_query: String,
     pub session: Option<SessionData>,
     pub time_zone: String,
+    pub unread_notifications: i64,
 }
 
 #[derive(Debug)]
@@ -205,6 +264,13 @@ impl RequestData {
             .get::<SiteDefaultTimezone>()
             .unwrap();
 
+        let unread_notifications = match &session {
+            Some(session) => Notification::unread_count(session.account.id, database::pool())
+                .await
+                .unwrap_or_default(),
+            None => 0,
+        };
+
         Self {
             language: language.0,
             current_path: path.path,
@@ -212,6 +278,7 @@ impl RequestData {
             session,
             current_path_and_query,
             time_zone,
+            unread_notifications,
         }
     }
 
@@ -235,14 +302,26 @@ impl<'a, 'r> FromRequest<'a, 'r> for FullPathAndQuery {
 struct MarkdownFilter;
 
 impl tera::Filter for MarkdownFilter {
-    fn filter(&self, markdown_source: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    fn filter(
+        &self,
+        markdown_source: &Value,
+        args: &HashMap<String, Value>,
+    ) -> tera::Result<Value> {
         if markdown_source.is_null() {
             Ok(Value::Null)
         } else {
-            let markdown =
-                articles::preformat_markdown(markdown_source.as_str().ok_or_else(|| {
+            let links = match args.get("links") {
+                Some(links) => serde_json::from_value(links.clone()).map_err(|err| {
+                    tera::Error::msg(format!("invalid links passed to markdown filter: {}", err))
+                })?,
+                None => MarkdownLinks::default(),
+            };
+            let markdown = articles::preformat_markdown(
+                markdown_source.as_str().ok_or_else(|| {
                     tera::Error::msg("Value passed to markdown filter needs to be a string")
-                })?);
+                })?,
+                &links,
+            );
             Ok(Value::String(comrak::markdown_to_html(
                 &markdown,
                 &ComrakOptions::default(),
@@ -308,6 +387,11 @@ impl Failure {
         Self::Redirect(Redirect::to(destination))
     }
 
+    /// Redirects with `301 Moved Permanently`, for pages whose address has changed
+    pub fn moved<U: TryInto<Uri<'static>>>(destination: U) -> Self {
+        Self::Redirect(Redirect::moved(destination))
+    }
+
     pub fn redirect_to_signin(origin: Option<&str>) -> Self {
         if let Some(origin) = origin {
             let origin = utf8_percent_encode(origin, QUERY);
@@ -1,9 +1,17 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
 use crate::webserver::localization::UserLanguage;
-use database::schema::cms::Article;
+use database::{
+    schema::{accounts::Account, attachments::Attachment, cms::Article, issues::Project},
+    sqlx,
+};
 use rocket_contrib::templates::Template;
 use serde::{Deserialize, Serialize};
 
-use super::{auth::SessionId, FullPathAndQuery, RequestData};
+use super::{auth::SessionId, Failure, FullPathAndQuery, RequestData};
 use crate::configuration::{Configuration, SiteIssuePrefix};
 
 pub fn find_article(slug: &str) -> Option<Article> {
@@ -20,12 +28,24 @@ pub fn find_article(slug: &str) -> Option<Article> {
     }
 }
 
+/// Articles are site content, so only administrators change them
+pub fn can_edit_article(request: &RequestData) -> bool {
+    request
+        .session
+        .as_ref()
+        .map(|session| session.account.administrator)
+        .unwrap_or_default()
+}
+
 #[derive(Serialize, Deserialize)]
 struct MarkdownContext {
     slug: String,
     request: RequestData,
     markdown: String,
     view_only: bool,
+    editable: bool,
+    attachments: Vec<Attachment>,
+    links: MarkdownLinks,
 }
 
 #[get("/")]
@@ -33,10 +53,8 @@ pub async fn home(
     language: UserLanguage,
     session: Option<SessionId>,
     path: FullPathAndQuery,
-) -> Template {
-    article_by_slug(String::from("home"), language, session, path)
-        .await
-        .unwrap()
+) -> Result<Template, Failure> {
+    render_article(find_article("home").unwrap(), language, session, path).await
 }
 
 #[get("/<slug>")]
@@ -45,9 +63,16 @@ pub async fn article_by_slug(
     language: UserLanguage,
     session: Option<SessionId>,
     path: FullPathAndQuery,
-) -> Result<Template, rocket::http::Status> {
-    let article = find_article(&slug.to_lowercase()).ok_or(rocket::http::Status::NotFound)?;
-    Ok(render_article(article, language, session, path).await)
+) -> Result<Template, Failure> {
+    let slug = slug.to_lowercase();
+    match find_article(&slug) {
+        Some(article) => render_article(article, language, session, path).await,
+        // Articles that have been renamed redirect to where they are now
+        None => match Article::current_slug(&slug, database::pool()).await? {
+            Some(current_slug) => Err(Failure::moved(format!("/{}", current_slug))),
+            None => Err(Failure::not_found()),
+        },
+    }
 }
 
 async fn render_article(
@@ -55,50 +80,480 @@ async fn render_article(
     language: UserLanguage,
     session: Option<SessionId>,
     path: FullPathAndQuery,
-) -> Template {
-    Template::render(
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let attachments = Attachment::list_for_article(&article.slug, database::pool()).await?;
+    let links = MarkdownLinks::load(vec![article.body.as_str()]).await?;
+    let editable = can_edit_article(&request);
+
+    Ok(Template::render(
         "markdown",
         MarkdownContext {
             slug: article.slug,
-            request: RequestData::new(language, path, session).await,
+            request,
             view_only: true,
-            markdown: preformat_markdown(&article.body),
+            editable,
+            attachments,
+            links,
+            markdown: article.body,
         },
+    ))
+}
+
+pub fn preformat_markdown(markdown: &str, links: &MarkdownLinks) -> String {
+    let issue_prefix = SiteIssuePrefix::get().unwrap();
+
+    link_mentions(
+        &link_issue_references(markdown, &issue_prefix, &links.project_keys),
+        &links.mentions,
     )
 }
 
-pub fn preformat_markdown(markdown: &str) -> String {
+/// An issue referenced from text, either by its id or by its project's key
+/// and its number within the project
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IssueReference {
+    Id(i64),
+    Key(String, i32),
+}
+
+/// Finds references to issues by id, like `KB-12`, and by project key and
+/// number, like `GOOEY-42`. Keys that aren't in `project_keys` are ignored,
+/// and where both forms match the same text the id takes precedence. Returns
+/// the byte offset and length of each reference, in order.
+pub fn find_all_issue_references(
+    text: &str,
+    issue_prefix: &str,
+    project_keys: &HashSet<String>,
+) -> Vec<(usize, usize, IssueReference)> {
+    let mut references = find_issue_references(text, issue_prefix)
+        .into_iter()
+        .map(|(offset, length, issue_id)| (offset, length, IssueReference::Id(issue_id)))
+        .collect::<Vec<_>>();
+    for (offset, length, key, number) in find_project_issue_references(text) {
+        let overlaps = references.iter().any(|&(other_offset, other_length, _)| {
+            offset < other_offset + other_length && other_offset < offset + length
+        });
+        if !overlaps && project_keys.contains(key) {
+            references.push((offset, length, IssueReference::Key(key.to_string(), number)));
+        }
+    }
+    references.sort_by_key(|(offset, _, _)| *offset);
+
+    references
+}
+
+fn link_issue_references(
+    markdown: &str,
+    issue_prefix: &str,
+    project_keys: &HashSet<String>,
+) -> String {
     let mut formatted = String::with_capacity(markdown.len());
-    let issue_prefix = SiteIssuePrefix::get().unwrap();
-    let mut parts = markdown.split(&issue_prefix);
-    formatted.push_str(parts.next().unwrap());
-
-    for part in parts {
-        // Find the first non-ascii-digit
-        let mut number_length = 0usize;
-        for c in part.chars() {
-            if c.is_ascii_digit() {
-                number_length += 1;
+    let mut copied = 0;
+    for (offset, length, reference) in
+        find_all_issue_references(markdown, issue_prefix, project_keys)
+    {
+        formatted.push_str(&markdown[copied..offset]);
+        formatted.push('[');
+        formatted.push_str(&markdown[offset..offset + length]);
+        formatted.push_str("](/issue/");
+        match reference {
+            IssueReference::Id(issue_id) => formatted.push_str(&issue_id.to_string()),
+            IssueReference::Key(key, number) => formatted.push_str(&format!("{}-{}", key, number)),
+        }
+        formatted.push(')');
+        copied = offset + length;
+    }
+    formatted.push_str(&markdown[copied..]);
+
+    formatted
+}
+
+/// Finds references to issues, which are the issue prefix immediately
+/// followed by the issue's id. Returns the byte offset and length of each
+/// reference along with the referenced id.
+pub fn find_issue_references(text: &str, issue_prefix: &str) -> Vec<(usize, usize, i64)> {
+    let mut references = Vec::new();
+    for (offset, _) in text.match_indices(issue_prefix) {
+        let number_start = offset + issue_prefix.len();
+        let number_length = text[number_start..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        if number_length == 0 {
+            continue;
+        }
+
+        if let Ok(issue_id) = text[number_start..number_start + number_length].parse() {
+            references.push((offset, issue_prefix.len() + number_length, issue_id));
+        }
+    }
+
+    references
+}
+
+/// Finds what look like references to issues by project key and number, such
+/// as `GOOEY-42`. Keys are uppercase letters and numbers starting with a
+/// letter, and a reference can't be part of a longer word. Returns the byte
+/// offset and length of each reference along with its key and number.
+pub fn find_project_issue_references(text: &str) -> Vec<(usize, usize, &str, i32)> {
+    let bytes = text.as_bytes();
+    // Anything joined to a word, including paths, isn't a reference
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'-' || b == b'/';
+    let mut references = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let starts_word = offset == 0 || !is_word(bytes[offset - 1]);
+        if !starts_word || !bytes[offset].is_ascii_uppercase() {
+            offset += 1;
+            continue;
+        }
+
+        let key_length = bytes[offset..]
+            .iter()
+            .take_while(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+            .count();
+        let separator = offset + key_length;
+        let number_length = bytes[(separator + 1).min(bytes.len())..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        let end = separator + 1 + number_length;
+        if bytes.get(separator) == Some(&b'-')
+            && number_length > 0
+            && bytes.get(end).map_or(true, |&b| !b.is_ascii_alphanumeric())
+        {
+            if let Ok(number) = text[separator + 1..end].parse() {
+                references.push((offset, end - offset, &text[offset..separator], number));
+            }
+            offset = end;
+        } else {
+            offset = separator.max(offset + 1);
+        }
+    }
+
+    references
+}
+
+/// Finds `@username` mentions, returning the byte offset of each `@` and the
+/// lowercased username. An `@` preceded by a letter or number isn't a mention,
+/// which keeps email addresses from matching, and nothing in code is.
+pub fn find_mentions(markdown: &str) -> Vec<(usize, String)> {
+    let code = code_ranges(markdown);
+    let mut mentions = Vec::new();
+    let mut previous = None;
+    let mut chars = markdown.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let preceded_by_word = previous.map_or(false, |p: char| p.is_alphanumeric());
+        previous = Some(c);
+        if c != '@' || preceded_by_word || code.iter().any(|range| range.contains(&offset)) {
+            continue;
+        }
+
+        let mut username = String::new();
+        while let Some((_, next)) = chars.peek() {
+            if next.is_ascii_alphanumeric() {
+                username.push(next.to_ascii_lowercase());
+                previous = Some(*next);
+                chars.next();
             } else {
                 break;
             }
         }
 
-        if number_length > 0 {
-            let number = &part[0..number_length];
-            let remaining_part = &part[number_length..];
+        // Usernames are at least 3 characters long
+        if username.len() >= 3 {
+            mentions.push((offset, username));
+        }
+    }
+
+    mentions
+}
+
+/// Finds the byte ranges of code in `markdown`: fenced and indented code
+/// blocks, and inline code spans. Indented lines only start a code block at
+/// the top level, after a blank line that doesn't follow a list.
+fn code_ranges(markdown: &str) -> Vec<Range<usize>> {
+    let mut blocks = Vec::new();
+    // The fence's character, its length and where the block started
+    let mut fence: Option<(char, usize, usize)> = None;
+    let mut indented_start = None;
+    let mut previous_blank = true;
+    let mut in_list = false;
+    let mut line_start = 0;
+    while line_start < markdown.len() {
+        let line_end = markdown[line_start..]
+            .find('\n')
+            .map_or(markdown.len(), |end| line_start + end + 1);
+        let line = &markdown[line_start..line_end];
+        let trimmed = line.trim_start_matches(' ');
+        let indent = line.len() - trimmed.len();
+        let fence_length = |c: char| trimmed.chars().take_while(|&next| next == c).count();
+
+        if let Some((fence_char, length, start)) = fence {
+            if indent < 4
+                && fence_length(fence_char) >= length
+                && trimmed.trim_start_matches(fence_char).trim().is_empty()
+            {
+                blocks.push(start..line_end);
+                fence = None;
+            }
+        } else if line.trim().is_empty() {
+            previous_blank = true;
+        } else if (indent >= 4 || line.starts_with('\t'))
+            && (indented_start.is_some() || (previous_blank && !in_list))
+        {
+            indented_start.get_or_insert(line_start);
+        } else {
+            if let Some(start) = indented_start.take() {
+                blocks.push(start..line_start);
+            }
+            let fence_char = trimmed.chars().next().filter(|&c| c == '`' || c == '~');
+            match fence_char.map(|c| (c, fence_length(c))) {
+                Some((fence_char, length)) if indent < 4 && length >= 3 => {
+                    fence = Some((fence_char, length, line_start));
+                }
+                _ => {
+                    let marker = trimmed.trim_start_matches(|c: char| c.is_ascii_digit());
+                    let numbered = marker.len() < trimmed.len()
+                        && (marker.starts_with(". ") || marker.starts_with(") "));
+                    let bulleted = ["- ", "* ", "+ "]
+                        .iter()
+                        .any(|bullet| trimmed.starts_with(bullet));
+                    if numbered || bulleted {
+                        in_list = true;
+                    } else if previous_blank && indent == 0 {
+                        in_list = false;
+                    }
+                }
+            }
+            previous_blank = false;
+        }
+        line_start = line_end;
+    }
+    if let Some((_, _, start)) = fence {
+        blocks.push(start..markdown.len());
+    }
+    if let Some(start) = indented_start {
+        blocks.push(start..markdown.len());
+    }
+
+    let mut ranges = blocks.clone();
+    let bytes = markdown.as_bytes();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        if let Some(block) = blocks.iter().find(|block| block.contains(&offset)) {
+            offset = block.end;
+            continue;
+        }
+        if bytes[offset] != b'`' || (offset > 0 && bytes[offset - 1] == b'\\') {
+            offset += 1;
+            continue;
+        }
+
+        // A span is closed by the next run of the same number of backticks,
+        // and doesn't continue past the end of the paragraph
+        let length = bytes[offset..].iter().take_while(|&&b| b == b'`').count();
+        let content_start = offset + length;
+        let paragraph_end = markdown[content_start..]
+            .find("\n\n")
+            .map_or(markdown.len(), |end| content_start + end);
+        let mut search = content_start;
+        let mut closed = None;
+        while search < paragraph_end {
+            if bytes[search] == b'`' {
+                let run = bytes[search..].iter().take_while(|&&b| b == b'`').count();
+                if run == length {
+                    closed = Some(search + run);
+                    break;
+                }
+                search += run;
+            } else {
+                search += 1;
+            }
+        }
+        match closed {
+            Some(end) => {
+                ranges.push(offset..end);
+                offset = end;
+            }
+            None => offset = content_start,
+        }
+    }
+
+    ranges
+}
+
+/// Resolves usernames to account ids. Usernames that don't exist are omitted.
+pub async fn resolve_mentions(usernames: HashSet<String>) -> sqlx::Result<HashMap<String, i64>> {
+    if usernames.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let usernames = usernames.into_iter().collect::<Vec<_>>();
+    Ok(Account::ids_by_username(&usernames, database::pool())
+        .await?
+        .into_iter()
+        .collect())
+}
+
+/// What the markdown on a page links to that has to be looked up in the
+/// database. Markdown is rendered from within Tera's synchronous filters, so
+/// this is loaded once per request and passed to `render_markdown` as its
+/// `links` argument.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct MarkdownLinks {
+    /// Account ids of the mentioned usernames
+    pub mentions: HashMap<String, i64>,
+    /// The keys of every project, when the markdown references any issue by
+    /// project key
+    pub project_keys: HashSet<String>,
+}
+
+impl MarkdownLinks {
+    pub async fn load<'a, I: IntoIterator<Item = &'a str>>(markdown: I) -> sqlx::Result<Self> {
+        let markdown = markdown.into_iter().collect::<Vec<_>>();
+        let usernames = markdown
+            .iter()
+            .flat_map(|markdown| find_mentions(markdown))
+            .map(|(_, username)| username)
+            .collect();
+        let project_keys = if markdown
+            .iter()
+            .all(|markdown| find_project_issue_references(markdown).is_empty())
+        {
+            HashSet::new()
+        } else {
+            Project::list_keys().await?.into_iter().collect()
+        };
+
+        Ok(Self {
+            mentions: resolve_mentions(usernames).await?,
+            project_keys,
+        })
+    }
+}
+
+fn link_mentions(markdown: &str, accounts: &HashMap<String, i64>) -> String {
+    let mut formatted = String::with_capacity(markdown.len());
+    let mut copied_through = 0;
+    for (offset, username) in find_mentions(markdown) {
+        if let Some(account_id) = accounts.get(&username) {
+            let end = offset + 1 + username.len();
+            formatted.push_str(&markdown[copied_through..offset]);
             formatted.push('[');
-            formatted.push_str(&issue_prefix);
-            formatted.push_str(number);
-            formatted.push_str("](/issue/");
-            formatted.push_str(number);
+            formatted.push_str(&markdown[offset..end]);
+            formatted.push_str("](/user/");
+            formatted.push_str(&account_id.to_string());
             formatted.push(')');
-            formatted.push_str(remaining_part);
-        } else {
-            formatted.push_str(&issue_prefix);
-            formatted.push_str(part);
+            copied_through = end;
         }
     }
+    formatted.push_str(&markdown[copied_through..]);
 
     formatted
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_are_found() {
+        assert_eq!(
+            find_mentions("@alice and @Bob, but not @me or bob@example.com"),
+            vec![(0, String::from("alice")), (11, String::from("bob"))]
+        );
+        assert_eq!(
+            find_mentions("(cc @carol)"),
+            vec![(4, String::from("carol"))]
+        );
+    }
+
+    #[test]
+    fn mentions_in_code_are_ignored() {
+        assert_eq!(
+            find_mentions("`@alice` and ``say `@bob` `` but @carol"),
+            vec![(33, String::from("carol"))]
+        );
+        assert_eq!(
+            find_mentions("```\n@alice\n```\n~~~~ rust\n@bob\n~~~~\n@carol"),
+            vec![(35, String::from("carol"))]
+        );
+        assert_eq!(
+            find_mentions("Paragraph\n\n    @alice\n\n@bob"),
+            vec![(23, String::from("bob"))]
+        );
+        assert_eq!(
+            find_mentions("- item\n\n    @alice continues the item"),
+            vec![(12, String::from("alice"))]
+        );
+        assert_eq!(
+            find_mentions("An unclosed ` doesn't hide @alice"),
+            vec![(27, String::from("alice"))]
+        );
+    }
+
+    #[test]
+    fn mentions_are_linked() {
+        let accounts = vec![(String::from("alice"), 4)].into_iter().collect();
+        assert_eq!(
+            link_mentions("@Alice, @bob and @alice", &accounts),
+            "[@Alice](/user/4), @bob and [@alice](/user/4)"
+        );
+    }
+
+    #[test]
+    fn project_issue_references_are_found() {
+        assert_eq!(
+            find_project_issue_references("GOOEY-42, (KB2-7) and UI-1."),
+            vec![(0, 8, "GOOEY", 42), (11, 5, "KB2", 7), (22, 4, "UI", 1)]
+        );
+        assert_eq!(
+            find_project_issue_references("UTF-8x my-GOOEY-4 gooey-4 GOOEY- 4 /issue/GOOEY-4"),
+            Vec::<(usize, usize, &str, i32)>::new()
+        );
+    }
+
+    #[test]
+    fn issue_references_are_linked() {
+        let project_keys = vec![String::from("KB"), String::from("GOOEY")]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            link_issue_references("KB-12, GOOEY-4 and NOPE-1", "KB-", &project_keys),
+            "[KB-12](/issue/12), [GOOEY-4](/issue/GOOEY-4) and NOPE-1"
+        );
+    }
+
+    #[test]
+    fn issue_ids_take_precedence_over_project_keys() {
+        let project_keys = vec![String::from("KB")].into_iter().collect();
+        assert_eq!(
+            find_all_issue_references("KB-12 and (KB-3)", "KB-", &project_keys),
+            vec![
+                (0, 5, IssueReference::Id(12)),
+                (11, 4, IssueReference::Id(3))
+            ]
+        );
+        assert_eq!(
+            find_all_issue_references("#12 and KB-3", "#", &project_keys),
+            vec![
+                (0, 3, IssueReference::Id(12)),
+                (8, 4, IssueReference::Key(String::from("KB"), 3))
+            ]
+        );
+    }
+
+    #[test]
+    fn issue_references_are_found() {
+        assert_eq!(
+            find_issue_references("Fixes KB-12 and KB-3, not KB- or KB-x", "KB-"),
+            vec![(6, 5, 12), (16, 4, 3)]
+        );
+        assert_eq!(
+            find_issue_references("#1#22", "#"),
+            vec![(0, 2, 1), (2, 3, 22)]
+        );
+    }
+}
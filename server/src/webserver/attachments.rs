@@ -0,0 +1,297 @@
+use rocket::{
+    data::{Data, ToByteUnit},
+    http::{ContentType, Header, Status},
+    response::content::Content,
+};
+use rocket_contrib::json::Json;
+use serde::{Deserialize, Serialize};
+
+use database::schema::{
+    attachments::Attachment,
+    issues::{Issue, IssueRevision, IssueView},
+};
+
+use crate::{
+    attachments::{self, AttachmentError, StorageError},
+    configuration::{AttachmentMaximumBytes, Configuration},
+    webserver::{
+        articles::{self, can_edit_article},
+        auth::SessionId,
+        issues::{can_edit_issue, can_view_issue},
+        localization::UserLanguage,
+        Failure, FullPathAndQuery, RequestData, ResultExt,
+    },
+};
+
+pub fn can_view_attachment(request: &RequestData, attachment: &Attachment) -> bool {
+    if attachment.is_orphaned() {
+        // Until an attachment is associated with something, only the uploader can see it
+        request
+            .session
+            .as_ref()
+            .map(|s| s.account.administrator || s.account.id == attachment.author_id)
+            .unwrap_or_default()
+    } else {
+        // Issues and articles are currently visible to everyone. Attachments on a
+        // comment inherit the visibility of the issue being commented on.
+        true
+    }
+}
+
+pub fn can_delete_attachment(request: &RequestData, attachment: &Attachment) -> bool {
+    request
+        .session
+        .as_ref()
+        .map(|s| s.account.administrator || s.account.id == attachment.author_id)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttachmentInfo {
+    pub id: i64,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    pub url: String,
+    pub thumbnail_url: Option<String>,
+}
+
+impl From<&Attachment> for AttachmentInfo {
+    fn from(attachment: &Attachment) -> Self {
+        Self {
+            id: attachment.id,
+            filename: attachment.filename.clone(),
+            content_type: attachment.content_type.clone(),
+            size: attachment.size,
+            url: attachment.permalink(),
+            thumbnail_url: attachment
+                .thumbnail_key
+                .as_ref()
+                .map(|_| format!("/attachment/{}/thumbnail", attachment.id)),
+        }
+    }
+}
+
+async fn receive_upload(
+    request: &RequestData,
+    filename: &str,
+    data: Data,
+) -> Result<Attachment, Failure> {
+    let session = match &request.session {
+        Some(session) => session,
+        None => return Err(Failure::forbidden()),
+    };
+
+    let maximum_bytes = AttachmentMaximumBytes::get().unwrap();
+    // Read one byte past the limit so that we can tell an upload that is exactly the limit from one that was truncated
+    let bytes = data.open((maximum_bytes + 1).bytes()).into_bytes().await?;
+
+    match attachments::ingest(session.account.id, filename, bytes, maximum_bytes).await {
+        Ok(attachment) => Ok(attachment),
+        Err(AttachmentError::TooLarge) => Err(Failure::Status(Status::PayloadTooLarge)),
+        Err(AttachmentError::Storage(err)) => Err(err.into()),
+    }
+}
+
+/// Uploads a file attached to an issue or an article. Files uploaded without
+/// either are orphaned until they're claimed.
+#[post(
+    "/attachments/upload?<filename>&<issue_id>&<article_slug>",
+    data = "<data>"
+)]
+pub async fn upload_attachment(
+    filename: String,
+    issue_id: Option<i64>,
+    article_slug: Option<String>,
+    data: Data,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Json<AttachmentInfo>, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    match (issue_id, &article_slug) {
+        (Some(_), Some(_)) => return Err(Failure::Status(Status::BadRequest)),
+        (Some(issue_id), None) => {
+            let issue = Issue::load(issue_id).await.map_to_failure()?;
+            if !can_edit_issue(&request, &issue) {
+                return Err(Failure::forbidden());
+            }
+        }
+        (None, Some(article_slug)) => {
+            if articles::find_article(article_slug).is_none() {
+                return Err(Failure::not_found());
+            } else if !can_edit_article(&request) {
+                return Err(Failure::forbidden());
+            }
+        }
+        (None, None) => {}
+    }
+
+    let mut attachment = receive_upload(&request, &filename, data).await?;
+    attachment.issue_id = issue_id;
+    attachment.article_slug = article_slug;
+    attachment.save(database::pool()).await?;
+
+    Ok(Json(AttachmentInfo::from(&attachment)))
+}
+
+/// Accepts images pasted or dropped into an editor. The uploaded image isn't
+/// associated with anything until the content referencing it is saved, and
+/// uploads that are never saved are removed by the orphaned attachment cleanup job.
+#[post("/attachments/images?<filename>", data = "<data>")]
+pub async fn upload_image(
+    filename: String,
+    data: Data,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Json<AttachmentInfo>, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let mut attachment = receive_upload(&request, &filename, data).await?;
+    if !attachments::is_previewable_image(&attachment.content_type) {
+        attachments::remove_stored_data(&attachment).await?;
+        return Err(Failure::Status(Status::UnsupportedMediaType));
+    }
+
+    attachment.save(database::pool()).await?;
+
+    Ok(Json(AttachmentInfo::from(&attachment)))
+}
+
+#[derive(Responder)]
+pub struct AttachmentResponse {
+    content: Content<Vec<u8>>,
+    disposition: Header<'static>,
+    nosniff: Header<'static>,
+}
+
+impl AttachmentResponse {
+    fn new(content_type: &str, data: Vec<u8>, disposition: String) -> Self {
+        let content_type = ContentType::parse_flexible(content_type).unwrap_or(ContentType::Binary);
+        Self {
+            content: Content(content_type, data),
+            disposition: Header::new("Content-Disposition", disposition),
+            nosniff: Header::new("X-Content-Type-Options", "nosniff"),
+        }
+    }
+}
+
+fn map_storage_error(error: StorageError) -> Failure {
+    match error {
+        StorageError::NotFound => Failure::not_found(),
+        other => other.into(),
+    }
+}
+
+async fn load_viewable_attachment(
+    attachment_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Attachment, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let attachment = Attachment::load(attachment_id, database::pool())
+        .await
+        .map_to_failure()?;
+
+    // Attachments on a comment inherit the visibility of the issue being
+    // commented on
+    let issue_id = match (attachment.issue_id, attachment.issue_revision_id) {
+        (Some(issue_id), _) => Some(issue_id),
+        (None, Some(revision_id)) => Some(
+            IssueRevision::load(revision_id)
+                .await
+                .map_to_failure()?
+                .issue_id,
+        ),
+        (None, None) => None,
+    };
+    if let Some(issue_id) = issue_id {
+        let issue = IssueView::load(issue_id).await.map_to_failure()?;
+        if !can_view_issue(&request, &issue) {
+            return Err(Failure::not_found());
+        }
+    }
+
+    if can_view_attachment(&request, &attachment) {
+        Ok(attachment)
+    } else {
+        Err(Failure::not_found())
+    }
+}
+
+#[get("/attachment/<attachment_id>/file/<_filename>")]
+pub async fn download_attachment(
+    attachment_id: i64,
+    _filename: String,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<AttachmentResponse, Failure> {
+    let attachment = load_viewable_attachment(attachment_id, language, path, session).await?;
+    let data = attachments::storage()?
+        .retrieve(&attachment.storage_key)
+        .await
+        .map_err(map_storage_error)?;
+
+    // Only images we know how to decode are displayed inline. Everything else,
+    // including potentially active content like html or svg, is downloaded.
+    let disposition = if attachments::is_previewable_image(&attachment.content_type) {
+        "inline"
+    } else {
+        "attachment"
+    };
+
+    Ok(AttachmentResponse::new(
+        &attachment.content_type,
+        data,
+        format!("{}; filename=\"{}\"", disposition, attachment.filename),
+    ))
+}
+
+#[get("/attachment/<attachment_id>/thumbnail")]
+pub async fn attachment_thumbnail(
+    attachment_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<AttachmentResponse, Failure> {
+    let attachment = load_viewable_attachment(attachment_id, language, path, session).await?;
+    let thumbnail_key = attachment.thumbnail_key.ok_or_else(Failure::not_found)?;
+    let data = attachments::storage()?
+        .retrieve(&thumbnail_key)
+        .await
+        .map_err(map_storage_error)?;
+
+    Ok(AttachmentResponse::new(
+        "image/png",
+        data,
+        String::from("inline"),
+    ))
+}
+
+#[post("/attachment/<attachment_id>/delete")]
+pub async fn delete_attachment(
+    attachment_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let attachment = Attachment::load(attachment_id, database::pool())
+        .await
+        .map_to_failure()?;
+
+    if !can_delete_attachment(&request, &attachment) {
+        return Err(Failure::forbidden());
+    }
+
+    Attachment::delete(attachment.id, database::pool()).await?;
+    attachments::remove_stored_data(&attachment).await?;
+
+    match (attachment.issue_id, attachment.article_slug) {
+        (Some(issue_id), _) => Err(Failure::redirect(format!("/issue/{}", issue_id))),
+        (None, Some(article_slug)) => Err(Failure::redirect(format!("/{}", article_slug))),
+        (None, None) => Err(Failure::redirect("/")),
+    }
+}
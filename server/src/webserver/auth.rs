@@ -346,10 +346,8 @@ impl<'a, 'r> FromRequest<'a, 'r> for Referer {
 // mod tests {
 //     use core::panic;
 
-//     use crate::{
-//         test_helpers::{self, TEST_ACCOUNT_PASSWORD, TEST_ACCOUNT_USERNAME},
-//         webserver::rocket_server,
-//     };
+//     use crate::webserver::rocket_server;
+//     use database::test_helpers::{self, TEST_ACCOUNT_PASSWORD, TEST_ACCOUNT_USERNAME};
 //     use rocket::{
 //         http::{ContentType, Status},
 //         local::asynchronous::Client,
@@ -0,0 +1,520 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use rocket::{
+    data::{Data, ToByteUnit},
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+use rocket_contrib::json::Json;
+use serde::{Deserialize, Serialize};
+
+use database::schema::{
+    accounts::User,
+    issues::{Issue, IssueCommit, Project},
+};
+
+use crate::{
+    configuration::{Configuration, GitPushSecret, SiteIssuePrefix},
+    notifications::{self, IssueEvent},
+    webhooks,
+    webserver::{
+        articles::{self, IssueReference},
+        issues::{self, IssueUpdateError},
+        Failure,
+    },
+};
+
+/// Pushes larger than this are rejected rather than partially processed
+const MAXIMUM_PAYLOAD_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Words that, when immediately before an issue reference, complete the issue
+const CLOSING_KEYWORDS: [&str; 9] = [
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushFormat {
+    GitHub,
+    Gitea,
+    Generic,
+}
+
+/// The headers git hosts send alongside a push payload
+pub struct PushHeaders {
+    format: PushFormat,
+    event: Option<String>,
+    signature: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'a, 'r> FromRequest<'a, 'r> for PushHeaders {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        let header = |name: &str| headers.get_one(name).map(String::from);
+        // GitHub signs with a `sha256=` prefix and Gitea signs without one.
+        // Generic pushes use the same header as outgoing webhooks.
+        let (format, event, signature) = if let Some(event) = header("X-GitHub-Event") {
+            (
+                PushFormat::GitHub,
+                Some(event),
+                header("X-Hub-Signature-256"),
+            )
+        } else if let Some(event) = header("X-Gitea-Event") {
+            (
+                PushFormat::Gitea,
+                Some(event),
+                header("X-Gitea-Signature").map(|signature| format!("sha256={}", signature)),
+            )
+        } else {
+            (
+                PushFormat::Generic,
+                None,
+                header(webhooks::SIGNATURE_HEADER),
+            )
+        };
+
+        Outcome::Success(PushHeaders {
+            format,
+            event,
+            signature,
+        })
+    }
+}
+
+/// A push, normalized from any of the supported formats
+#[derive(Debug, PartialEq)]
+pub struct Push {
+    pub repository: String,
+    pub commits: Vec<PushedCommit>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PushedCommit {
+    pub sha: String,
+    pub message: String,
+    pub url: Option<String>,
+    pub author_name: String,
+    pub author_email: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// GitHub and Gitea send the same shape of push payload
+#[derive(Deserialize)]
+struct HostedPush {
+    repository: HostedRepository,
+    commits: Vec<HostedCommit>,
+}
+
+#[derive(Deserialize)]
+struct HostedRepository {
+    full_name: String,
+}
+
+#[derive(Deserialize)]
+struct HostedCommit {
+    id: String,
+    message: String,
+    url: Option<String>,
+    timestamp: Option<DateTime<Utc>>,
+    author: HostedAuthor,
+}
+
+#[derive(Deserialize)]
+struct HostedAuthor {
+    name: String,
+    email: Option<String>,
+}
+
+/// The generic format, for pushes from git hooks or other hosts
+#[derive(Deserialize)]
+struct GenericPush {
+    repository: String,
+    commits: Vec<GenericCommit>,
+}
+
+#[derive(Deserialize)]
+struct GenericCommit {
+    sha: String,
+    message: String,
+    url: Option<String>,
+    timestamp: Option<DateTime<Utc>>,
+    author_name: String,
+    author_email: Option<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PushError {
+    #[error("invalid payload: {0}")]
+    InvalidPayload(#[from] serde_json::Error),
+    #[error("invalid commit sha '{0}'")]
+    InvalidSha(String),
+}
+
+impl Push {
+    pub fn parse(format: PushFormat, body: &[u8]) -> Result<Self, PushError> {
+        let push = match format {
+            PushFormat::GitHub | PushFormat::Gitea => {
+                let push = serde_json::from_slice::<HostedPush>(body)?;
+                Push {
+                    repository: push.repository.full_name,
+                    commits: push
+                        .commits
+                        .into_iter()
+                        .map(|commit| PushedCommit {
+                            sha: commit.id,
+                            message: commit.message,
+                            url: commit.url,
+                            author_name: commit.author.name,
+                            author_email: commit.author.email,
+                            timestamp: commit.timestamp,
+                        })
+                        .collect(),
+                }
+            }
+            PushFormat::Generic => {
+                let push = serde_json::from_slice::<GenericPush>(body)?;
+                Push {
+                    repository: push.repository,
+                    commits: push
+                        .commits
+                        .into_iter()
+                        .map(|commit| PushedCommit {
+                            sha: commit.sha,
+                            message: commit.message,
+                            url: commit.url,
+                            author_name: commit.author_name,
+                            author_email: commit.author_email,
+                            timestamp: commit.timestamp,
+                        })
+                        .collect(),
+                }
+            }
+        };
+
+        for commit in &push.commits {
+            if commit.sha.is_empty() || !commit.sha.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(PushError::InvalidSha(commit.sha.clone()));
+            }
+        }
+
+        Ok(push)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitReference {
+    pub issue: IssueReference,
+    /// True if the reference was preceded by a closing keyword, e.g. "fixes KB-12"
+    pub closes: bool,
+}
+
+/// Finds the issues referenced by a commit message, by id or by project key
+/// and number, in the order they're first referenced
+pub fn commit_references(
+    message: &str,
+    issue_prefix: &str,
+    project_keys: &HashSet<String>,
+) -> Vec<CommitReference> {
+    let mut references: Vec<CommitReference> = Vec::new();
+    for (offset, _, issue) in
+        articles::find_all_issue_references(message, issue_prefix, project_keys)
+    {
+        let preceding_word = message[..offset]
+            .trim_end_matches(|c: char| c.is_whitespace() || c == ':')
+            .rsplit(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let closes = CLOSING_KEYWORDS.contains(&preceding_word.as_str());
+
+        match references.iter_mut().find(|r| r.issue == issue) {
+            Some(existing) => existing.closes |= closes,
+            None => references.push(CommitReference { issue, closes }),
+        }
+    }
+
+    references
+}
+
+/// Looks up the ids of the referenced issues, skipping issues that don't
+/// exist. An issue referenced both ways closes if either reference does.
+async fn resolve_references(
+    references: Vec<CommitReference>,
+) -> database::sqlx::Result<Vec<(i64, bool)>> {
+    let mut resolved: Vec<(i64, bool)> = Vec::new();
+    for reference in references {
+        let issue_id = match reference.issue {
+            IssueReference::Id(issue_id) => Issue::load(issue_id).await.map(|issue| issue.id),
+            IssueReference::Key(key, number) => {
+                Issue::find_by_key(&key, number, database::pool()).await
+            }
+        };
+        let issue_id = match issue_id {
+            Ok(issue_id) => issue_id,
+            Err(database::sqlx::Error::RowNotFound) => continue,
+            Err(other) => return Err(other),
+        };
+
+        match resolved.iter_mut().find(|(id, _)| *id == issue_id) {
+            Some((_, closes)) => *closes |= reference.closes,
+            None => resolved.push((issue_id, reference.closes)),
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Compares signatures without exiting early, so that response timing doesn't
+/// reveal how much of a forged signature was correct
+fn signatures_match(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len()
+        && expected
+            .bytes()
+            .zip(provided.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PushSummary {
+    linked: usize,
+    completed: Vec<i64>,
+    /// Issues a closing keyword couldn't complete, and why
+    not_completed: HashMap<i64, String>,
+}
+
+#[post("/git/push", data = "<data>")]
+pub async fn receive_push(headers: PushHeaders, data: Data) -> Result<Json<PushSummary>, Failure> {
+    let secret = match GitPushSecret::get() {
+        Some(secret) => secret,
+        None => return Err(Failure::not_found()),
+    };
+
+    // Read one byte past the limit so that a truncated payload can be detected
+    let body = data
+        .open((MAXIMUM_PAYLOAD_SIZE + 1).bytes())
+        .into_bytes()
+        .await?;
+    if body.len() as u64 > MAXIMUM_PAYLOAD_SIZE {
+        return Err(Failure::Status(Status::PayloadTooLarge));
+    }
+
+    let expected = format!("sha256={}", webhooks::sign(&secret, &body));
+    match &headers.signature {
+        Some(signature) if signatures_match(&expected, signature) => {}
+        _ => return Err(Failure::forbidden()),
+    }
+
+    // Hosts send other events, like pings when a webhook is first configured
+    if headers
+        .event
+        .as_deref()
+        .map_or(false, |event| event != "push")
+    {
+        return Ok(Json(PushSummary::default()));
+    }
+
+    let push = match Push::parse(headers.format, &body) {
+        Ok(push) => push,
+        Err(err) => {
+            error!("rejecting git push: {}", err);
+            return Err(Failure::Status(Status::BadRequest));
+        }
+    };
+
+    Ok(Json(process_push(push).await?))
+}
+
+async fn process_push(push: Push) -> Result<PushSummary, Failure> {
+    let mut events = Vec::new();
+    let summary = link_commits(push, &mut events).await;
+    // Issues completed before a failure stay completed, so their
+    // notifications go out either way
+    notifications::publish(events).await;
+    summary
+}
+
+async fn link_commits(push: Push, events: &mut Vec<IssueEvent>) -> Result<PushSummary, Failure> {
+    let issue_prefix = SiteIssuePrefix::get().unwrap();
+    let project_keys: HashSet<String> = Project::list_keys().await?.into_iter().collect();
+    let mut summary = PushSummary::default();
+    for commit in push.commits {
+        let references = resolve_references(commit_references(
+            &commit.message,
+            &issue_prefix,
+            &project_keys,
+        ))
+        .await?;
+        if references.is_empty() {
+            continue;
+        }
+
+        let author = match &commit.author_email {
+            Some(email) => User::find_by_email(email, database::pool()).await?,
+            None => None,
+        };
+
+        for (issue_id, closes) in references {
+            let mut issue_commit = IssueCommit::new(
+                issue_id,
+                push.repository.clone(),
+                commit.sha.clone(),
+                commit.message.clone(),
+                commit.timestamp.unwrap_or_else(Utc::now),
+            );
+            issue_commit.url = commit
+                .url
+                .clone()
+                .filter(|url| url.starts_with("https://") || url.starts_with("http://"));
+            issue_commit.author_name = commit.author_name.clone();
+            issue_commit.author_email = commit.author_email.clone();
+            issue_commit.author_id = author.as_ref().map(|author| author.id);
+            // The link and the completion share a transaction, so a failed
+            // completion is retried when the push is delivered again
+            let mut tx = database::pool().begin().await?;
+            if !issue_commit.link(&mut tx).await? {
+                // Already processed, e.g. when a commit is pushed to another branch
+                continue;
+            }
+            summary.linked += 1;
+
+            if !closes {
+                tx.commit().await?;
+                continue;
+            }
+
+            // Changes are attributed to an account, so only commits from known
+            // authors can complete issues
+            let author = match &author {
+                Some(author) => author,
+                None => {
+                    summary
+                        .not_completed
+                        .insert(issue_id, String::from("unknown author"));
+                    tx.commit().await?;
+                    continue;
+                }
+            };
+
+            match issues::complete_issue(issue_id, author.id, &mut tx).await {
+                Ok(completed) => {
+                    if !completed.is_empty() {
+                        summary.completed.push(issue_id);
+                    }
+                    events.extend(completed);
+                }
+                Err(IssueUpdateError::CantCloseBecauseBlocked) => {
+                    summary
+                        .not_completed
+                        .insert(issue_id, String::from("blocked"));
+                }
+                Err(IssueUpdateError::CantCloseBecauseOfChild) => {
+                    summary
+                        .not_completed
+                        .insert(issue_id, String::from("open children"));
+                }
+                Err(IssueUpdateError::TransitionNotAllowed) => {
+                    summary
+                        .not_completed
+                        .insert(issue_id, String::from("transition not allowed"));
+                }
+                Err(_) => return Err(Failure::Status(Status::InternalServerError)),
+            }
+            tx.commit().await?;
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn references(message: &str, issue_prefix: &str) -> Vec<(IssueReference, bool)> {
+        let project_keys = vec![String::from("KB"), String::from("GOOEY")]
+            .into_iter()
+            .collect();
+        commit_references(message, issue_prefix, &project_keys)
+            .into_iter()
+            .map(|reference| (reference.issue, reference.closes))
+            .collect()
+    }
+
+    #[test]
+    fn closing_keywords_complete_references() {
+        assert_eq!(
+            references("Fixes KB-12: crash when KB-3 is empty\n\nSee KB-12", "KB-"),
+            vec![
+                (IssueReference::Id(12), true),
+                (IssueReference::Id(3), false)
+            ]
+        );
+        assert_eq!(
+            references("Refactor prefix handling (resolves: KB-7)", "KB-"),
+            vec![(IssueReference::Id(7), true)]
+        );
+        assert_eq!(
+            references("Prefixes KB-7 but doesn't fix it", "KB-"),
+            vec![(IssueReference::Id(7), false)]
+        );
+    }
+
+    #[test]
+    fn project_keys_are_referenced() {
+        assert_eq!(
+            references("Fixes GOOEY-42 and #3, see KB-7 and UTF-8", "#"),
+            vec![
+                (IssueReference::Key(String::from("GOOEY"), 42), true),
+                (IssueReference::Id(3), false),
+                (IssueReference::Key(String::from("KB"), 7), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn github_and_generic_payloads_parse() {
+        let github = br#"{
+            "ref": "refs/heads/main",
+            "repository": { "full_name": "khonsulabs/khonsubase" },
+            "commits": [{
+                "id": "0d1a26e67d8f5eaf1f6ba5c57fc3c7d91ac0fd1c",
+                "message": "Fixes KB-1",
+                "timestamp": "2021-01-05T08:00:00-08:00",
+                "url": "https://github.com/khonsulabs/khonsubase/commit/0d1a26e",
+                "author": { "name": "Alice", "email": "alice@example.com", "username": "alice" }
+            }]
+        }"#;
+        let push = Push::parse(PushFormat::GitHub, github).unwrap();
+        assert_eq!(push.repository, "khonsulabs/khonsubase");
+        assert_eq!(
+            push.commits[0].author_email.as_deref(),
+            Some("alice@example.com")
+        );
+        assert_eq!(
+            push.commits[0].timestamp.unwrap().to_rfc3339(),
+            "2021-01-05T16:00:00+00:00"
+        );
+
+        let generic = br#"{
+            "repository": "khonsubase",
+            "commits": [{ "sha": "abc123", "message": "KB-2", "author_name": "Bob" }]
+        }"#;
+        let push = Push::parse(PushFormat::Generic, generic).unwrap();
+        assert_eq!(push.commits[0].sha, "abc123");
+        assert_eq!(push.commits[0].author_email, None);
+
+        let bad_sha = br#"{
+            "repository": "khonsubase",
+            "commits": [{ "sha": "<script>", "message": "KB-2", "author_name": "Bob" }]
+        }"#;
+        assert!(matches!(
+            Push::parse(PushFormat::Generic, bad_sha),
+            Err(PushError::InvalidSha(_))
+        ));
+    }
+}
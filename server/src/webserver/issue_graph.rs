@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use rocket_contrib::templates::Template;
+use serde::{Deserialize, Serialize};
+
+use database::schema::issues::{DependencyGraph, DependencyNode};
+
+use crate::webserver::{
+    auth::SessionId, localization::UserLanguage, Failure, FullPathAndQuery, RequestData, ResultExt,
+};
+
+const NODE_WIDTH: i64 = 240;
+const NODE_HEIGHT: i64 = 44;
+const COLUMN_GAP: i64 = 60;
+const ROW_GAP: i64 = 16;
+const MARGIN: i64 = 8;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GraphNode {
+    id: i64,
+    project_key: Option<String>,
+    number: Option<i32>,
+    summary: String,
+    /// One of `completed`, `started`, `blocked` or `open`, used to colour the node
+    status: String,
+    current: bool,
+    x: i64,
+    y: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct GraphEdge {
+    x1: i64,
+    y1: i64,
+    x2: i64,
+    y2: i64,
+}
+
+/// Blockers are laid out in columns to the left of what they block, the
+/// issue itself being in the rightmost column.
+#[derive(Debug, Serialize, Deserialize)]
+struct GraphLayout {
+    width: i64,
+    height: i64,
+    node_width: i64,
+    node_height: i64,
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+fn node_status(node: &DependencyNode) -> &'static str {
+    if node.completed_at.is_some() {
+        "completed"
+    } else if node.blocked {
+        "blocked"
+    } else if node.started_at.is_some() {
+        "started"
+    } else {
+        "open"
+    }
+}
+
+impl GraphLayout {
+    fn new(graph: &DependencyGraph) -> Self {
+        // Each blocker sits one column further left than the furthest-left
+        // issue it blocks. Blocking cycles are rejected when linking, but
+        // the number of passes is bounded in case older data has one.
+        let mut columns = HashMap::new();
+        columns.insert(graph.issue_id, 0);
+        for _ in 0..graph.nodes.len() {
+            let mut changed = false;
+            for edge in &graph.edges {
+                if let Some(&blocked_column) = columns.get(&edge.blocked_id) {
+                    let column = columns.entry(edge.blocker_id).or_insert(0);
+                    if *column < blocked_column + 1 {
+                        *column = blocked_column + 1;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let column_count = columns.values().max().map(|max| max + 1).unwrap_or(1);
+        let mut rows = vec![0; column_count];
+        let mut positions = HashMap::new();
+        let mut nodes = Vec::new();
+        for node in &graph.nodes {
+            let column = match columns.get(&node.id) {
+                Some(column) => *column,
+                None => continue,
+            };
+            let x = MARGIN + (column_count - 1 - column) as i64 * (NODE_WIDTH + COLUMN_GAP);
+            let y = MARGIN + rows[column] * (NODE_HEIGHT + ROW_GAP);
+            rows[column] += 1;
+            positions.insert(node.id, (x, y));
+            nodes.push(GraphNode {
+                id: node.id,
+                project_key: node.project_key.clone(),
+                number: node.number,
+                summary: node.summary.clone(),
+                status: node_status(node).to_string(),
+                current: node.id == graph.issue_id,
+                x,
+                y,
+            });
+        }
+
+        let edges = graph
+            .edges
+            .iter()
+            .filter_map(|edge| {
+                let (blocker_x, blocker_y) = positions.get(&edge.blocker_id)?;
+                let (blocked_x, blocked_y) = positions.get(&edge.blocked_id)?;
+                Some(GraphEdge {
+                    x1: blocker_x + NODE_WIDTH,
+                    y1: blocker_y + NODE_HEIGHT / 2,
+                    x2: *blocked_x,
+                    y2: blocked_y + NODE_HEIGHT / 2,
+                })
+            })
+            .collect();
+
+        let row_count = rows.into_iter().max().unwrap_or(1);
+        Self {
+            width: 2 * MARGIN + column_count as i64 * (NODE_WIDTH + COLUMN_GAP) - COLUMN_GAP,
+            height: 2 * MARGIN + row_count * (NODE_HEIGHT + ROW_GAP) - ROW_GAP,
+            node_width: NODE_WIDTH,
+            node_height: NODE_HEIGHT,
+            nodes,
+            edges,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct IssueGraphContext {
+    request: RequestData,
+    issue: DependencyNode,
+    blockers: Vec<DependencyNode>,
+    layout: GraphLayout,
+}
+
+#[get("/issue/<issue_id>/graph")]
+pub async fn issue_graph(
+    issue_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let graph = DependencyGraph::load(issue_id).await.map_to_failure()?;
+    let issue = graph
+        .node(issue_id)
+        .cloned()
+        .ok_or_else(Failure::not_found)?;
+    let blockers = graph.unfinished_blockers().into_iter().cloned().collect();
+    let layout = GraphLayout::new(&graph);
+
+    Ok(Template::render(
+        "issue_graph",
+        IssueGraphContext {
+            request,
+            issue,
+            blockers,
+            layout,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use database::schema::issues::DependencyEdge;
+
+    use super::*;
+
+    /// Only the ids and edges matter to the layout
+    fn graph(ids: &[i64], edges: &[(i64, i64)]) -> DependencyGraph {
+        DependencyGraph {
+            issue_id: ids[0],
+            nodes: ids
+                .iter()
+                .map(|&id| DependencyNode {
+                    id,
+                    project_key: None,
+                    number: None,
+                    summary: String::new(),
+                    started_at: None,
+                    completed_at: None,
+                    blocked: false,
+                })
+                .collect(),
+            edges: edges
+                .iter()
+                .map(|&(blocker_id, blocked_id)| DependencyEdge {
+                    blocker_id,
+                    blocked_id,
+                })
+                .collect(),
+        }
+    }
+
+    fn position(layout: &GraphLayout, id: i64) -> (i64, i64) {
+        let node = layout.nodes.iter().find(|node| node.id == id).unwrap();
+        (node.x, node.y)
+    }
+
+    #[test]
+    fn blockers_are_left_of_what_they_block() {
+        // 3 blocks both 1 and 2, and 2 blocks 1, so 3 is two columns left of 1
+        let graph = graph(&[1, 2, 3, 4], &[(2, 1), (3, 1), (3, 2), (4, 1)]);
+        let layout = GraphLayout::new(&graph);
+
+        let column = NODE_WIDTH + COLUMN_GAP;
+        let row = NODE_HEIGHT + ROW_GAP;
+        assert_eq!(position(&layout, 1), (MARGIN + 2 * column, MARGIN));
+        assert_eq!(position(&layout, 2), (MARGIN + column, MARGIN));
+        assert_eq!(position(&layout, 4), (MARGIN + column, MARGIN + row));
+        assert_eq!(position(&layout, 3), (MARGIN, MARGIN));
+        assert_eq!(layout.width, 2 * MARGIN + 3 * column - COLUMN_GAP);
+        assert_eq!(layout.height, 2 * MARGIN + 2 * row - ROW_GAP);
+        assert_eq!(
+            layout.edges[0],
+            GraphEdge {
+                x1: MARGIN + column + NODE_WIDTH,
+                y1: MARGIN + NODE_HEIGHT / 2,
+                x2: MARGIN + 2 * column,
+                y2: MARGIN + NODE_HEIGHT / 2,
+            }
+        );
+    }
+
+    #[test]
+    fn cycles_do_not_loop_forever() {
+        let graph = graph(&[1, 2], &[(2, 1), (1, 2)]);
+        let layout = GraphLayout::new(&graph);
+        assert_eq!(layout.nodes.len(), 2);
+    }
+
+    #[test]
+    fn unblocked_issue_is_drawn_alone() {
+        let graph = graph(&[1], &[]);
+        let layout = GraphLayout::new(&graph);
+        assert_eq!(position(&layout, 1), (MARGIN, MARGIN));
+        assert_eq!(layout.width, 2 * MARGIN + NODE_WIDTH);
+        assert_eq!(layout.height, 2 * MARGIN + NODE_HEIGHT);
+    }
+}
@@ -5,24 +5,40 @@ use std::{
 };
 
 use rocket::{http::Status, request::Form};
-use rocket_contrib::templates::{tera, Template};
+use rocket_contrib::{
+    json::Json,
+    templates::{tera, Template},
+};
 use serde::{Deserialize, Serialize};
 
 use database::{
-    schema::issues::{
-        ContextualizedRelationship, Issue, IssueQueryBuilder, IssueQueryResults, IssueRelationship,
-        IssueRevision, IssueRevisionChange, IssueRevisionView, IssueView, Project, Relationship,
-        Tag, Taxonomy,
+    schema::{
+        attachments::Attachment,
+        issues::{
+            ContextualizedRelationship, Issue, IssueCommit, IssueOrdering, IssueOrderingField,
+            IssueQueryBuilder, IssueQueryResults, IssueRelationship, IssueRevision,
+            IssueRevisionChange, IssueRevisionView, IssueTree, IssueView, IssueWatcher, Iteration,
+            Milestone, Project, Relationship, RelationshipGroup, Resolution, Schedule, Tag,
+            Taxonomy, Workflow, WorkflowState,
+        },
+        notifications::IssueEventKind,
+    },
+    sqlx::{
+        self,
+        types::chrono::{DateTime, Utc},
+        Postgres, Transaction,
     },
-    sqlx,
-    sqlx::types::chrono::Utc,
     DatabaseError, SqlxResultExt,
 };
 
 use crate::{
+    attachments,
+    notifications::{self, IssueEvent},
     webserver::{
-        auth::SessionId, localization::UserLanguage, Failure, FullPathAndQuery, RequestData,
-        ResultExt,
+        articles::{self, MarkdownLinks},
+        auth::SessionId,
+        localization::UserLanguage,
+        Failure, FullPathAndQuery, RequestData, ResultExt,
     },
     Optionable,
 };
@@ -50,6 +66,10 @@ impl AuthoredBy for Issue {
     }
 }
 
+pub fn can_view_issue<I: AuthoredBy>(_request: &RequestData, _issue: &I) -> bool {
+    true
+}
+
 pub fn can_edit_issue<I: AuthoredBy>(request: &RequestData, _issue: &I) -> bool {
     request.session.is_some()
 }
@@ -81,7 +101,54 @@ pub async fn list_issues(
 
 #[derive(Serialize, Deserialize)]
 struct IssueTimeline {
-    entries: Vec<IssueRevisionView>,
+    entries: Vec<TimelineEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum TimelineEntry {
+    Revision(IssueRevisionView),
+    Commit(IssueCommit),
+}
+
+impl TimelineEntry {
+    fn created_at(&self) -> DateTime<Utc> {
+        match self {
+            TimelineEntry::Revision(revision) => revision.created_at,
+            TimelineEntry::Commit(commit) => commit.created_at,
+        }
+    }
+}
+
+impl IssueTimeline {
+    fn new(revisions: Vec<IssueRevisionView>, commits: Vec<IssueCommit>) -> Self {
+        let mut entries = revisions
+            .into_iter()
+            .map(TimelineEntry::Revision)
+            .chain(commits.into_iter().map(TimelineEntry::Commit))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(TimelineEntry::created_at);
+        Self { entries }
+    }
+
+    /// The markdown shown in the timeline: comments and description changes
+    fn markdown(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                TimelineEntry::Revision(revision) => Some(revision),
+                TimelineEntry::Commit(_) => None,
+            })
+            .flat_map(|revision| {
+                let description = revision.changes.get("description");
+                revision.comment.as_deref().into_iter().chain(
+                    description
+                        .into_iter()
+                        .flat_map(|change| vec![&change.old_value, &change.new_value])
+                        .filter_map(|value| value.as_ref().and_then(|value| value.as_str())),
+                )
+            })
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -89,42 +156,95 @@ struct ViewIssueContext {
     request: RequestData,
     issue: IssueView,
     parents: Vec<Issue>,
-    relationships: Vec<IssueRelationship>,
+    tree: IssueTree,
+    relationships: Vec<RelationshipGroup>,
     timeline: IssueTimeline,
     response: IssueQueryResults,
     editable: bool,
     projects: HashMap<i64, Project>,
     tags: Vec<i32>,
     taxonomy: Taxonomy,
+    attachments: Vec<Attachment>,
+    watching: bool,
+    workflow_states: HashMap<i64, WorkflowState>,
+    milestones: HashMap<i64, Milestone>,
+    iterations: HashMap<i64, Iteration>,
+    links: MarkdownLinks,
 }
 
 async fn render_issue(request: RequestData, issue_id: i64) -> sqlx::Result<Template> {
-    let (issue, parents, relationships, entries, response, projects, tags, taxonomy) = futures::try_join!(
+    let (
+        issue,
+        parents,
+        tree,
+        relationships,
+        revisions,
+        commits,
+        response,
+        projects,
+        tags,
+        taxonomy,
+        attachments,
+        workflow_states,
+        milestones,
+        iterations,
+    ) = futures::try_join!(
         IssueView::load(issue_id),
         Issue::all_parents(issue_id),
+        IssueTree::load(issue_id),
         IssueRelationship::list_for(issue_id, database::pool()),
         IssueRevisionView::list_for(issue_id),
+        IssueCommit::list_for_issue(issue_id, database::pool()),
         IssueQueryBuilder::new()
             .owned_by(Some(issue_id))
+            .order_by(IssueOrdering {
+                field: IssueOrderingField::Rank,
+                ascending: true,
+            })
             .query(database::pool()),
         Project::list_as_map(),
         Tag::list_for_issue(issue_id),
-        Taxonomy::load(database::pool())
+        Taxonomy::load(database::pool()),
+        Attachment::list_for_issue(issue_id, database::pool()),
+        WorkflowState::list_as_map(database::pool()),
+        Milestone::list_as_map(),
+        Iteration::list_as_map()
     )?;
-    let timeline = IssueTimeline { entries };
+    let timeline = IssueTimeline::new(revisions, commits);
+    let links = MarkdownLinks::load(
+        issue
+            .description
+            .as_deref()
+            .into_iter()
+            .chain(timeline.markdown()),
+    )
+    .await?;
     let editable = can_edit_issue(&request, &issue);
+    let watching = match &request.session {
+        Some(session) => {
+            IssueWatcher::is_watching(issue_id, session.account.id, database::pool()).await?
+        }
+        None => false,
+    };
     Ok(Template::render(
         "view_issue",
         ViewIssueContext {
             request,
             issue,
             parents,
+            tree,
             relationships,
             timeline,
             response,
             editable,
             projects,
             taxonomy,
+            attachments,
+            watching,
+            workflow_states,
+            milestones,
+            iterations,
+            links,
             tags: tags.into_iter().map(|t| t.id).collect(),
         },
     ))
@@ -141,6 +261,45 @@ pub async fn view_issue(
     render_issue(request, issue_id).await.map_to_failure()
 }
 
+/// Splits a reference like `GOOEY-42` into its project key and number
+pub fn parse_issue_key(reference: &str) -> Option<(&str, i32)> {
+    let separator = reference.rfind('-')?;
+    let key = &reference[..separator];
+    let number = &reference[separator + 1..];
+    if key.is_empty() || number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some((key, number.parse().ok()?))
+}
+
+/// Issues can also be viewed by their project key and number, e.g.
+/// `/issue/GOOEY-42`. Numeric ids are matched by `view_issue` first.
+#[get("/issue/<reference>", rank = 2)]
+pub async fn view_issue_by_key(
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+    reference: String,
+) -> Result<Template, Failure> {
+    let (key, number) = parse_issue_key(&reference).ok_or_else(Failure::not_found)?;
+    let issue_id = Issue::find_by_key(&key.to_ascii_uppercase(), number, database::pool())
+        .await
+        .map_to_failure()?;
+    let request = RequestData::new(language, path, session).await;
+    render_issue(request, issue_id).await.map_to_failure()
+}
+
+/// The schedule of the open work beneath an issue
+#[get("/issue/<issue_id>/schedule.json")]
+pub async fn issue_schedule(issue_id: i64) -> Result<Json<Schedule>, Failure> {
+    let schedule = Schedule::for_subtree(issue_id, Utc::now())
+        .await
+        .map_to_failure()?;
+
+    Ok(Json(schedule))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct EditIssueContext {
     request: RequestData,
@@ -151,16 +310,80 @@ struct EditIssueContext {
     summary: Option<String>,
     description: Option<String>,
     comment: Option<String>,
-    started: bool,
-    completed: bool,
+    workflow_state_id: Option<i64>,
+    resolution: Option<String>,
+    duplicate_of: Option<i64>,
+    estimate_hours: Option<i32>,
     project_id: Option<i64>,
+    milestone_id: Option<i64>,
+    iteration_id: Option<i64>,
     ungrouped_tags: Vec<String>,
 
     projects: Vec<Project>,
+    milestones: Vec<Milestone>,
+    iterations: Vec<Iteration>,
     taxonomy: Taxonomy,
+    workflow_states: Vec<WorkflowState>,
+    resolutions: Vec<String>,
 }
 
-#[get("/issues/new?<summary>&<description>&<project_id>&<parent_id>")]
+fn resolution_choices() -> Vec<String> {
+    Resolution::ALL
+        .iter()
+        .map(|resolution| resolution.to_string())
+        .collect()
+}
+
+/// The milestones an issue can be planned into: every open milestone, along
+/// with the issue's current milestone even if it has been closed
+async fn milestone_choices(current_milestone_id: Option<i64>) -> sqlx::Result<Vec<Milestone>> {
+    let mut milestones = Milestone::list_open().await?;
+    if let Some(milestone_id) = current_milestone_id {
+        if !milestones
+            .iter()
+            .any(|milestone| milestone.id == milestone_id)
+        {
+            match Milestone::load(milestone_id).await {
+                Ok(milestone) => milestones.push(milestone),
+                Err(sqlx::Error::RowNotFound) => {}
+                Err(other) => return Err(other),
+            }
+        }
+    }
+    Ok(milestones)
+}
+
+/// The iterations an issue can be planned into: every iteration that hasn't
+/// ended yet, along with the issue's current iteration
+async fn iteration_choices(current_iteration_id: Option<i64>) -> sqlx::Result<Vec<Iteration>> {
+    let mut iterations = Iteration::list_unfinished(Utc::now().date().naive_utc()).await?;
+    if let Some(iteration_id) = current_iteration_id {
+        if !iterations
+            .iter()
+            .any(|iteration| iteration.id == iteration_id)
+        {
+            match Iteration::load(iteration_id).await {
+                Ok(iteration) => iterations.insert(0, iteration),
+                Err(sqlx::Error::RowNotFound) => {}
+                Err(other) => return Err(other),
+            }
+        }
+    }
+    Ok(iterations)
+}
+
+/// The states an issue can be saved in. New issues can start in any state of
+/// their project's workflow, while existing issues are limited to the
+/// transitions allowed from their current state.
+async fn workflow_choices(
+    project_id: Option<i64>,
+    current_state_id: Option<i64>,
+) -> sqlx::Result<Vec<WorkflowState>> {
+    let workflow = Workflow::for_project(project_id).await?;
+    Ok(workflow.next_states(current_state_id))
+}
+
+#[get("/issues/new?<summary>&<description>&<project_id>&<parent_id>&<milestone_id>&<iteration_id>")]
 pub async fn new_issue(
     language: UserLanguage,
     path: FullPathAndQuery,
@@ -169,11 +392,14 @@ pub async fn new_issue(
     description: Option<String>,
     project_id: Option<i64>,
     parent_id: Option<i64>,
+    milestone_id: Option<i64>,
+    iteration_id: Option<i64>,
 ) -> Result<Template, Failure> {
     let request = RequestData::new(language, path, session).await;
     let projects = Project::list().await?;
     let taxonomy = Taxonomy::load(database::pool()).await?;
     if request.logged_in() {
+        let workflow = Workflow::for_project(project_id).await?;
         Ok(Template::render(
             "edit_issue",
             EditIssueContext {
@@ -182,6 +408,8 @@ pub async fn new_issue(
                 description,
                 project_id,
                 parent_id,
+                milestone_id,
+                iteration_id,
                 projects,
                 taxonomy,
 
@@ -189,9 +417,15 @@ pub async fn new_issue(
                 current_revision_id: None,
                 error_message: None,
                 comment: None,
-                completed: false,
-                started: false,
+                workflow_state_id: workflow.initial_state().map(|state| state.id),
+                resolution: None,
+                duplicate_of: None,
+                estimate_hours: None,
                 ungrouped_tags: Default::default(),
+                milestones: milestone_choices(milestone_id).await?,
+                iterations: iteration_choices(iteration_id).await?,
+                workflow_states: workflow.states,
+                resolutions: resolution_choices(),
             },
         ))
     } else {
@@ -212,6 +446,8 @@ pub async fn edit_issue(
         if can_edit_issue(&request, &issue) {
             let projects = Project::list().await?;
             let taxonomy = Taxonomy::load(database::pool()).await?;
+            let workflow_states =
+                workflow_choices(issue.project_id, issue.workflow_state_id).await?;
             let mut unassigned_tags = Vec::default();
 
             for tag in Tag::list_for_issue(issue.id).await? {
@@ -230,13 +466,21 @@ pub async fn edit_issue(
                     summary: Some(issue.summary),
                     description: issue.description,
                     comment: None,
-                    started: issue.started_at.is_some(),
-                    completed: issue.completed_at.is_some(),
+                    workflow_state_id: issue.workflow_state_id,
+                    resolution: issue.resolution.map(|resolution| resolution.to_string()),
+                    duplicate_of: None,
+                    estimate_hours: issue.estimate_hours,
                     project_id: issue.project_id,
                     parent_id: issue.parent_id,
+                    milestone_id: issue.milestone_id,
+                    iteration_id: issue.iteration_id,
                     ungrouped_tags: unassigned_tags,
+                    milestones: milestone_choices(issue.milestone_id).await?,
+                    iterations: iteration_choices(issue.iteration_id).await?,
                     projects,
                     taxonomy,
+                    workflow_states,
+                    resolutions: resolution_choices(),
                 },
             ))
         } else {
@@ -257,17 +501,26 @@ pub struct EditIssueForm {
     summary: String,
     description: Option<String>,
     comment: Option<String>,
-    started: bool,
-    completed: bool,
+    workflow_state_id: Option<i64>,
+    resolution: Option<String>,
+    duplicate_of: Option<i64>,
+    estimate_hours: Option<i32>,
     project_id: Option<i64>,
+    milestone_id: Option<i64>,
+    iteration_id: Option<i64>,
     tags: String,
 }
 
-enum IssueUpdateError {
+#[derive(Debug)]
+pub(crate) enum IssueUpdateError {
     IssueAlreadyUpdated { current_revision_id: Option<i64> },
     ParentNotFound,
+    ParentCycle,
     CantCloseBecauseOfChild,
     CantCloseBecauseBlocked,
+    TransitionNotAllowed,
+    DuplicateNotFound,
+    MilestoneNotInProject,
     InternalError,
 }
 
@@ -278,12 +531,91 @@ impl From<sqlx::Error> for IssueUpdateError {
     }
 }
 
+/// Finds the state an issue should be saved in. A state from another
+/// project's workflow, left over from moving the issue, is mapped to the
+/// corresponding state of `workflow`.
+async fn resolve_workflow_state(
+    workflow: &Workflow,
+    state_id: Option<i64>,
+) -> Result<WorkflowState, IssueUpdateError> {
+    let state = match state_id {
+        Some(state_id) => match workflow.state(state_id) {
+            Some(state) => Some(state),
+            None => match WorkflowState::load(state_id, database::pool())
+                .await
+                .map_database_error()
+            {
+                Ok(other_state) => workflow.corresponding_state(&other_state),
+                Err(DatabaseError::RowNotFound) => workflow.initial_state(),
+                Err(_) => return Err(IssueUpdateError::InternalError),
+            },
+        },
+        None => workflow.initial_state(),
+    };
+
+    state.cloned().ok_or(IssueUpdateError::InternalError)
+}
+
+/// Issues in a completing state are fixed unless the form says otherwise, and
+/// open issues have no resolution. Issues becoming duplicates must name the
+/// issue they duplicate, which is returned alongside the resolution.
+async fn resolve_resolution(
+    issue_form: &EditIssueForm,
+    state: &WorkflowState,
+    current: Option<Resolution>,
+) -> Result<(Option<Resolution>, Option<i64>), IssueUpdateError> {
+    if !state.completes {
+        return Ok((None, None));
+    }
+
+    let resolution = match issue_form.resolution.as_deref() {
+        None | Some("") => Resolution::Fixed,
+        Some(value) => Resolution::from_str(value).map_err(|_| IssueUpdateError::InternalError)?,
+    };
+    if resolution != Resolution::Duplicate
+        || (current == Some(resolution) && issue_form.duplicate_of.is_none())
+    {
+        return Ok((Some(resolution), None));
+    }
+
+    match issue_form.duplicate_of {
+        Some(duplicate_of) if Some(duplicate_of) != issue_form.issue_id => {
+            match Issue::load(duplicate_of).await.map_database_error() {
+                Ok(_) => Ok((Some(resolution), Some(duplicate_of))),
+                Err(DatabaseError::RowNotFound) => Err(IssueUpdateError::DuplicateNotFound),
+                Err(_) => Err(IssueUpdateError::InternalError),
+            }
+        }
+        _ => Err(IssueUpdateError::DuplicateNotFound),
+    }
+}
+
+/// Issues can only be planned into milestones of their own project
+async fn ensure_milestone_in_project(issue_form: &EditIssueForm) -> Result<(), IssueUpdateError> {
+    let milestone_id = match issue_form.milestone_id {
+        Some(milestone_id) => milestone_id,
+        None => return Ok(()),
+    };
+
+    match Milestone::load(milestone_id).await.map_database_error() {
+        Ok(milestone) if Some(milestone.project_id) == issue_form.project_id => Ok(()),
+        Ok(_) | Err(DatabaseError::RowNotFound) => Err(IssueUpdateError::MilestoneNotInProject),
+        Err(_) => Err(IssueUpdateError::InternalError),
+    }
+}
+
 async fn update_issue(
     issue_form: &Form<EditIssueForm>,
     author_id: i64,
     taxonomy: &Taxonomy,
-) -> Result<Issue, IssueUpdateError> {
+) -> Result<(Issue, Vec<IssueEvent>), IssueUpdateError> {
+    let workflow = Workflow::for_project(issue_form.project_id).await?;
+    let state = resolve_workflow_state(&workflow, issue_form.workflow_state_id).await?;
+    ensure_milestone_in_project(issue_form).await?;
     let mut tx = database::pool().begin().await?;
+    let mut comment_revision_id = None;
+    let mut previous_description = None;
+    let mut events = Vec::new();
     let issue = if let Some(issue_id) = issue_form.issue_id {
         if let Some(parent_id) = issue_form.parent_id {
             let _ = Issue::load(parent_id)
@@ -298,25 +630,71 @@ async fn update_issue(
                 })?;
         }
 
+        if issue_form.parent_id.is_some() {
+            Issue::lock_hierarchy(&mut tx).await?;
+        }
         let mut issue = Issue::load_for_update(issue_id, &mut tx).await?;
+        if let Some(parent_id) = issue_form.parent_id {
+            if issue.parent_id != Some(parent_id)
+                && Issue::would_create_parent_cycle(issue.id, parent_id, &mut tx).await?
+            {
+                return Err(IssueUpdateError::ParentCycle);
+            }
+        }
         if issue.current_revision_id != issue_form.current_revision_id {
             return Err(IssueUpdateError::IssueAlreadyUpdated {
                 current_revision_id: issue.current_revision_id,
             });
         }
+        previous_description = issue.description.clone();
+
+        if !workflow.can_transition(issue.workflow_state_id, state.id) {
+            return Err(IssueUpdateError::TransitionNotAllowed);
+        }
+        let (resolution, duplicate_of) =
+            resolve_resolution(issue_form, &state, issue.resolution).await?;
 
+        // Completing an issue keeps the time it was started
+        let completed = state.completes;
+        let started = state.started || (completed && issue.started_at.is_some());
+        let changed_state = issue.workflow_state_id != Some(state.id);
         let mut changed_issue_status = false;
 
         if issue_form.comment.is_some()
             || issue.summary != issue_form.summary
             || issue.description != issue_form.description
-            || issue.completed_at.is_some() != issue_form.completed
+            || changed_state
+            || issue.resolution != resolution
+            || issue.completed_at.is_some() != completed
             || issue.project_id != issue_form.project_id
             || issue.parent_id != issue_form.parent_id
+            || issue.estimate_hours != issue_form.estimate_hours
+            || issue.milestone_id != issue_form.milestone_id
+            || issue.iteration_id != issue_form.iteration_id
         {
             let issue_revision =
                 IssueRevision::create(issue.id, author_id, issue_form.comment.clone(), &mut tx)
                     .await?;
+            if issue_form.comment.is_some() {
+                comment_revision_id = Some(issue_revision.id);
+                IssueWatcher::subscribe(issue.id, author_id, &mut tx).await?;
+                events.push(
+                    IssueEvent::new(issue.id, author_id, IssueEventKind::Commented)
+                        .with_comment(issue_form.comment.clone()),
+                );
+            }
+            // Moves between states are reported as updates unless they start,
+            // complete or reopen the issue, which have their own events
+            let mut updated_details = issue.summary != issue_form.summary
+                || issue.description != issue_form.description
+                || issue.project_id != issue_form.project_id
+                || issue.parent_id != issue_form.parent_id
+                || issue.estimate_hours != issue_form.estimate_hours
+                || issue.milestone_id != issue_form.milestone_id
+                || issue.iteration_id != issue_form.iteration_id
+                || (changed_state
+                    && completed == issue.completed_at.is_some()
+                    && (!started || issue.started_at.is_some()));
             if issue.summary != issue_form.summary {
                 IssueRevisionChange::create(
                     issue_revision.id,
@@ -341,14 +719,20 @@ async fn update_issue(
                 issue.description = issue_form.description.clone();
             }
 
-            issue.update_blocked_status(&mut tx).await?;
+            if changed_state {
+                IssueRevisionChange::create(
+                    issue_revision.id,
+                    "workflow_state_id",
+                    issue.workflow_state_id,
+                    Some(state.id),
+                    &mut tx,
+                )
+                .await?;
+                issue.workflow_state_id = Some(state.id);
+            }
 
-            if issue_form.started != issue.started_at.is_some() {
-                let new_value = if issue_form.started {
-                    Some(Utc::now())
-                } else {
-                    None
-                };
+            if started != issue.started_at.is_some() {
+                let new_value = if started { Some(Utc::now()) } else { None };
                 IssueRevisionChange::create(
                     issue_revision.id,
                     "started_at",
@@ -358,24 +742,22 @@ async fn update_issue(
                 )
                 .await?;
                 issue.started_at = new_value;
+                if started {
+                    events.push(IssueEvent::new(
+                        issue.id,
+                        author_id,
+                        IssueEventKind::Started,
+                    ));
+                }
             }
 
-            if issue_form.completed != issue.completed_at.is_some() {
+            if completed != issue.completed_at.is_some() {
                 if issue.blocked {
                     return Err(IssueUpdateError::CantCloseBecauseBlocked);
                 }
 
-                let new_value = if issue_form.completed {
-                    // Make sure no children are open
-                    let children = IssueQueryBuilder::new()
-                        .owned_by(Some(issue.id))
-                        .open()
-                        .query(&mut tx)
-                        .await?;
-                    if !children.issues.is_empty() {
-                        return Err(IssueUpdateError::CantCloseBecauseOfChild);
-                    }
-
+                let new_value = if completed {
+                    ensure_can_complete(&issue, &mut tx).await?;
                     Some(Utc::now())
                 } else {
                     None
@@ -390,6 +772,47 @@ async fn update_issue(
                 .await?;
                 issue.completed_at = new_value;
                 changed_issue_status = true;
+                events.push(IssueEvent::new(
+                    issue.id,
+                    author_id,
+                    if completed {
+                        IssueEventKind::Completed
+                    } else {
+                        IssueEventKind::Reopened
+                    },
+                ));
+            }
+
+            if resolution != issue.resolution {
+                IssueRevisionChange::create(
+                    issue_revision.id,
+                    "resolution",
+                    issue.resolution.map(|resolution| resolution.to_string()),
+                    resolution.map(|resolution| resolution.to_string()),
+                    &mut tx,
+                )
+                .await?;
+                issue.resolution = resolution;
+                // Changing the resolution of an issue that stays complete is an update
+                updated_details |=
+                    completed && issue.completed_at.is_some() && !changed_issue_status;
+
+                if let Some(duplicate_of) = duplicate_of {
+                    IssueRelationship::link(
+                        issue.id,
+                        duplicate_of,
+                        Relationship::Duplicates,
+                        None::<String>,
+                        &mut tx,
+                    )
+                    .await?;
+                    events.extend(link_events(
+                        issue.id,
+                        duplicate_of,
+                        author_id,
+                        IssueEventKind::Linked,
+                    ));
+                }
             }
 
             if issue_form.project_id != issue.project_id {
@@ -416,6 +839,42 @@ async fn update_issue(
                 issue.parent_id = issue_form.parent_id;
             }
 
+            if issue_form.estimate_hours != issue.estimate_hours {
+                IssueRevisionChange::create(
+                    issue_revision.id,
+                    "estimate_hours",
+                    issue.estimate_hours,
+                    issue_form.estimate_hours,
+                    &mut tx,
+                )
+                .await?;
+                issue.estimate_hours = issue_form.estimate_hours;
+            }
+
+            if issue_form.milestone_id != issue.milestone_id {
+                IssueRevisionChange::create(
+                    issue_revision.id,
+                    "milestone_id",
+                    issue.milestone_id,
+                    issue_form.milestone_id,
+                    &mut tx,
+                )
+                .await?;
+                issue.milestone_id = issue_form.milestone_id;
+            }
+
+            if issue_form.iteration_id != issue.iteration_id {
+                IssueRevisionChange::create(
+                    issue_revision.id,
+                    "iteration_id",
+                    issue.iteration_id,
+                    issue_form.iteration_id,
+                    &mut tx,
+                )
+                .await?;
+                issue.iteration_id = issue_form.iteration_id;
+            }
+
             let mut existing_tags = Tag::list_for_issue(issue.id).await?;
             let mut tags_to_remove = existing_tags.iter().map(|t| t.id).collect::<HashSet<_>>();
             let mut tags_to_insert = HashSet::new();
@@ -479,16 +938,22 @@ async fn update_issue(
                 for tag in tags_to_insert {
                     issue.add_tag(tag, &mut tx).await?;
                 }
+
+                updated_details = true;
+            }
+
+            if updated_details {
+                events.push(IssueEvent::new(
+                    issue.id,
+                    author_id,
+                    IssueEventKind::Updated,
+                ));
             }
 
             issue.current_revision_id = Some(issue_revision.id);
         }
         issue.save(&mut tx).await?;
 
-        if changed_issue_status {
-            tx = Issue::update_blocked_relationships(&[issue.id], tx).await?;
-        }
-
         issue
     } else {
         let mut issue = Issue::new(
@@ -499,21 +964,194 @@ async fn update_issue(
             issue_form.project_id,
         );
 
-        if issue_form.started {
+        issue.workflow_state_id = Some(state.id);
+        issue.estimate_hours = issue_form.estimate_hours;
+        issue.milestone_id = issue_form.milestone_id;
+        issue.iteration_id = issue_form.iteration_id;
+        if state.started {
             issue.started_at = Some(Utc::now());
         }
 
-        if issue_form.completed {
+        if state.completes {
             issue.completed_at = Some(Utc::now());
         }
+        let (resolution, duplicate_of) = resolve_resolution(issue_form, &state, None).await?;
+        issue.resolution = resolution;
 
         issue.save(&mut tx).await?;
+        IssueWatcher::subscribe(issue.id, author_id, &mut tx).await?;
+        events.push(IssueEvent::new(
+            issue.id,
+            author_id,
+            IssueEventKind::Created,
+        ));
+
+        if let Some(duplicate_of) = duplicate_of {
+            IssueRelationship::link(
+                issue.id,
+                duplicate_of,
+                Relationship::Duplicates,
+                None::<String>,
+                &mut tx,
+            )
+            .await?;
+            events.extend(link_events(
+                issue.id,
+                duplicate_of,
+                author_id,
+                IssueEventKind::Linked,
+            ));
+        }
         issue
     };
 
+    // People mentioned for the first time are subscribed and notified directly
+    let mut mentioned = mentioned_usernames(issue.description.as_deref());
+    for username in mentioned_usernames(previous_description.as_deref()) {
+        mentioned.remove(&username);
+    }
+    if comment_revision_id.is_some() {
+        mentioned.extend(mentioned_usernames(issue_form.comment.as_deref()));
+    }
+    for account_id in articles::resolve_mentions(mentioned).await?.values() {
+        if *account_id == author_id {
+            continue;
+        }
+
+        IssueWatcher::subscribe(issue.id, *account_id, &mut tx).await?;
+        events.push(
+            IssueEvent::new(issue.id, author_id, IssueEventKind::Mentioned)
+                .for_recipient(*account_id),
+        );
+    }
+
+    // Images uploaded while editing aren't associated with anything until the content referencing them is saved
+    if let Some(description) = &issue.description {
+        Attachment::claim(
+            &attachments::attachment_ids_in(description),
+            author_id,
+            Some(issue.id),
+            None,
+            &mut tx,
+        )
+        .await?;
+    }
+
+    if let (Some(comment), Some(revision_id)) = (&issue_form.comment, comment_revision_id) {
+        Attachment::claim(
+            &attachments::attachment_ids_in(comment),
+            author_id,
+            None,
+            Some(revision_id),
+            &mut tx,
+        )
+        .await?;
+    }
+
     tx.commit().await?;
 
-    Ok(issue)
+    Ok((issue, events))
+}
+
+/// Checks that an issue can be completed: it must not be blocked and must not
+/// have any open children
+async fn ensure_can_complete(
+    issue: &Issue,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), IssueUpdateError> {
+    if issue.blocked {
+        return Err(IssueUpdateError::CantCloseBecauseBlocked);
+    }
+
+    let children = IssueQueryBuilder::new()
+        .owned_by(Some(issue.id))
+        .open()
+        .query(&mut *tx)
+        .await?;
+    if !children.issues.is_empty() {
+        return Err(IssueUpdateError::CantCloseBecauseOfChild);
+    }
+
+    Ok(())
+}
+
+/// Completes an issue outside of the edit form, such as when a commit says it
+/// fixes the issue. The issue moves to the first completing state its
+/// workflow allows, and the same checks apply as when completing an issue by
+/// editing it. Completing an issue that is already complete does nothing.
+/// The changes are made in the caller's transaction, which the caller commits.
+pub(crate) async fn complete_issue(
+    issue_id: i64,
+    author_id: i64,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Vec<IssueEvent>, IssueUpdateError> {
+    let mut issue = Issue::load_for_update(issue_id, &mut *tx).await?;
+    if issue.completed_at.is_some() {
+        return Ok(Vec::new());
+    }
+
+    let workflow = Workflow::for_project(issue.project_id).await?;
+    let state = workflow
+        .states
+        .iter()
+        .find(|state| state.completes && workflow.can_transition(issue.workflow_state_id, state.id))
+        .ok_or(IssueUpdateError::TransitionNotAllowed)?;
+
+    ensure_can_complete(&issue, tx).await?;
+
+    let issue_revision =
+        IssueRevision::create(issue.id, author_id, None::<String>, &mut *tx).await?;
+    if issue.workflow_state_id != Some(state.id) {
+        IssueRevisionChange::create(
+            issue_revision.id,
+            "workflow_state_id",
+            issue.workflow_state_id,
+            Some(state.id),
+            &mut *tx,
+        )
+        .await?;
+        issue.workflow_state_id = Some(state.id);
+    }
+
+    let completed_at = Some(Utc::now());
+    IssueRevisionChange::create(
+        issue_revision.id,
+        "completed_at",
+        issue.completed_at,
+        completed_at,
+        &mut *tx,
+    )
+    .await?;
+    issue.completed_at = completed_at;
+
+    IssueRevisionChange::create(
+        issue_revision.id,
+        "resolution",
+        issue.resolution.map(|resolution| resolution.to_string()),
+        Some(Resolution::Fixed.to_string()),
+        &mut *tx,
+    )
+    .await?;
+    issue.resolution = Some(Resolution::Fixed);
+    issue.current_revision_id = Some(issue_revision.id);
+    issue.save(&mut *tx).await?;
+
+    Ok(vec![IssueEvent::new(
+        issue.id,
+        author_id,
+        IssueEventKind::Completed,
+    )])
+}
+
+fn mentioned_usernames(markdown: Option<&str>) -> HashSet<String> {
+    markdown
+        .map(|markdown| {
+            articles::find_mentions(markdown)
+                .into_iter()
+                .map(|(_, username)| username)
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 fn parse_tags(source: &str) -> Vec<Cow<'_, str>> {
@@ -548,7 +1186,10 @@ pub async fn save_issue(
             let result = update_issue(&issue_form, session.account.id, &taxonomy).await;
 
             match result {
-                Ok(issue) => Err(Failure::redirect(format!("/issue/{}", issue.id))),
+                Ok((issue, events)) => {
+                    notifications::publish(events).await;
+                    Err(Failure::redirect(format!("/issue/{}", issue.id)))
+                }
                 Err(error) => {
                     let mut current_revision_id = issue_form.current_revision_id;
                     let error_messsage = match error {
@@ -559,16 +1200,30 @@ pub async fn save_issue(
                             "issues-error-already-updated"
                         }
                         IssueUpdateError::ParentNotFound => "issues-error-parent-not-found",
+                        IssueUpdateError::ParentCycle => "issues-error-parent-cycle",
                         IssueUpdateError::CantCloseBecauseOfChild => {
                             "issues-error-cant-close-child"
                         }
                         IssueUpdateError::CantCloseBecauseBlocked => {
                             "issues-error-cant-close-blocked"
                         }
+                        IssueUpdateError::TransitionNotAllowed => {
+                            "issues-error-transition-not-allowed"
+                        }
+                        IssueUpdateError::DuplicateNotFound => "issues-error-duplicate-not-found",
+                        IssueUpdateError::MilestoneNotInProject => {
+                            "issues-error-milestone-not-in-project"
+                        }
                         IssueUpdateError::InternalError => "internal-error-saving",
                     }
                     .to_string();
                     let projects = Project::list().await?;
+                    let current_state_id = match issue_form.issue_id {
+                        Some(issue_id) => Issue::load(issue_id).await?.workflow_state_id,
+                        None => None,
+                    };
+                    let workflow_states =
+                        workflow_choices(issue_form.project_id, current_state_id).await?;
 
                     Ok(Template::render(
                         "edit_issue",
@@ -580,10 +1235,14 @@ pub async fn save_issue(
                             summary: Some(issue_form.summary.clone()),
                             description: issue_form.description.clone(),
                             comment: issue_form.comment.clone(),
-                            started: issue_form.started,
-                            completed: issue_form.completed,
+                            workflow_state_id: issue_form.workflow_state_id,
+                            resolution: issue_form.resolution.clone(),
+                            duplicate_of: issue_form.duplicate_of,
+                            estimate_hours: issue_form.estimate_hours,
                             project_id: issue_form.project_id,
                             parent_id: issue_form.parent_id,
+                            milestone_id: issue_form.milestone_id,
+                            iteration_id: issue_form.iteration_id,
                             ungrouped_tags: issue_form
                                 .tags
                                 .split(',')
@@ -591,7 +1250,11 @@ pub async fn save_issue(
                                 .collect(),
 
                             projects,
+                            milestones: milestone_choices(issue_form.milestone_id).await?,
+                            iterations: iteration_choices(issue_form.iteration_id).await?,
                             taxonomy,
+                            workflow_states,
+                            resolutions: resolution_choices(),
                         },
                     ))
                 }
@@ -617,6 +1280,9 @@ struct LinkIssueContext {
     target: Option<i64>,
     relationship: Option<String>,
     comment: Option<String>,
+    error_message: Option<String>,
+    /// The issues the requested link would have made into a cycle, in order
+    cycle: Vec<Issue>,
 }
 
 #[get("/issue/<issue_id>/link?<to>")]
@@ -636,10 +1302,7 @@ pub async fn link_issue(
 
     if let Some(target_id) = target {
         if let Ok(link) = IssueRelationship::find(issue_id, target_id, database::pool()).await {
-            relationship = link
-                .relationship
-                .relationship
-                .map(|_| link.relationship.to_string());
+            relationship = Some(link.relationship.to_string());
             comment = link.comment;
             existing = true;
         }
@@ -655,6 +1318,8 @@ pub async fn link_issue(
                 relationship,
                 comment,
                 existing,
+                error_message: None,
+                cycle: Vec::new(),
             },
         ))
     } else {
@@ -673,15 +1338,73 @@ pub async fn unlink_issue(
     let request = RequestData::new(language, path, session).await;
     let issue = IssueView::load(issue_id).await?;
 
-    if can_edit_issue(&request, &issue) {
-        let mut tx = database::pool().begin().await?;
-        IssueRelationship::unlink(issue_id, other_issue_id, &mut tx).await?;
-        tx = Issue::update_blocked_relationships(&[issue_id, other_issue_id], tx).await?;
-        tx.commit().await?;
+    match &request.session {
+        Some(session) if can_edit_issue(&request, &issue) => {
+            IssueRelationship::unlink(issue_id, other_issue_id, database::pool()).await?;
+
+            notifications::publish(link_events(
+                issue_id,
+                other_issue_id,
+                session.account.id,
+                IssueEventKind::Unlinked,
+            ))
+            .await;
+
+            Err(Failure::redirect(format!("/issue/{}", issue_id)))
+        }
+        _ => Err(Failure::forbidden()),
+    }
+}
+
+#[get("/issue/<issue_id>/watch")]
+pub async fn watch_issue(
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+    issue_id: i64,
+) -> Result<(), Failure> {
+    set_watching_issue(issue_id, true, language, path, session).await
+}
+
+#[get("/issue/<issue_id>/unwatch")]
+pub async fn unwatch_issue(
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+    issue_id: i64,
+) -> Result<(), Failure> {
+    set_watching_issue(issue_id, false, language, path, session).await
+}
+
+async fn set_watching_issue(
+    issue_id: i64,
+    watching: bool,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let session = match &request.session {
+        Some(session) => session,
+        None => {
+            return Err(Failure::redirect_to_signin(Some(&format!(
+                "/issue/{}",
+                issue_id
+            ))))
+        }
+    };
+    let issue = IssueView::load(issue_id).await.map_to_failure()?;
+
+    if can_view_issue(&request, &issue) {
+        if watching {
+            IssueWatcher::watch(issue_id, session.account.id, database::pool()).await?;
+        } else {
+            IssueWatcher::unwatch(issue_id, session.account.id, database::pool()).await?;
+        }
 
         Err(Failure::redirect(format!("/issue/{}", issue_id)))
     } else {
-        Err(Failure::forbidden())
+        Err(Failure::not_found())
     }
 }
 
@@ -692,19 +1415,33 @@ pub struct LinkIssueForm {
     comment: Option<String>,
 }
 
+enum LinkIssueError {
+    /// Contains the issues of the cycle the link would have created
+    Cycle(Vec<i64>),
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for LinkIssueError {
+    fn from(error: sqlx::Error) -> Self {
+        Self::Database(error)
+    }
+}
+
 async fn link_issues(
     issue_a: i64,
     issue_b: i64,
-    relationship: Option<Relationship>,
+    relationship: Relationship,
     comment: Option<&str>,
-) -> Result<(), sqlx::Error> {
+) -> Result<(), LinkIssueError> {
     let mut tx = database::pool().begin().await?;
-    IssueRelationship::link(issue_a, issue_b, relationship, comment, database::pool()).await?;
-
-    if matches!(relationship, Some(Relationship::Blocks)) {
-        tx = Issue::update_blocked_relationships(&[issue_a], tx).await?;
+    if relationship.is_acyclic() {
+        if let Some(cycle) =
+            IssueRelationship::find_cycle(issue_a, issue_b, relationship, &mut tx).await?
+        {
+            return Err(LinkIssueError::Cycle(cycle));
+        }
     }
-
+    IssueRelationship::link(issue_a, issue_b, relationship, comment, &mut tx).await?;
     tx.commit().await?;
 
     Ok(())
@@ -717,33 +1454,447 @@ pub async fn link_issue_post(
     path: FullPathAndQuery,
     session: Option<SessionId>,
     issue_id: i64,
-) -> Result<(), Failure> {
+) -> Result<Template, Failure> {
     let request = RequestData::new(language, path, session).await;
     let issue = IssueView::load(issue_id).await?;
 
-    if can_edit_issue(&request, &issue) {
-        let link = ContextualizedRelationship::from_str(&form.relationship)?;
-        let (issue_a, issue_b) = if link.is_inverse {
-            (form.target, issue_id)
+    match request.session.clone() {
+        Some(session) if can_edit_issue(&request, &issue) => {
+            let link = ContextualizedRelationship::from_str(&form.relationship)?;
+            let (issue_a, issue_b) = if link.is_inverse {
+                (form.target, issue_id)
+            } else {
+                (issue_id, form.target)
+            };
+
+            let result = link_issues(
+                issue_a,
+                issue_b,
+                link.relationship,
+                form.comment
+                    .as_ref()
+                    .map(|comment| comment.trim().into_option())
+                    .flatten(),
+            )
+            .await;
+            match result {
+                Ok(()) => {}
+                Err(LinkIssueError::Cycle(issue_ids)) => {
+                    let mut cycle = Vec::with_capacity(issue_ids.len());
+                    for cycle_issue_id in issue_ids {
+                        cycle.push(Issue::load(cycle_issue_id).await?);
+                    }
+                    let existing = IssueRelationship::find(issue_id, form.target, database::pool())
+                        .await
+                        .is_ok();
+
+                    return Ok(Template::render(
+                        "link_issue",
+                        LinkIssueContext {
+                            request,
+                            issue,
+                            target: Some(form.target),
+                            relationship: Some(form.relationship.clone()),
+                            comment: form.comment.clone(),
+                            existing,
+                            error_message: Some(String::from("link-error-cycle")),
+                            cycle,
+                        },
+                    ));
+                }
+                Err(LinkIssueError::Database(error)) => return Err(error.into()),
+            }
+
+            notifications::publish(link_events(
+                issue_id,
+                form.target,
+                session.account.id,
+                IssueEventKind::Linked,
+            ))
+            .await;
+
+            Err(Failure::redirect(format!("/issue/{}", issue_id)))
+        }
+        _ => Err(Failure::forbidden()),
+    }
+}
+
+/// Relationship changes are visible from both issues, so watchers of either are notified
+fn link_events(
+    issue_id: i64,
+    other_issue_id: i64,
+    actor_id: i64,
+    kind: IssueEventKind,
+) -> Vec<IssueEvent> {
+    vec![
+        IssueEvent::new(issue_id, actor_id, kind).related_to(other_issue_id),
+        IssueEvent::new(other_issue_id, actor_id, kind).related_to(issue_id),
+    ]
+}
+
+#[derive(Serialize, Deserialize)]
+struct IssueTreeEditorContext {
+    request: RequestData,
+    issue: IssueView,
+    tree: IssueTree,
+    taxonomy: Taxonomy,
+}
+
+#[get("/issue/<issue_id>/tree")]
+pub async fn edit_issue_tree(
+    issue_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    if !request.logged_in() {
+        return Err(Failure::redirect_to_signin(Some(&request.current_path)));
+    }
+
+    let issue = IssueView::load(issue_id).await.map_to_failure()?;
+    if !can_edit_issue(&request, &issue) {
+        return Err(Failure::forbidden());
+    }
+    let tree = IssueTree::load(issue_id).await?;
+    let taxonomy = Taxonomy::load(database::pool()).await?;
+
+    Ok(Template::render(
+        "issue_tree_editor",
+        IssueTreeEditorContext {
+            request,
+            issue,
+            tree,
+            taxonomy,
+        },
+    ))
+}
+
+#[derive(Debug)]
+enum MoveIssueError {
+    ParentNotFound,
+    ParentCycle,
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for MoveIssueError {
+    fn from(error: sqlx::Error) -> Self {
+        Self::Database(error)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MovedIssue {
+    id: i64,
+    parent_id: Option<i64>,
+    rank: i32,
+}
+
+/// Places `issue_id` among `siblings` so it ends up at `position`, or last if
+/// `position` is past the end
+fn place_among_siblings(siblings: &[i64], issue_id: i64, position: usize) -> Vec<i64> {
+    let mut ordered = siblings
+        .iter()
+        .copied()
+        .filter(|&id| id != issue_id)
+        .collect::<Vec<_>>();
+    ordered.insert(position.min(ordered.len()), issue_id);
+    ordered
+}
+
+/// Moves an issue beneath `parent_id` at `position` among its new siblings.
+/// Issues moved to the top level have no siblings to be ordered among, so
+/// they keep their rank.
+async fn move_issue_to(
+    issue_id: i64,
+    parent_id: Option<i64>,
+    position: usize,
+    author_id: i64,
+) -> Result<(Issue, Vec<IssueEvent>), MoveIssueError> {
+    let mut tx = database::pool().begin().await?;
+    if parent_id.is_some() {
+        Issue::lock_hierarchy(&mut tx).await?;
+    }
+    let mut issue = Issue::load_for_update(issue_id, &mut tx).await?;
+
+    let siblings = match parent_id {
+        Some(parent_id) => {
+            match Issue::load(parent_id).await {
+                Ok(_) => {}
+                Err(sqlx::Error::RowNotFound) => return Err(MoveIssueError::ParentNotFound),
+                Err(err) => return Err(err.into()),
+            }
+            if Issue::would_create_parent_cycle(issue.id, parent_id, &mut tx).await? {
+                return Err(MoveIssueError::ParentCycle);
+            }
+
+            let siblings = Issue::child_ids(parent_id, &mut tx).await?;
+            place_among_siblings(&siblings, issue.id, position)
+        }
+        None => Vec::new(),
+    };
+    let rank = siblings
+        .iter()
+        .position(|&id| id == issue.id)
+        .map(|rank| rank as i32)
+        .unwrap_or(issue.rank);
+
+    let mut events = Vec::new();
+    if issue.parent_id != parent_id || issue.rank != rank {
+        let revision = IssueRevision::create(issue.id, author_id, None::<String>, &mut tx).await?;
+        if issue.parent_id != parent_id {
+            IssueRevisionChange::create(
+                revision.id,
+                "parent_id",
+                issue.parent_id,
+                parent_id,
+                &mut tx,
+            )
+            .await?;
+            events.push(IssueEvent::new(
+                issue.id,
+                author_id,
+                IssueEventKind::Updated,
+            ));
+        }
+        if issue.rank != rank {
+            IssueRevisionChange::create(revision.id, "rank", Some(issue.rank), Some(rank), &mut tx)
+                .await?;
+        }
+
+        issue.parent_id = parent_id;
+        issue.rank = rank;
+        issue.current_revision_id = Some(revision.id);
+        issue.save(&mut tx).await?;
+    }
+    Issue::set_ranks(&siblings, &mut tx).await?;
+    tx.commit().await?;
+
+    Ok((issue, events))
+}
+
+/// Used by the tree editor to reparent and reorder issues. `parent_id` is
+/// left out to move an issue to the top level.
+#[post("/issue/<issue_id>/move?<parent_id>&<position>")]
+pub async fn move_issue(
+    issue_id: i64,
+    parent_id: Option<i64>,
+    position: usize,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Json<MovedIssue>, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let session = match &request.session {
+        Some(session) => session,
+        None => return Err(Failure::forbidden()),
+    };
+    let issue = Issue::load(issue_id).await.map_to_failure()?;
+    if !can_edit_issue(&request, &issue) {
+        return Err(Failure::forbidden());
+    }
+
+    match move_issue_to(issue_id, parent_id, position, session.account.id).await {
+        Ok((issue, events)) => {
+            notifications::publish(events).await;
+            Ok(Json(MovedIssue {
+                id: issue.id,
+                parent_id: issue.parent_id,
+                rank: issue.rank,
+            }))
+        }
+        Err(MoveIssueError::ParentNotFound) => Err(Failure::not_found()),
+        Err(MoveIssueError::ParentCycle) => Err(Failure::Status(Status::Conflict)),
+        Err(MoveIssueError::Database(err)) => Err(err.into()),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MoveIssueProjectContext {
+    request: RequestData,
+    issue: IssueView,
+    issue_project_id: Option<i64>,
+    projects: Vec<Project>,
+    subtask_count: usize,
+}
+
+#[get("/issue/<issue_id>/move-project")]
+pub async fn move_issue_project(
+    issue_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    if !request.logged_in() {
+        return Err(Failure::redirect_to_signin(Some(&request.current_path)));
+    }
+
+    let issue = Issue::load(issue_id).await.map_to_failure()?;
+    if !can_edit_issue(&request, &issue) {
+        return Err(Failure::forbidden());
+    }
+    let view = IssueView::load(issue_id).await?;
+    let projects = Project::list().await?;
+    let subtask_count = Issue::descendant_ids(issue_id, database::pool())
+        .await?
+        .len();
+
+    Ok(Template::render(
+        "move_issue_project",
+        MoveIssueProjectContext {
+            request,
+            issue: view,
+            issue_project_id: issue.project_id,
+            projects,
+            subtask_count,
+        },
+    ))
+}
+
+#[derive(FromForm, Clone, Debug)]
+pub struct MoveIssueProjectForm {
+    project_id: Option<i64>,
+    include_subtasks: bool,
+    keep_tags: bool,
+}
+
+/// Moves an issue, and optionally everything beneath it, to another project.
+/// Each issue's workflow state is mapped to the corresponding state of the
+/// new project's workflow. Relationships are left alone, as they can link
+/// issues across projects.
+async fn move_to_project(
+    issue_id: i64,
+    form: &MoveIssueProjectForm,
+    author_id: i64,
+) -> sqlx::Result<Vec<IssueEvent>> {
+    let workflow = Workflow::for_project(form.project_id).await?;
+    let states = WorkflowState::list_as_map(database::pool()).await?;
+    let mut tx = database::pool().begin().await?;
+    let mut issue_ids = vec![issue_id];
+    if form.include_subtasks {
+        issue_ids.extend(Issue::descendant_ids(issue_id, &mut tx).await?);
+    }
+
+    let mut events = Vec::new();
+    for issue_id in issue_ids {
+        let mut issue = Issue::load_for_update(issue_id, &mut tx).await?;
+        let mut removed_tags = if form.keep_tags {
+            Vec::new()
         } else {
-            (issue_id, form.target)
+            Tag::list_for_issue(issue.id).await?
         };
+        if issue.project_id == form.project_id && removed_tags.is_empty() {
+            continue;
+        }
 
-        link_issues(
-            issue_a,
-            issue_b,
-            link.relationship,
-            form.comment
-                .as_ref()
-                .map(|comment| comment.trim().into_option())
-                .flatten(),
-        )
-        .await?;
+        let revision = IssueRevision::create(issue.id, author_id, None::<String>, &mut tx).await?;
+        if issue.project_id != form.project_id {
+            IssueRevisionChange::create(
+                revision.id,
+                "project_id",
+                issue.project_id,
+                form.project_id,
+                &mut tx,
+            )
+            .await?;
+            issue.project_id = form.project_id;
+
+            // Milestones belong to a single project
+            if issue.milestone_id.is_some() {
+                IssueRevisionChange::create(
+                    revision.id,
+                    "milestone_id",
+                    issue.milestone_id,
+                    None,
+                    &mut tx,
+                )
+                .await?;
+                issue.milestone_id = None;
+            }
 
-        Err(Failure::redirect(format!("/issue/{}", issue_id)))
-    } else {
-        Err(Failure::forbidden())
+            let state_id = issue
+                .workflow_state_id
+                .and_then(|state_id| states.get(&state_id))
+                .and_then(|state| workflow.corresponding_state(state))
+                .map(|state| state.id);
+            if state_id.is_some() && state_id != issue.workflow_state_id {
+                IssueRevisionChange::create(
+                    revision.id,
+                    "workflow_state_id",
+                    issue.workflow_state_id,
+                    state_id,
+                    &mut tx,
+                )
+                .await?;
+                issue.workflow_state_id = state_id;
+            }
+        }
+
+        if !removed_tags.is_empty() {
+            removed_tags.sort_by_key(|tag| tag.name.to_lowercase());
+            IssueRevisionChange::create(
+                revision.id,
+                "tags",
+                Some(
+                    removed_tags
+                        .iter()
+                        .map(|tag| tag.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+                None,
+                &mut tx,
+            )
+            .await?;
+            for tag in removed_tags {
+                issue.remove_tag(tag.id, &mut tx).await?;
+            }
+        }
+
+        issue.current_revision_id = Some(revision.id);
+        issue.save(&mut tx).await?;
+        events.push(IssueEvent::new(
+            issue.id,
+            author_id,
+            IssueEventKind::Updated,
+        ));
+    }
+    tx.commit().await?;
+
+    Ok(events)
+}
+
+#[post("/issue/<issue_id>/move-project", data = "<form>")]
+pub async fn move_issue_project_post(
+    issue_id: i64,
+    form: Form<MoveIssueProjectForm>,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let session = match &request.session {
+        Some(session) => session,
+        None => {
+            return Err(Failure::redirect_to_signin(Some(&format!(
+                "/issue/{}/move-project",
+                issue_id
+            ))))
+        }
+    };
+    let issue = Issue::load(issue_id).await.map_to_failure()?;
+    if !can_edit_issue(&request, &issue) {
+        return Err(Failure::forbidden());
+    }
+    if let Some(project_id) = form.project_id {
+        Project::load(project_id).await.map_to_failure()?;
     }
+
+    let events = move_to_project(issue_id, &form, session.account.id).await?;
+    notifications::publish(events).await;
+
+    Err(Failure::redirect(format!("/issue/{}", issue_id)))
 }
 
 pub struct RelationshipSummaryKeyFilter;
@@ -754,12 +1905,32 @@ impl tera::Filter for RelationshipSummaryKeyFilter {
         relationship: &tera::Value,
         _: &HashMap<String, tera::Value>,
     ) -> tera::Result<tera::Value> {
-        let link = if relationship.is_null() {
-            ContextualizedRelationship::plain()
-        } else {
-            serde_json::from_value::<ContextualizedRelationship>(relationship.clone())?
-        };
+        let link = serde_json::from_value::<ContextualizedRelationship>(relationship.clone())?;
         let key = format!("issue-relationship-{}-summary", link);
         Ok(tera::Value::String(key))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_issue_key, place_among_siblings};
+
+    #[test]
+    fn issues_are_placed_among_siblings() {
+        assert_eq!(place_among_siblings(&[1, 2, 3], 4, 1), vec![1, 4, 2, 3]);
+        assert_eq!(place_among_siblings(&[1, 2, 3], 4, 10), vec![1, 2, 3, 4]);
+        // Moving within the same parent
+        assert_eq!(place_among_siblings(&[1, 2, 3], 1, 2), vec![2, 3, 1]);
+        assert_eq!(place_among_siblings(&[], 4, 0), vec![4]);
+    }
+
+    #[test]
+    fn issue_keys_are_parsed() {
+        assert_eq!(parse_issue_key("GOOEY-42"), Some(("GOOEY", 42)));
+        assert_eq!(parse_issue_key("gooey-7"), Some(("gooey", 7)));
+        assert_eq!(parse_issue_key("GOOEY-"), None);
+        assert_eq!(parse_issue_key("-42"), None);
+        assert_eq!(parse_issue_key("GOOEY-4x"), None);
+        assert_eq!(parse_issue_key("42"), None);
+    }
+}
@@ -0,0 +1,264 @@
+use rocket::request::Form;
+use rocket_contrib::templates::Template;
+use serde::{Deserialize, Serialize};
+
+use database::{
+    schema::issues::{Iteration, IterationReport},
+    sqlx::types::chrono::{NaiveDate, Utc},
+    DatabaseError,
+};
+
+use crate::webserver::{
+    auth::SessionId, localization::UserLanguage, Failure, FullPathAndQuery, RequestData, ResultExt,
+};
+
+#[derive(Serialize, Deserialize)]
+struct ListIterationsContext {
+    request: RequestData,
+    iterations: Vec<Iteration>,
+    /// The iterations that are running today
+    current: Vec<i64>,
+    editable: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ViewIterationContext {
+    request: RequestData,
+    iteration: Iteration,
+    report: IterationReport,
+    editable: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EditIterationContext {
+    request: RequestData,
+    iteration_id: i64,
+    name: String,
+    starts_on: Option<String>,
+    ends_on: Option<String>,
+    capacity_hours: Option<i32>,
+    error_message: Option<String>,
+}
+
+/// Iterations span every project, so only administrators manage them
+fn can_manage_iterations(request: &RequestData) -> bool {
+    request
+        .session
+        .as_ref()
+        .map(|session| session.account.administrator)
+        .unwrap_or_default()
+}
+
+/// Checks that the current account may manage iterations. Visitors who
+/// aren't signed in are sent back to `origin`.
+fn authorize(request: &RequestData, origin: &str) -> Result<(), Failure> {
+    if can_manage_iterations(request) {
+        Ok(())
+    } else if request.logged_in() {
+        Err(Failure::forbidden())
+    } else {
+        Err(Failure::redirect_to_signin(Some(origin)))
+    }
+}
+
+#[get("/iterations")]
+pub async fn list_iterations(
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let iterations = Iteration::list().await?;
+    let today = Utc::now().date().naive_utc();
+    let current = iterations
+        .iter()
+        .filter(|iteration| iteration.starts_on <= today && today <= iteration.ends_on)
+        .map(|iteration| iteration.id)
+        .collect();
+    let editable = can_manage_iterations(&request);
+
+    Ok(Template::render(
+        "list_iterations",
+        ListIterationsContext {
+            request,
+            iterations,
+            current,
+            editable,
+        },
+    ))
+}
+
+#[get("/iteration/<iteration_id>")]
+pub async fn view_iteration(
+    iteration_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let iteration = Iteration::load(iteration_id).await.map_to_failure()?;
+    let report = IterationReport::new(&iteration, iteration.planned_issues().await?);
+    let editable = can_manage_iterations(&request);
+
+    Ok(Template::render(
+        "view_iteration",
+        ViewIterationContext {
+            request,
+            iteration,
+            report,
+            editable,
+        },
+    ))
+}
+
+#[get("/iterations/new")]
+pub async fn new_iteration(
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    authorize(&request, &request.current_path)?;
+
+    Ok(Template::render(
+        "edit_iteration",
+        EditIterationContext {
+            request,
+            iteration_id: 0,
+            name: String::default(),
+            starts_on: None,
+            ends_on: None,
+            capacity_hours: None,
+            error_message: None,
+        },
+    ))
+}
+
+#[get("/iteration/<iteration_id>/edit")]
+pub async fn edit_iteration(
+    iteration_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    authorize(&request, &request.current_path)?;
+    let iteration = Iteration::load(iteration_id).await.map_to_failure()?;
+
+    Ok(Template::render(
+        "edit_iteration",
+        EditIterationContext {
+            request,
+            iteration_id,
+            name: iteration.name,
+            starts_on: Some(iteration.starts_on.format(DATE_FORMAT).to_string()),
+            ends_on: Some(iteration.ends_on.format(DATE_FORMAT).to_string()),
+            capacity_hours: iteration.capacity_hours,
+            error_message: None,
+        },
+    ))
+}
+
+/// The format used by date inputs
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(FromForm, Clone, Debug)]
+pub struct EditIterationForm {
+    iteration_id: i64,
+    name: String,
+    starts_on: String,
+    ends_on: String,
+    capacity_hours: Option<i32>,
+}
+
+enum IterationUpdateError {
+    NameRequired,
+    InvalidDate,
+    EndsBeforeStart,
+    InvalidCapacity,
+    Database(DatabaseError),
+}
+
+impl From<DatabaseError> for IterationUpdateError {
+    fn from(error: DatabaseError) -> Self {
+        Self::Database(error)
+    }
+}
+
+async fn update_iteration(form: &EditIterationForm) -> Result<Iteration, IterationUpdateError> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(IterationUpdateError::NameRequired);
+    }
+    let parse_date = |date: &str| {
+        NaiveDate::parse_from_str(date.trim(), DATE_FORMAT)
+            .map_err(|_| IterationUpdateError::InvalidDate)
+    };
+    let starts_on = parse_date(&form.starts_on)?;
+    let ends_on = parse_date(&form.ends_on)?;
+    if ends_on < starts_on {
+        return Err(IterationUpdateError::EndsBeforeStart);
+    }
+    if form.capacity_hours.map_or(false, |hours| hours < 0) {
+        return Err(IterationUpdateError::InvalidCapacity);
+    }
+
+    let mut iteration = if form.iteration_id == 0 {
+        Iteration::new(name.to_string(), starts_on, ends_on)
+    } else {
+        Iteration::load(form.iteration_id)
+            .await
+            .map_err(DatabaseError::from)?
+    };
+    iteration.name = name.to_string();
+    iteration.starts_on = starts_on;
+    iteration.ends_on = ends_on;
+    iteration.capacity_hours = form.capacity_hours;
+    iteration.save(database::pool()).await?;
+
+    Ok(iteration)
+}
+
+#[post("/iterations/save", data = "<form>")]
+pub async fn save_iteration(
+    form: Form<EditIterationForm>,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let origin = if form.iteration_id == 0 {
+        String::from("/iterations/new")
+    } else {
+        format!("/iteration/{}/edit", form.iteration_id)
+    };
+    authorize(&request, &origin)?;
+
+    let error_message = match update_iteration(&form).await {
+        Ok(iteration) => return Err(Failure::redirect(format!("/iteration/{}", iteration.id))),
+        Err(IterationUpdateError::NameRequired) => "iteration-error-name-required",
+        Err(IterationUpdateError::InvalidDate) => "iteration-error-invalid-date",
+        Err(IterationUpdateError::EndsBeforeStart) => "iteration-error-ends-before-start",
+        Err(IterationUpdateError::InvalidCapacity) => "iteration-error-invalid-capacity",
+        Err(IterationUpdateError::Database(DatabaseError::RowNotFound)) => {
+            return Err(Failure::not_found())
+        }
+        Err(IterationUpdateError::Database(error)) => {
+            error!("error while saving iteration: {:?}", error);
+            "internal-error-saving"
+        }
+    };
+
+    Ok(Template::render(
+        "edit_iteration",
+        EditIterationContext {
+            request,
+            iteration_id: form.iteration_id,
+            name: form.name.clone(),
+            starts_on: Some(form.starts_on.clone()),
+            ends_on: Some(form.ends_on.clone()),
+            capacity_hours: form.capacity_hours,
+            error_message: Some(String::from(error_message)),
+        },
+    ))
+}
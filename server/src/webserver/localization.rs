@@ -72,6 +72,12 @@ impl tera::Function for Localize {
     }
 }
 
+/// Looks up a localized string outside of a template, such as when composing an email
+pub fn localize(language: &str, key: &str, args: &HashMap<String, FluentValue<'_>>) -> String {
+    let lang = LanguageIdentifier::from_str(language).expect("language code not found");
+    LOCALES.lookup_with_args(&lang, key, args)
+}
+
 pub struct LanguageCode;
 
 impl tera::Filter for LanguageCode {
@@ -0,0 +1,257 @@
+use rocket::request::Form;
+use rocket_contrib::templates::Template;
+use serde::{Deserialize, Serialize};
+
+use database::{
+    schema::issues::{Milestone, MilestoneReport, Project, Taxonomy},
+    sqlx::types::chrono::{NaiveDate, Utc},
+    DatabaseError,
+};
+
+use crate::webserver::{
+    articles::MarkdownLinks,
+    auth::{SessionData, SessionId},
+    localization::UserLanguage,
+    Failure, FullPathAndQuery, RequestData, ResultExt,
+};
+
+#[derive(Serialize, Deserialize)]
+struct ViewMilestoneContext {
+    request: RequestData,
+    project: Project,
+    milestone: Milestone,
+    report: MilestoneReport,
+    editable: bool,
+    links: MarkdownLinks,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EditMilestoneContext {
+    request: RequestData,
+    project: Project,
+    milestone_id: i64,
+    name: String,
+    description: Option<String>,
+    target_date: Option<String>,
+    closed: bool,
+    error_message: Option<String>,
+}
+
+/// Milestones are managed by administrators and the project's owner
+fn can_manage_milestones(session: &SessionData, project: &Project) -> bool {
+    session.account.administrator || session.account.id == project.owner_id
+}
+
+/// Loads the project and checks that the current account may manage its
+/// milestones. Visitors who aren't signed in are sent back to `origin`.
+async fn authorize(
+    request: &RequestData,
+    project_id: i64,
+    origin: &str,
+) -> Result<Project, Failure> {
+    let project = Project::load(project_id).await.map_to_failure()?;
+    match &request.session {
+        Some(session) if can_manage_milestones(session, &project) => Ok(project),
+        Some(_) => Err(Failure::forbidden()),
+        None => Err(Failure::redirect_to_signin(Some(origin))),
+    }
+}
+
+#[get("/milestone/<milestone_id>")]
+pub async fn view_milestone(
+    milestone_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let milestone = Milestone::load(milestone_id).await.map_to_failure()?;
+    let project = Project::load(milestone.project_id).await?;
+    let taxonomy = Taxonomy::load(database::pool()).await?;
+    let report = MilestoneReport::new(milestone.issues().await?, &taxonomy);
+    let links = MarkdownLinks::load(milestone.description.as_deref()).await?;
+    let editable = request
+        .session
+        .as_ref()
+        .map(|session| can_manage_milestones(session, &project))
+        .unwrap_or_default();
+
+    Ok(Template::render(
+        "view_milestone",
+        ViewMilestoneContext {
+            request,
+            project,
+            milestone,
+            report,
+            editable,
+            links,
+        },
+    ))
+}
+
+#[get("/project/<project_id>/milestones/new")]
+pub async fn new_milestone(
+    project_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let project = authorize(&request, project_id, &request.current_path).await?;
+
+    Ok(Template::render(
+        "edit_milestone",
+        EditMilestoneContext {
+            request,
+            project,
+            milestone_id: 0,
+            name: String::default(),
+            description: None,
+            target_date: None,
+            closed: false,
+            error_message: None,
+        },
+    ))
+}
+
+#[get("/milestone/<milestone_id>/edit")]
+pub async fn edit_milestone(
+    milestone_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let milestone = Milestone::load(milestone_id).await.map_to_failure()?;
+    let project = authorize(&request, milestone.project_id, &request.current_path).await?;
+
+    Ok(Template::render(
+        "edit_milestone",
+        EditMilestoneContext {
+            request,
+            project,
+            milestone_id,
+            name: milestone.name,
+            description: milestone.description,
+            target_date: milestone
+                .target_date
+                .map(|date| date.format(DATE_FORMAT).to_string()),
+            closed: milestone.closed_at.is_some(),
+            error_message: None,
+        },
+    ))
+}
+
+/// The format used by date inputs
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(FromForm, Clone, Debug)]
+pub struct EditMilestoneForm {
+    milestone_id: i64,
+    project_id: i64,
+    name: String,
+    description: Option<String>,
+    target_date: Option<String>,
+    closed: bool,
+}
+
+enum MilestoneUpdateError {
+    NameRequired,
+    NameConflict,
+    InvalidTargetDate,
+    Database(DatabaseError),
+}
+
+impl From<DatabaseError> for MilestoneUpdateError {
+    fn from(error: DatabaseError) -> Self {
+        match error {
+            DatabaseError::Conflict => Self::NameConflict,
+            other => Self::Database(other),
+        }
+    }
+}
+
+async fn update_milestone(form: &EditMilestoneForm) -> Result<Milestone, MilestoneUpdateError> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(MilestoneUpdateError::NameRequired);
+    }
+    let target_date = match form.target_date.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(date) => Some(
+            NaiveDate::parse_from_str(date, DATE_FORMAT)
+                .map_err(|_| MilestoneUpdateError::InvalidTargetDate)?,
+        ),
+    };
+
+    let mut milestone = if form.milestone_id == 0 {
+        Milestone::new(form.project_id, name.to_string())
+    } else {
+        let milestone = Milestone::load(form.milestone_id)
+            .await
+            .map_err(DatabaseError::from)?;
+        // A milestone's project can't be changed by editing it
+        if milestone.project_id != form.project_id {
+            return Err(MilestoneUpdateError::Database(DatabaseError::RowNotFound));
+        }
+        milestone
+    };
+    milestone.name = name.to_string();
+    milestone.description = form
+        .description
+        .clone()
+        .filter(|description| !description.is_empty());
+    milestone.target_date = target_date;
+    milestone.closed_at = match (form.closed, milestone.closed_at) {
+        (true, None) => Some(Utc::now()),
+        (true, closed_at) => closed_at,
+        (false, _) => None,
+    };
+    milestone.save(database::pool()).await?;
+
+    Ok(milestone)
+}
+
+#[post("/milestones/save", data = "<form>")]
+pub async fn save_milestone(
+    form: Form<EditMilestoneForm>,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let origin = if form.milestone_id == 0 {
+        format!("/project/{}/milestones/new", form.project_id)
+    } else {
+        format!("/milestone/{}/edit", form.milestone_id)
+    };
+    let project = authorize(&request, form.project_id, &origin).await?;
+
+    let error_message = match update_milestone(&form).await {
+        Ok(milestone) => return Err(Failure::redirect(format!("/milestone/{}", milestone.id))),
+        Err(MilestoneUpdateError::NameRequired) => "milestone-error-name-required",
+        Err(MilestoneUpdateError::NameConflict) => "milestone-error-name-conflict",
+        Err(MilestoneUpdateError::InvalidTargetDate) => "milestone-error-invalid-date",
+        Err(MilestoneUpdateError::Database(DatabaseError::RowNotFound)) => {
+            return Err(Failure::not_found())
+        }
+        Err(MilestoneUpdateError::Database(error)) => {
+            error!("error while saving milestone: {:?}", error);
+            "internal-error-saving"
+        }
+    };
+
+    Ok(Template::render(
+        "edit_milestone",
+        EditMilestoneContext {
+            request,
+            project,
+            milestone_id: form.milestone_id,
+            name: form.name.clone(),
+            description: form.description.clone(),
+            target_date: form.target_date.clone(),
+            closed: form.closed,
+            error_message: Some(String::from(error_message)),
+        },
+    ))
+}
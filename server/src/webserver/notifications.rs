@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use rocket::request::{Form, FormItems, FromForm};
+use rocket_contrib::templates::Template;
+use serde::{Deserialize, Serialize};
+
+use database::schema::notifications::{
+    Delivery, IssueEventKind, Notification, NotificationPreferences, NotificationView,
+};
+
+use crate::webserver::{
+    auth::SessionId, localization::UserLanguage, Failure, FullPathAndQuery, RequestData, ResultExt,
+};
+
+/// The inbox only shows recent notifications; older ones remain in the database
+const NOTIFICATIONS_PER_PAGE: i64 = 100;
+
+#[derive(Serialize, Deserialize)]
+struct NotificationsContext {
+    request: RequestData,
+    notifications: Vec<NotificationView>,
+    preferences: Vec<PreferenceRow>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PreferenceRow {
+    kind: String,
+    inbox: bool,
+    email: bool,
+}
+
+#[get("/notifications")]
+pub async fn list_notifications(
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let account_id = match &request.session {
+        Some(session) => session.account.id,
+        None => return Err(Failure::redirect_to_signin(Some("/notifications"))),
+    };
+
+    let (notifications, preferences) = futures::try_join!(
+        NotificationView::list_for(account_id, NOTIFICATIONS_PER_PAGE, database::pool()),
+        NotificationPreferences::load(account_id, database::pool()),
+    )
+    .map_to_failure()?;
+
+    let preferences = IssueEventKind::ALL
+        .iter()
+        .map(|kind| {
+            let delivery = preferences.delivery_for(*kind);
+            PreferenceRow {
+                kind: kind.as_str().to_string(),
+                inbox: delivery.inbox,
+                email: delivery.email,
+            }
+        })
+        .collect();
+
+    Ok(Template::render(
+        "notifications",
+        NotificationsContext {
+            request,
+            notifications,
+            preferences,
+        },
+    ))
+}
+
+#[get("/notification/<notification_id>/read")]
+pub async fn read_notification(
+    notification_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let account_id = match &request.session {
+        Some(session) => session.account.id,
+        None => return Err(Failure::redirect_to_signin(Some("/notifications"))),
+    };
+
+    let issue_id = Notification::mark_read(notification_id, account_id, database::pool())
+        .await
+        .map_to_failure()?;
+
+    Err(Failure::redirect(format!("/issue/{}", issue_id)))
+}
+
+#[get("/notifications/read-all")]
+pub async fn read_all_notifications(
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let account_id = match &request.session {
+        Some(session) => session.account.id,
+        None => return Err(Failure::redirect_to_signin(Some("/notifications"))),
+    };
+
+    Notification::mark_all_read(account_id, database::pool()).await?;
+
+    Err(Failure::redirect("/notifications"))
+}
+
+/// Checkboxes are named `<event kind>-inbox` and `<event kind>-email`. As with
+/// any checkbox, unchecked boxes aren't submitted.
+pub struct PreferencesForm {
+    deliveries: HashMap<IssueEventKind, Delivery>,
+}
+
+impl<'f> FromForm<'f> for PreferencesForm {
+    type Error = ();
+
+    fn from_form(items: &mut FormItems<'f>, _strict: bool) -> Result<Self, Self::Error> {
+        let mut deliveries = IssueEventKind::ALL
+            .iter()
+            .map(|kind| {
+                (
+                    *kind,
+                    Delivery {
+                        inbox: false,
+                        email: false,
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        for item in items {
+            let (key, _) = item.key_value_decoded();
+            let separator = match key.rfind('-') {
+                Some(separator) => separator,
+                None => continue,
+            };
+            let delivery = match key[..separator]
+                .parse::<IssueEventKind>()
+                .ok()
+                .and_then(|kind| deliveries.get_mut(&kind))
+            {
+                Some(delivery) => delivery,
+                None => continue,
+            };
+
+            match &key[separator + 1..] {
+                "inbox" => delivery.inbox = true,
+                "email" => delivery.email = true,
+                _ => {}
+            }
+        }
+
+        Ok(Self { deliveries })
+    }
+}
+
+#[post("/notifications/preferences", data = "<form>")]
+pub async fn save_notification_preferences(
+    form: Form<PreferencesForm>,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let account_id = match &request.session {
+        Some(session) => session.account.id,
+        None => return Err(Failure::redirect_to_signin(Some("/notifications"))),
+    };
+
+    let mut tx = database::pool().begin().await?;
+    for (kind, delivery) in &form.deliveries {
+        NotificationPreferences::set(account_id, *kind, *delivery, &mut tx).await?;
+    }
+    tx.commit().await?;
+
+    Err(Failure::redirect("/notifications"))
+}
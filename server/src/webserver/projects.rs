@@ -1,23 +1,29 @@
 use rocket::{http::Status, request::Form};
-use rocket_contrib::templates::Template;
+use rocket_contrib::{json::Json, templates::Template};
 use serde::{Deserialize, Serialize};
 
 use database::schema::issues::{
-    IssueQueryBuilder, IssueQueryResults, Project, ProjectError, Taxonomy,
+    IssueQueryBuilder, IssueQueryResults, Milestone, Project, ProjectError, ProjectWatcher,
+    Schedule, Taxonomy,
 };
 
 use crate::webserver::{
-    auth::SessionId, localization::UserLanguage, Failure, FullPathAndQuery, RequestData, ResultExt,
+    articles::MarkdownLinks, auth::SessionId, localization::UserLanguage, Failure,
+    FullPathAndQuery, RequestData, ResultExt,
 };
-use database::sqlx::types::chrono::Utc;
+use database::sqlx::{types::chrono::Utc, PgConnection};
 
 #[derive(Serialize, Deserialize)]
 struct ViewProjectContext {
     request: RequestData,
     project: Project,
     editable: bool,
+    watching: bool,
     response: IssueQueryResults,
     taxonomy: Taxonomy,
+    schedule: Schedule,
+    milestones: Vec<Milestone>,
+    links: MarkdownLinks,
 }
 
 #[get("/project/<project_id>")]
@@ -39,9 +45,39 @@ pub async fn view_project_by_slug(
     session: Option<SessionId>,
     path: FullPathAndQuery,
 ) -> Result<Template, Failure> {
-    let project = Project::find_by_slug(&slug).await?;
+    // The connection is released before rendering, which uses the pool
+    let found = {
+        let mut connection = database::pool().acquire().await?;
+        find_project_by_slug(&slug, &mut connection).await
+    };
+    match found {
+        Ok(ProjectSlug::Current(project)) => render_project(project, language, session, path).await,
+        Ok(ProjectSlug::Moved(permalink)) => Err(Failure::moved(permalink)),
+        Err(ProjectError::ProjectNotFound) => Err(Failure::not_found()),
+        Err(other) => Err(other.into()),
+    }
+}
 
-    render_project(project, language, session, path).await
+/// Where a project slug leads
+#[derive(Debug)]
+enum ProjectSlug {
+    Current(Project),
+    /// The project was renamed, and is now found at this permalink
+    Moved(String),
+}
+
+async fn find_project_by_slug(
+    slug: &str,
+    connection: &mut PgConnection,
+) -> Result<ProjectSlug, ProjectError> {
+    match Project::find_by_slug(slug, &mut *connection).await {
+        Ok(project) => Ok(ProjectSlug::Current(project)),
+        Err(ProjectError::ProjectNotFound) => {
+            let project = Project::find_by_former_slug(slug, &mut *connection).await?;
+            Ok(ProjectSlug::Moved(project.permalink()))
+        }
+        Err(other) => Err(other),
+    }
 }
 
 async fn render_project(
@@ -59,12 +95,21 @@ async fn render_project(
         .await
         .map_to_failure()?;
     let taxonomy = Taxonomy::load(database::pool()).await.map_to_failure()?;
+    let schedule = Schedule::for_project(project.id, Utc::now()).await?;
+    let milestones = Milestone::list_for_project(project.id).await?;
+    let links = MarkdownLinks::load(project.description.as_deref()).await?;
 
     let editable = request
         .session
         .as_ref()
         .map(|s| s.account.administrator || s.account.id == project.owner_id)
         .unwrap_or_default();
+    let watching = match &request.session {
+        Some(session) => {
+            ProjectWatcher::is_watching(project.id, session.account.id, database::pool()).await?
+        }
+        None => false,
+    };
 
     Ok(Template::render(
         "view_project",
@@ -72,12 +117,69 @@ async fn render_project(
             request,
             project,
             editable,
+            watching,
             response,
             taxonomy,
+            schedule,
+            milestones,
+            links,
         },
     ))
 }
 
+#[get("/project/<project_id>/schedule.json")]
+pub async fn project_schedule(project_id: i64) -> Result<Json<Schedule>, Failure> {
+    let project = Project::load(project_id).await.map_to_failure()?;
+    let schedule = Schedule::for_project(project.id, Utc::now()).await?;
+
+    Ok(Json(schedule))
+}
+
+#[get("/project/<project_id>/watch")]
+pub async fn watch_project(
+    project_id: i64,
+    language: UserLanguage,
+    session: Option<SessionId>,
+    path: FullPathAndQuery,
+) -> Result<(), Failure> {
+    set_watching_project(project_id, true, language, session, path).await
+}
+
+#[get("/project/<project_id>/unwatch")]
+pub async fn unwatch_project(
+    project_id: i64,
+    language: UserLanguage,
+    session: Option<SessionId>,
+    path: FullPathAndQuery,
+) -> Result<(), Failure> {
+    set_watching_project(project_id, false, language, session, path).await
+}
+
+async fn set_watching_project(
+    project_id: i64,
+    watching: bool,
+    language: UserLanguage,
+    session: Option<SessionId>,
+    path: FullPathAndQuery,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    if let Some(session) = &request.session {
+        let project = Project::load(project_id).await.map_to_failure()?;
+        if watching {
+            ProjectWatcher::watch(project.id, session.account.id, database::pool()).await?;
+        } else {
+            ProjectWatcher::unwatch(project.id, session.account.id, database::pool()).await?;
+        }
+
+        Err(Failure::redirect(project.permalink()))
+    } else {
+        Err(Failure::redirect_to_signin(Some(&format!(
+            "/project/{}",
+            project_id
+        ))))
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct EditProjectContext {
     request: RequestData,
@@ -127,7 +229,13 @@ pub async fn new_project(
                 "edit_project",
                 EditProjectContext {
                     request,
-                    project: Project::new(Default::default(), Default::default(), None, owner_id),
+                    project: Project::new(
+                        Default::default(),
+                        Default::default(),
+                        Default::default(),
+                        None,
+                        owner_id,
+                    ),
                     error_message: None,
                 },
             ))
@@ -143,6 +251,7 @@ pub async fn new_project(
 pub struct EditProjectForm {
     project_id: i64,
     slug: String,
+    key: String,
     name: String,
     description: Option<String>,
 }
@@ -167,6 +276,7 @@ async fn update_project(
     let mut project = if project_form.project_id == 0 {
         Project::new(
             project_form.slug.clone(),
+            project_form.key.clone(),
             project_form.name.clone(),
             description,
             current_user_id,
@@ -174,6 +284,7 @@ async fn update_project(
     } else {
         let mut project = Project::load_for_update(project_form.project_id, &mut tx).await?;
         project.slug = project_form.slug.clone();
+        project.key = project_form.key.clone();
         project.name = project_form.name.clone();
         project.description = description;
         project
@@ -203,6 +314,8 @@ pub async fn save_project(
                     let error_message = match error {
                         ProjectError::SlugInvalidCharacter(_) => "project-error-invalid-username",
                         ProjectError::SlugConflict => "project-error-slug-conflict",
+                        ProjectError::KeyInvalid(_) => "project-error-invalid-key",
+                        ProjectError::KeyConflict => "project-error-key-conflict",
                         ProjectError::ProjectNotFound => return Err(Failure::not_found()),
                         ProjectError::Sql(sql_error) => {
                             error!("sql error while saving project: {:?}", sql_error);
@@ -218,6 +331,7 @@ pub async fn save_project(
                             project: Project {
                                 id: project_form.project_id,
                                 slug: project_form.slug.clone(),
+                                key: project_form.key.clone(),
                                 name: project_form.name.clone(),
                                 description: project_form.description.clone(),
                                 owner_id: 0,
@@ -239,3 +353,40 @@ pub async fn save_project(
         Err(Failure::redirect_to_signin(Some(&origin)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use database::schema::issues::Project;
+
+    use database::test_helpers::{self, begin};
+
+    use super::{find_project_by_slug, ProjectSlug};
+
+    #[rocket::async_test]
+    #[ignore]
+    async fn former_slugs_redirect_to_the_permalink() -> anyhow::Result<()> {
+        let mut tx = begin().await?;
+        let account = test_helpers::setup_test_account(&mut tx).await?;
+        let mut project = Project::new(
+            String::from("redirect-before"),
+            String::from("REDIRECT"),
+            String::from("Redirect"),
+            None,
+            account.id,
+        );
+        project.save(&mut tx).await?;
+        project.slug = String::from("redirect-after");
+        project.save(&mut tx).await?;
+
+        match find_project_by_slug("redirect-before", &mut tx).await? {
+            ProjectSlug::Moved(permalink) => assert_eq!(permalink, "/project/redirect-after"),
+            other => panic!("expected a redirect, found {:?}", other),
+        }
+        match find_project_by_slug("redirect-after", &mut tx).await? {
+            ProjectSlug::Current(current) => assert_eq!(current.id, project.id),
+            other => panic!("expected the project, found {:?}", other),
+        }
+
+        Ok(())
+    }
+}
@@ -1,14 +1,27 @@
 use rocket::{
-    http::{ContentType, Status},
+    data::{Data, ToByteUnit},
+    http::{ContentType, Header, Status},
+    request::{FromRequest, Outcome},
     response::content::Content,
+    Request,
 };
-use rocket_contrib::templates::Template;
+use rocket_contrib::{json::Json, templates::Template};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use database::schema::accounts::{AccountError, User};
+use database::schema::{
+    accounts::{Account, AccountError, User, UserAvatar},
+    notifications::{DigestSchedule, DigestSettings},
+};
 
-use crate::webserver::{
-    auth::SessionId, localization::UserLanguage, Failure, FullPathAndQuery, RequestData, ResultExt,
+use crate::{
+    attachments::{self, StorageError},
+    avatars,
+    configuration::{AttachmentMaximumBytes, Configuration},
+    webserver::{
+        auth::SessionId, localization::UserLanguage, Failure, FullPathAndQuery, RequestData,
+        ResultExt,
+    },
 };
 use rocket::{request::Form, response::Redirect};
 
@@ -47,34 +60,187 @@ pub async fn view_user(
     ))
 }
 
+/// How many suggestions the editor's mention autocomplete shows
+const AUTOCOMPLETE_LIMIT: i64 = 8;
+
+#[get("/users/autocomplete?<q>")]
+pub async fn autocomplete_users(
+    q: String,
+    language: UserLanguage,
+    session: Option<SessionId>,
+    path: FullPathAndQuery,
+) -> Result<Json<Vec<User>>, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    if !request.logged_in() {
+        return Err(Failure::forbidden());
+    }
+
+    let users = User::search(q.trim(), AUTOCOMPLETE_LIMIT, database::pool()).await?;
+
+    Ok(Json(users))
+}
+
+#[derive(Debug)]
+pub struct IfNoneMatch(Option<String>);
+
+#[rocket::async_trait]
+impl<'a, 'r> FromRequest<'a, 'r> for IfNoneMatch {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfNoneMatch(
+            request
+                .headers()
+                .get_one("If-None-Match")
+                .map(|value| value.to_owned()),
+        ))
+    }
+}
+
+#[derive(Responder)]
+pub enum AvatarResponse {
+    #[response(status = 304)]
+    NotModified((), Header<'static>, Header<'static>),
+    Image(Content<Vec<u8>>, Header<'static>, Header<'static>),
+}
+
+fn avatar_cache_control() -> Header<'static> {
+    Header::new("Cache-Control", "public, max-age=300")
+}
+
 #[get("/user/<user_id>/avatar.jpg?<size>")]
-pub async fn user_avatar(user_id: i64, size: Option<usize>) -> Result<Content<Vec<u8>>, Status> {
+pub async fn user_avatar(
+    user_id: i64,
+    size: Option<u32>,
+    if_none_match: IfNoneMatch,
+) -> Result<AvatarResponse, Status> {
     let size = size.unwrap_or(64);
-    let user = User::load(user_id, database::pool())
+    if !avatars::SUPPORTED_SIZES.contains(&size) {
+        return Err(Status::new(500, "invalid avatar size requested"));
+    }
+
+    let avatar = UserAvatar::load(user_id, database::pool())
         .await
         .map_err(|_| Status::NotFound)?;
 
-    let identicon = identicon_rs::new(user.username).size(8).unwrap();
-    let identicon = match size {
-        512 => identicon.scale(384).unwrap().border(64), // + 32
-        256 => identicon.scale(192).unwrap().border(32), // 224 + 16
-        128 => identicon.scale(96).unwrap().border(16),  // 112 + 8
-        64 => identicon.scale(48).unwrap().border(8),    // 52 + 6
-        32 => identicon.scale(24).unwrap().border(4),    // 24 + 4
-        16 => identicon.scale(12).unwrap().border(2),    // 12 + 2
-        _ => return Err(Status::new(500, "invalid avatar size requested")),
-    };
-    let identicon = identicon.export_jpeg_data().unwrap();
-    Ok(Content(ContentType::JPEG, identicon))
+    let etag = avatars::etag(&avatar, size);
+    if if_none_match.0.as_deref() == Some(etag.as_str()) {
+        return Ok(AvatarResponse::NotModified(
+            (),
+            Header::new("ETag", etag),
+            avatar_cache_control(),
+        ));
+    }
+
+    let rendered = avatars::render(&avatar, size).await.map_err(|err| {
+        error!("error rendering avatar: {:?}", err);
+        Status::InternalServerError
+    })?;
+
+    Ok(AvatarResponse::Image(
+        Content(ContentType::JPEG, rendered.as_ref().clone()),
+        Header::new("ETag", etag),
+        avatar_cache_control(),
+    ))
+}
+
+fn can_edit_user(request: &RequestData, user_id: i64) -> bool {
+    request
+        .session
+        .as_ref()
+        .map(|s| s.account.administrator || s.account.id == user_id)
+        .unwrap_or_default()
+}
+
+#[post("/user/<user_id>/avatar", data = "<data>")]
+pub async fn upload_avatar(
+    user_id: i64,
+    data: Data,
+    language: UserLanguage,
+    session: Option<SessionId>,
+    path: FullPathAndQuery,
+) -> Result<Status, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    if !can_edit_user(&request, user_id) {
+        return Err(Failure::forbidden());
+    }
+
+    let maximum_bytes = AttachmentMaximumBytes::get().unwrap();
+    let source = data.open((maximum_bytes + 1).bytes()).into_bytes().await?;
+    if source.len() as u64 > maximum_bytes {
+        return Err(Failure::Status(Status::PayloadTooLarge));
+    }
+    // Decoding is CPU bound, so it happens off of the async executor
+    let (valid, source) =
+        tokio::task::spawn_blocking(move || (avatars::validate_upload(&source), source)).await?;
+    if !valid {
+        return Err(Failure::Status(Status::UnsupportedMediaType));
+    }
+
+    let key = format!("avatars/{}/{}", user_id, Uuid::new_v4());
+    attachments::storage()?
+        .store(&key, &attachments::sniff_content_type(&source), source)
+        .await?;
+
+    replace_avatar(user_id, Some(&key)).await?;
+
+    Ok(Status::NoContent)
+}
+
+#[post("/user/<user_id>/avatar/remove")]
+pub async fn remove_avatar(
+    user_id: i64,
+    language: UserLanguage,
+    session: Option<SessionId>,
+    path: FullPathAndQuery,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    if !can_edit_user(&request, user_id) {
+        return Err(Failure::forbidden());
+    }
+
+    replace_avatar(user_id, None).await?;
+
+    Err(Failure::redirect(format!("/user/{}/edit", user_id)))
+}
+
+async fn replace_avatar(user_id: i64, key: Option<&str>) -> Result<(), Failure> {
+    let previous_key = UserAvatar::replace(user_id, key, database::pool())
+        .await
+        .map_to_failure()?;
+
+    if let Some(previous_key) = previous_key {
+        let deleted = match attachments::storage() {
+            Ok(storage) => storage.delete(&previous_key).await,
+            Err(err) => Err(err),
+        };
+        match deleted {
+            Ok(_) | Err(StorageError::NotFound) => {}
+            Err(err) => error!("error removing previous avatar {}: {:?}", previous_key, err),
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(Serialize, Deserialize)]
 struct EditUserContext {
     request: RequestData,
     user: User,
+    email: Option<String>,
+    digest_schedule: String,
+    timezone: Option<String>,
+    timezones: Vec<String>,
     error_message: Option<String>,
 }
 
+fn timezone_names() -> Vec<String> {
+    chrono_tz::TZ_VARIANTS
+        .iter()
+        .map(|tz| tz.name().to_string())
+        .collect()
+}
+
 #[get("/user/<user_id>/edit")]
 pub async fn edit_user(
     user_id: i64,
@@ -88,11 +254,18 @@ pub async fn edit_user(
             .await
             .map_to_failure()?;
         if session.account.administrator || session.account.id == user_id {
+            let digest = DigestSettings::load(user_id, database::pool())
+                .await
+                .map_to_failure()?;
             Ok(Template::render(
                 "edit_user",
                 EditUserContext {
                     request,
                     user,
+                    email: digest.email,
+                    digest_schedule: digest.schedule.as_str().to_string(),
+                    timezone: digest.timezone,
+                    timezones: timezone_names(),
                     error_message: None,
                 },
             ))
@@ -112,9 +285,25 @@ pub struct EditUserForm {
     user_id: i64,
     username: String,
     displayname: Option<String>,
+    email: Option<String>,
+    digest_schedule: String,
+    timezone: Option<String>,
 }
 
 async fn update_user(user_form: &Form<EditUserForm>) -> Result<(), AccountError> {
+    let timezone = match user_form.timezone.as_deref().map(str::trim) {
+        Some(name) if !name.is_empty() => {
+            name.parse::<chrono_tz::Tz>()
+                .map_err(|_| AccountError::InvalidTimezone)?;
+            Some(name)
+        }
+        _ => None,
+    };
+    let digest_schedule = user_form
+        .digest_schedule
+        .parse()
+        .unwrap_or(DigestSchedule::None);
+
     let mut tx = database::pool().begin().await?;
     // It's a little wasteful to do a load here when as of writing this comment
     // User.save() is hard-coded to an update function. However, if for some reason
@@ -128,6 +317,8 @@ async fn update_user(user_form: &Form<EditUserForm>) -> Result<(), AccountError>
         .map(|name| if name.is_empty() { None } else { Some(name) })
         .flatten();
     user.save(&mut tx).await?;
+    Account::update_email(user.id, user_form.email.as_deref(), &mut tx).await?;
+    DigestSettings::update(user.id, digest_schedule, timezone, &mut tx).await?;
 
     tx.commit().await?;
 
@@ -157,6 +348,8 @@ pub async fn save_user(
                         "user-error-invalid-username"
                     }
                     AccountError::UsernameConflict => "user-error-username-conflict",
+                    AccountError::InvalidEmail => "user-error-invalid-email",
+                    AccountError::InvalidTimezone => "user-error-invalid-timezone",
                     AccountError::Sql(sql_error) => {
                         error!("sql error while saving user: {:?}", sql_error);
                         "internal-error-saving"
@@ -173,6 +366,10 @@ pub async fn save_user(
                             username: user_form.username.clone(),
                             display_name: user_form.displayname.clone(),
                         },
+                        email: user_form.email.clone(),
+                        digest_schedule: user_form.digest_schedule.clone(),
+                        timezone: user_form.timezone.clone(),
+                        timezones: timezone_names(),
                     },
                 ))
             }
@@ -0,0 +1,321 @@
+use rocket::request::{Form, FormItems, FromForm};
+use rocket_contrib::templates::Template;
+use serde::{Deserialize, Serialize};
+
+use database::schema::{
+    issues::Project,
+    webhooks::{Webhook, WebhookDelivery},
+};
+
+use crate::{
+    webhooks,
+    webserver::{
+        auth::{SessionData, SessionId},
+        localization::UserLanguage,
+        Failure, FullPathAndQuery, RequestData, ResultExt,
+    },
+};
+
+/// The delivery log only shows recent deliveries; older ones remain in the database
+const DELIVERIES_PER_PAGE: i64 = 50;
+
+#[derive(Serialize, Deserialize)]
+struct WebhooksContext {
+    request: RequestData,
+    project: Option<Project>,
+    webhooks: Vec<Webhook>,
+    event_kinds: Vec<String>,
+    error_message: Option<String>,
+    url: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WebhookContext {
+    request: RequestData,
+    project: Option<Project>,
+    webhook: Webhook,
+    deliveries: Vec<WebhookDelivery>,
+    event_kinds: Vec<String>,
+    error_message: Option<String>,
+}
+
+/// Site-wide webhooks may only be managed by administrators. Project webhooks
+/// may also be managed by the project's owner.
+fn can_manage_webhooks(session: &SessionData, project: Option<&Project>) -> bool {
+    session.account.administrator
+        || project
+            .map(|project| project.owner_id == session.account.id)
+            .unwrap_or_default()
+}
+
+async fn load_project(project_id: Option<i64>) -> Result<Option<Project>, Failure> {
+    match project_id {
+        Some(project_id) => Ok(Some(Project::load(project_id).await.map_to_failure()?)),
+        None => Ok(None),
+    }
+}
+
+fn event_kinds() -> Vec<String> {
+    webhooks::EVENT_KINDS
+        .iter()
+        .map(|kind| kind.as_str().to_string())
+        .collect()
+}
+
+fn webhooks_path(project_id: Option<i64>) -> String {
+    match project_id {
+        Some(project_id) => format!("/project/{}/webhooks", project_id),
+        None => String::from("/webhooks"),
+    }
+}
+
+#[get("/webhooks")]
+pub async fn list_site_webhooks(
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    render_webhooks(request, None, None, String::default()).await
+}
+
+#[get("/project/<project_id>/webhooks")]
+pub async fn list_project_webhooks(
+    project_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    render_webhooks(request, Some(project_id), None, String::default()).await
+}
+
+async fn render_webhooks(
+    request: RequestData,
+    project_id: Option<i64>,
+    error_message: Option<String>,
+    url: String,
+) -> Result<Template, Failure> {
+    let project = load_project(project_id).await?;
+    match &request.session {
+        Some(session) if can_manage_webhooks(session, project.as_ref()) => {}
+        Some(_) => return Err(Failure::forbidden()),
+        None => return Err(Failure::redirect_to_signin(Some(&request.current_path))),
+    }
+
+    let webhooks = Webhook::list_for_project(project_id, database::pool())
+        .await
+        .map_to_failure()?;
+
+    Ok(Template::render(
+        "webhooks",
+        WebhooksContext {
+            request,
+            project,
+            webhooks,
+            event_kinds: event_kinds(),
+            error_message,
+            url,
+        },
+    ))
+}
+
+#[get("/webhook/<webhook_id>")]
+pub async fn view_webhook(
+    webhook_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let webhook = Webhook::load(webhook_id, database::pool())
+        .await
+        .map_to_failure()?;
+    render_webhook(request, webhook, None).await
+}
+
+async fn render_webhook(
+    request: RequestData,
+    webhook: Webhook,
+    error_message: Option<String>,
+) -> Result<Template, Failure> {
+    let project = load_project(webhook.project_id).await?;
+    match &request.session {
+        Some(session) if can_manage_webhooks(session, project.as_ref()) => {}
+        Some(_) => return Err(Failure::forbidden()),
+        None => return Err(Failure::redirect_to_signin(Some(&request.current_path))),
+    }
+
+    let deliveries =
+        WebhookDelivery::list_recent(webhook.id, DELIVERIES_PER_PAGE, database::pool())
+            .await
+            .map_to_failure()?;
+
+    Ok(Template::render(
+        "view_webhook",
+        WebhookContext {
+            request,
+            project,
+            webhook,
+            deliveries,
+            event_kinds: event_kinds(),
+            error_message,
+        },
+    ))
+}
+
+/// Event checkboxes are named `event-<event kind>`. As with any checkbox,
+/// unchecked boxes aren't submitted.
+pub struct WebhookForm {
+    webhook_id: i64,
+    project_id: Option<i64>,
+    url: String,
+    secret: Option<String>,
+    active: bool,
+    events: Vec<String>,
+}
+
+impl<'f> FromForm<'f> for WebhookForm {
+    type Error = ();
+
+    fn from_form(items: &mut FormItems<'f>, _strict: bool) -> Result<Self, Self::Error> {
+        let mut form = Self {
+            webhook_id: 0,
+            project_id: None,
+            url: String::default(),
+            secret: None,
+            active: false,
+            events: Vec::new(),
+        };
+
+        for item in items {
+            let (key, value) = item.key_value_decoded();
+            match key.as_str() {
+                "webhook_id" => form.webhook_id = value.parse().map_err(|_| ())?,
+                "project_id" if value.is_empty() => {}
+                "project_id" => form.project_id = Some(value.parse().map_err(|_| ())?),
+                "url" => form.url = value.trim().to_string(),
+                "secret" if value.trim().is_empty() => {}
+                "secret" => form.secret = Some(value.trim().to_string()),
+                "active" => form.active = true,
+                other => {
+                    if let Some(kind) = other.strip_prefix("event-") {
+                        if webhooks::EVENT_KINDS.iter().any(|k| k.as_str() == kind) {
+                            form.events.push(kind.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(form)
+    }
+}
+
+/// Returns the localization key of the problem with `form`, if any
+fn validate(form: &WebhookForm) -> Option<&'static str> {
+    match reqwest::Url::parse(&form.url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+        _ => return Some("webhook-error-invalid-url"),
+    }
+
+    if form.events.is_empty() {
+        return Some("webhook-error-no-events");
+    }
+
+    None
+}
+
+#[post("/webhooks/save", data = "<form>")]
+pub async fn save_webhook(
+    form: Form<WebhookForm>,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let session = match &request.session {
+        Some(session) => session.clone(),
+        None => {
+            return Err(Failure::redirect_to_signin(Some(&webhooks_path(
+                form.project_id,
+            ))))
+        }
+    };
+
+    let mut webhook = if form.webhook_id == 0 {
+        Webhook::new(
+            form.project_id,
+            form.url.clone(),
+            form.secret
+                .clone()
+                .unwrap_or_else(webhooks::generate_secret),
+            form.events.clone(),
+            session.account.id,
+        )
+    } else {
+        let mut webhook = Webhook::load(form.webhook_id, database::pool())
+            .await
+            .map_to_failure()?;
+        webhook.url = form.url.clone();
+        if let Some(secret) = &form.secret {
+            webhook.secret = secret.clone();
+        }
+        webhook.events = form.events.clone();
+        webhook.active = form.active;
+        webhook
+    };
+
+    // The project always comes from the stored webhook when editing, so a
+    // project owner can't move a webhook to another scope
+    let project = load_project(webhook.project_id).await?;
+    if !can_manage_webhooks(&session, project.as_ref()) {
+        return Err(Failure::forbidden());
+    }
+
+    if let Some(error_message) = validate(&form) {
+        return if form.webhook_id == 0 {
+            render_webhooks(
+                request,
+                webhook.project_id,
+                Some(String::from(error_message)),
+                form.url.clone(),
+            )
+            .await
+        } else {
+            render_webhook(request, webhook, Some(String::from(error_message))).await
+        };
+    }
+
+    webhook.save(database::pool()).await?;
+
+    Err(Failure::redirect(format!("/webhook/{}", webhook.id)))
+}
+
+#[post("/webhook/<webhook_id>/delete")]
+pub async fn delete_webhook(
+    webhook_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let webhook = Webhook::load(webhook_id, database::pool())
+        .await
+        .map_to_failure()?;
+    let project = load_project(webhook.project_id).await?;
+    match &request.session {
+        Some(session) if can_manage_webhooks(session, project.as_ref()) => {}
+        Some(_) => return Err(Failure::forbidden()),
+        None => {
+            return Err(Failure::redirect_to_signin(Some(&format!(
+                "/webhook/{}",
+                webhook.id
+            ))))
+        }
+    }
+
+    Webhook::delete(webhook.id, database::pool()).await?;
+
+    Err(Failure::redirect(webhooks_path(webhook.project_id)))
+}
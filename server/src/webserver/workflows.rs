@@ -0,0 +1,336 @@
+use std::collections::HashSet;
+
+use rocket::{
+    http::Status,
+    request::{Form, FormItems, FromForm},
+};
+use rocket_contrib::templates::Template;
+use serde::{Deserialize, Serialize};
+
+use database::{
+    schema::issues::{Project, Workflow, WorkflowState},
+    DatabaseError,
+};
+
+use crate::webserver::{
+    auth::{SessionData, SessionId},
+    localization::UserLanguage,
+    Failure, FullPathAndQuery, RequestData, ResultExt,
+};
+
+#[derive(Serialize, Deserialize)]
+struct WorkflowContext {
+    request: RequestData,
+    project: Option<Project>,
+    workflow: Workflow,
+    /// Allowed transitions as `<from>-<to>`, matching the checkbox names
+    allowed_transitions: Vec<String>,
+    /// False when a project is shown the default workflow it inherits
+    customized: bool,
+    error_message: Option<String>,
+}
+
+/// The default workflow may only be managed by administrators. A project's
+/// workflow may also be managed by the project's owner.
+fn can_manage_workflow(session: &SessionData, project: Option<&Project>) -> bool {
+    session.account.administrator
+        || project
+            .map(|project| project.owner_id == session.account.id)
+            .unwrap_or_default()
+}
+
+async fn load_project(project_id: Option<i64>) -> Result<Option<Project>, Failure> {
+    match project_id {
+        Some(project_id) => Ok(Some(Project::load(project_id).await.map_to_failure()?)),
+        None => Ok(None),
+    }
+}
+
+fn workflow_path(project_id: Option<i64>) -> String {
+    match project_id {
+        Some(project_id) => format!("/project/{}/workflow", project_id),
+        None => String::from("/workflow"),
+    }
+}
+
+/// Loads the project and checks that the current account may change its workflow
+async fn authorize(
+    request: &RequestData,
+    project_id: Option<i64>,
+) -> Result<Option<Project>, Failure> {
+    let project = load_project(project_id).await?;
+    match &request.session {
+        Some(session) if can_manage_workflow(session, project.as_ref()) => Ok(project),
+        Some(_) => Err(Failure::forbidden()),
+        None => Err(Failure::redirect_to_signin(Some(&workflow_path(
+            project_id,
+        )))),
+    }
+}
+
+#[get("/workflow")]
+pub async fn view_default_workflow(
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    render_workflow(request, None, None).await
+}
+
+#[get("/project/<project_id>/workflow")]
+pub async fn view_project_workflow(
+    project_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    render_workflow(request, Some(project_id), None).await
+}
+
+async fn render_workflow(
+    request: RequestData,
+    project_id: Option<i64>,
+    error_message: Option<String>,
+) -> Result<Template, Failure> {
+    let project = authorize(&request, project_id).await?;
+    let mut workflow = Workflow::load(project_id).await.map_to_failure()?;
+    let customized = project_id.is_none() || !workflow.states.is_empty();
+    if !customized {
+        workflow = Workflow::load(None).await.map_to_failure()?;
+    }
+    let allowed_transitions = workflow
+        .transitions
+        .iter()
+        .flat_map(|(from, to)| to.iter().map(move |to| format!("{}-{}", from, to)))
+        .collect();
+
+    Ok(Template::render(
+        "workflow",
+        WorkflowContext {
+            request,
+            project,
+            workflow,
+            allowed_transitions,
+            customized,
+            error_message,
+        },
+    ))
+}
+
+#[post("/project/<project_id>/workflow/customize")]
+pub async fn customize_workflow(
+    project_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    authorize(&request, Some(project_id)).await?;
+
+    // Customizing twice would copy the default states again
+    let workflow = Workflow::load(Some(project_id)).await.map_to_failure()?;
+    if workflow.states.is_empty() {
+        let mut tx = database::pool().begin().await?;
+        Workflow::customize(project_id, &mut tx).await?;
+        tx.commit().await?;
+    }
+
+    Err(Failure::redirect(workflow_path(Some(project_id))))
+}
+
+#[post("/project/<project_id>/workflow/reset")]
+pub async fn reset_workflow(
+    project_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    authorize(&request, Some(project_id)).await?;
+
+    let mut tx = database::pool().begin().await?;
+    Workflow::reset_to_default(project_id, &mut tx).await?;
+    tx.commit().await?;
+
+    Err(Failure::redirect(workflow_path(Some(project_id))))
+}
+
+#[derive(FromForm, Clone, Debug)]
+pub struct WorkflowStateForm {
+    state_id: i64,
+    project_id: Option<i64>,
+    name: String,
+    position: i32,
+    initial: bool,
+    started: bool,
+    completes: bool,
+}
+
+#[post("/workflow/states/save", data = "<form>")]
+pub async fn save_workflow_state(
+    form: Form<WorkflowStateForm>,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let mut state = if form.state_id == 0 {
+        WorkflowState::new(form.project_id, form.name.trim().to_string(), form.position)
+    } else {
+        WorkflowState::load(form.state_id, database::pool())
+            .await
+            .map_to_failure()?
+    };
+
+    // The project always comes from the stored state when editing, so a
+    // project owner can't change the default workflow
+    authorize(&request, state.project_id).await?;
+    let workflow = Workflow::load(state.project_id).await.map_to_failure()?;
+    if state.project_id.is_some() && workflow.states.is_empty() {
+        return Err(Failure::redirect(workflow_path(state.project_id)));
+    }
+
+    if form.name.trim().is_empty() {
+        return render_workflow(
+            request,
+            state.project_id,
+            Some(String::from("workflow-error-empty-name")),
+        )
+        .await;
+    }
+
+    // Issues in the state would keep timestamps that disagree with it
+    if state.id != 0
+        && (state.started != form.started || state.completes != form.completes)
+        && WorkflowState::in_use(state.id, database::pool()).await?
+    {
+        return render_workflow(
+            request,
+            state.project_id,
+            Some(String::from("workflow-error-status-in-use")),
+        )
+        .await;
+    }
+
+    state.name = form.name.trim().to_string();
+    state.position = form.position;
+    state.initial = form.initial;
+    state.started = form.started;
+    state.completes = form.completes;
+
+    let mut tx = database::pool().begin().await?;
+    match state.save(&mut tx).await {
+        Ok(()) => {}
+        Err(DatabaseError::Conflict) => {
+            return render_workflow(
+                request,
+                state.project_id,
+                Some(String::from("workflow-error-duplicate-name")),
+            )
+            .await
+        }
+        Err(_) => return Err(Failure::Status(Status::InternalServerError)),
+    }
+
+    // There is only one state new issues start in
+    if state.initial {
+        for mut other in workflow.states {
+            if other.initial && other.id != state.id {
+                other.initial = false;
+                other.save(&mut tx).await?;
+            }
+        }
+    }
+    tx.commit().await?;
+
+    Err(Failure::redirect(workflow_path(state.project_id)))
+}
+
+#[post("/workflow/state/<state_id>/delete")]
+pub async fn delete_workflow_state(
+    state_id: i64,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<Template, Failure> {
+    let request = RequestData::new(language, path, session).await;
+    let state = WorkflowState::load(state_id, database::pool())
+        .await
+        .map_to_failure()?;
+    authorize(&request, state.project_id).await?;
+
+    if WorkflowState::delete_if_unused(state.id, database::pool()).await? {
+        Err(Failure::redirect(workflow_path(state.project_id)))
+    } else {
+        render_workflow(
+            request,
+            state.project_id,
+            Some(String::from("workflow-error-state-in-use")),
+        )
+        .await
+    }
+}
+
+/// Each allowed transition is a checkbox named `transition-<from>-<to>`. As
+/// with any checkbox, unchecked boxes aren't submitted.
+pub struct TransitionsForm {
+    project_id: Option<i64>,
+    allowed: HashSet<(i64, i64)>,
+}
+
+impl<'f> FromForm<'f> for TransitionsForm {
+    type Error = ();
+
+    fn from_form(items: &mut FormItems<'f>, _strict: bool) -> Result<Self, Self::Error> {
+        let mut form = Self {
+            project_id: None,
+            allowed: HashSet::new(),
+        };
+
+        for item in items {
+            let (key, value) = item.key_value_decoded();
+            match key.as_str() {
+                "project_id" if value.is_empty() => {}
+                "project_id" => form.project_id = Some(value.parse().map_err(|_| ())?),
+                other => {
+                    if let Some(pair) = other.strip_prefix("transition-") {
+                        let mut ids = pair.splitn(2, '-').map(str::parse::<i64>);
+                        if let (Some(Ok(from)), Some(Ok(to))) = (ids.next(), ids.next()) {
+                            form.allowed.insert((from, to));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(form)
+    }
+}
+
+#[post("/workflow/transitions/save", data = "<form>")]
+pub async fn save_workflow_transitions(
+    form: Form<TransitionsForm>,
+    language: UserLanguage,
+    path: FullPathAndQuery,
+    session: Option<SessionId>,
+) -> Result<(), Failure> {
+    let request = RequestData::new(language, path, session).await;
+    authorize(&request, form.project_id).await?;
+
+    // Only pairs of states in this workflow are touched, whatever was submitted
+    let workflow = Workflow::load(form.project_id).await.map_to_failure()?;
+    let mut tx = database::pool().begin().await?;
+    for from in &workflow.states {
+        for to in &workflow.states {
+            if from.id != to.id {
+                let allowed = form.allowed.contains(&(from.id, to.id));
+                WorkflowState::set_transition(from.id, to.id, allowed, &mut tx).await?;
+            }
+        }
+    }
+    tx.commit().await?;
+
+    Err(Failure::redirect(workflow_path(form.project_id)))
+}
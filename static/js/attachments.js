@@ -0,0 +1,35 @@
+function upload_attachment(file, issue_id, article_slug) {
+    let url = '/attachments/upload?filename=' + encodeURIComponent(file.name || 'image.png');
+    if (issue_id) {
+        url += '&issue_id=' + issue_id;
+    }
+    if (article_slug) {
+        url += '&article_slug=' + encodeURIComponent(article_slug);
+    }
+
+    return fetch(url, {
+        method: 'POST',
+        credentials: 'same-origin',
+        body: file,
+    }).then(function (response) {
+        if (!response.ok) {
+            throw new Error(response.status === 413 ? 'too-large' : 'upload-failed');
+        }
+        return response.json();
+    });
+}
+
+function upload_image(file) {
+    const url = '/attachments/images?filename=' + encodeURIComponent(file.name || 'image.png');
+
+    return fetch(url, {
+        method: 'POST',
+        credentials: 'same-origin',
+        body: file,
+    }).then(function (response) {
+        if (!response.ok) {
+            throw new Error(response.status === 413 ? 'too-large' : 'upload-failed');
+        }
+        return response.json();
+    });
+}
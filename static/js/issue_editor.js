@@ -18,6 +18,136 @@ function html_sanitizer(prefix) {
     };
 }
 
+function image_upload_hook(error_message) {
+    // Invoked by the editor when an image is pasted, dropped, or chosen from the toolbar.
+    // The callback inserts the markdown image link at the cursor.
+    return function (blob, callback) {
+        upload_image(blob)
+            .then(function (attachment) {
+                callback(attachment.url, attachment.filename);
+            })
+            .catch(function () {
+                alert(error_message);
+            });
+        return false;
+    };
+}
+
+function mention_autocomplete(editor) {
+    // Suggests users while typing an @mention in the markdown editor. Choosing a
+    // suggestion replaces the partially typed username.
+    const menu = document.createElement('div');
+    menu.className = 'dropdown-menu';
+    menu.style.position = 'absolute';
+    document.body.appendChild(menu);
+
+    const cm = editor.getCodeMirror();
+    let query = null;
+    let users = [];
+    let active = 0;
+    let request_counter = 0;
+
+    function hide() {
+        query = null;
+        menu.classList.remove('show');
+    }
+
+    function current_query() {
+        const cursor = cm.getCursor();
+        const before = cm.getRange({line: cursor.line, ch: 0}, cursor);
+        const match = /(^|[^A-Za-z0-9])@([A-Za-z0-9]*)$/.exec(before);
+        if (!match) {
+            return null;
+        }
+        return {
+            text: match[2],
+            from: {line: cursor.line, ch: cursor.ch - match[2].length},
+            to: cursor,
+        };
+    }
+
+    function choose(index) {
+        const user = users[index];
+        if (user && query) {
+            cm.replaceRange(user.username + ' ', query.from, query.to);
+        }
+        hide();
+        cm.focus();
+    }
+
+    function render() {
+        menu.innerHTML = '';
+        users.forEach(function (user, index) {
+            const item = document.createElement('button');
+            item.type = 'button';
+            item.className = 'dropdown-item' + (index === active ? ' active' : '');
+            item.textContent = user.display_name ? user.display_name + ' (@' + user.username + ')' : '@' + user.username;
+            item.addEventListener('mousedown', function (event) {
+                event.preventDefault();
+                choose(index);
+            });
+            menu.appendChild(item);
+        });
+
+        if (users.length === 0) {
+            menu.classList.remove('show');
+            return;
+        }
+
+        const coords = cm.cursorCoords(true, 'page');
+        menu.style.left = coords.left + 'px';
+        menu.style.top = coords.bottom + 'px';
+        menu.classList.add('show');
+    }
+
+    cm.on('cursorActivity', function () {
+        if (!editor.isMarkdownMode()) {
+            return hide();
+        }
+
+        query = current_query();
+        if (!query) {
+            return hide();
+        }
+
+        const request_id = ++request_counter;
+        fetch('/users/autocomplete?q=' + encodeURIComponent(query.text), {credentials: 'same-origin'})
+            .then(function (response) {
+                return response.ok ? response.json() : [];
+            })
+            .then(function (results) {
+                // Ignore responses that arrive after a newer request was made
+                if (request_id !== request_counter || !query) {
+                    return;
+                }
+                users = results;
+                active = 0;
+                render();
+            });
+    });
+
+    cm.on('keydown', function (cm, event) {
+        if (!menu.classList.contains('show')) {
+            return;
+        }
+
+        if (event.key === 'ArrowDown' || event.key === 'ArrowUp') {
+            const offset = event.key === 'ArrowDown' ? 1 : users.length - 1;
+            active = (active + offset) % users.length;
+            render();
+        } else if (event.key === 'Enter' || event.key === 'Tab') {
+            choose(active);
+        } else if (event.key === 'Escape') {
+            hide();
+        } else {
+            return;
+        }
+        event.preventDefault();
+    });
+
+    cm.on('blur', hide);
+}
+
 function sanitize_node(node, sanitize_self) {
     if (sanitize_self) {
         if (node.nodeType === Node.TEXT_NODE || !tagWhitelist[node.tagName]) {
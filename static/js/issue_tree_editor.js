@@ -0,0 +1,101 @@
+// Where a dragged issue would land relative to the label it's over: the top
+// and bottom quarters place it beside the issue, anything else inside it.
+function tree_drop_placement(label, event) {
+    const bounds = label.getBoundingClientRect();
+    const offset = (event.clientY - bounds.top) / bounds.height;
+    if (offset < 0.25) {
+        return 'before';
+    } else if (offset > 0.75) {
+        return 'after';
+    }
+    return 'inside';
+}
+
+function clear_tree_drop_markers(root) {
+    root.querySelectorAll('.drop-before, .drop-after, .drop-inside').forEach(function (label) {
+        label.classList.remove('drop-before', 'drop-after', 'drop-inside');
+    });
+}
+
+function move_issue(issue_id, parent_id, position) {
+    const url = '/issue/' + issue_id + '/move?parent_id=' + parent_id + '&position=' + position;
+
+    return fetch(url, {
+        method: 'POST',
+        credentials: 'same-origin',
+    }).then(function (response) {
+        if (!response.ok) {
+            throw new Error(response.status === 409 ? 'cycle' : 'move-failed');
+        }
+        return response.json();
+    });
+}
+
+function initialize_issue_tree_editor(root, error_alert) {
+    let dragged = null;
+
+    root.addEventListener('dragstart', function (event) {
+        dragged = event.target.closest('.issue-tree-item');
+        if (!dragged) {
+            return;
+        }
+        dragged.classList.add('dragging');
+        event.dataTransfer.effectAllowed = 'move';
+        event.dataTransfer.setData('text/plain', dragged.dataset.issueId);
+    });
+
+    root.addEventListener('dragend', function () {
+        if (dragged) {
+            dragged.classList.remove('dragging');
+        }
+        dragged = null;
+        clear_tree_drop_markers(root);
+    });
+
+    root.addEventListener('dragover', function (event) {
+        const label = event.target.closest('.issue-tree-label');
+        // An issue can't be dropped onto itself or anything beneath it
+        if (!dragged || !label || dragged.contains(label)) {
+            return;
+        }
+        event.preventDefault();
+        clear_tree_drop_markers(root);
+        label.classList.add('drop-' + tree_drop_placement(label, event));
+    });
+
+    root.addEventListener('drop', function (event) {
+        const label = event.target.closest('.issue-tree-label');
+        if (!dragged || !label || dragged.contains(label)) {
+            return;
+        }
+        event.preventDefault();
+        clear_tree_drop_markers(root);
+
+        const target = label.closest('.issue-tree-item');
+        const placement = tree_drop_placement(label, event);
+        let list;
+        let position;
+        if (placement === 'inside') {
+            list = target.querySelector(':scope > .issue-tree-list');
+            position = list.children.length;
+        } else {
+            list = target.parentElement;
+            // Positions are counted without the issue being moved
+            const siblings = Array.from(list.children).filter(function (item) {
+                return item !== dragged;
+            });
+            position = siblings.indexOf(target) + (placement === 'after' ? 1 : 0);
+        }
+
+        move_issue(dragged.dataset.issueId, list.dataset.parentId, position)
+            .then(function () {
+                window.location.reload();
+            })
+            .catch(function (error) {
+                error_alert.textContent = error.message === 'cycle'
+                    ? error_alert.dataset.cycleMessage
+                    : error_alert.dataset.message;
+                error_alert.classList.remove('d-none');
+            });
+    });
+}
@@ -12,5 +12,10 @@ internal-error-saving = An error occurred saving to the database. Please try aga
 view-profile = View Profile
 change-password = Change Password
 
+watch = Watch
+unwatch = Unwatch
+
 empty-value = &lt;none&gt;
-deleted-value = &lt;gone&gt;
\ No newline at end of file
+deleted-value = &lt;gone&gt;
+
+article-attachments = Attachments
@@ -0,0 +1,19 @@
+email-subject-single = [{$site}] {$issue}
+email-subject-multiple = [{$site}] {$count} watched issues changed
+email-event-created = {$actor} created {$issue}.
+email-event-updated = {$actor} updated {$issue}.
+email-event-commented = {$actor} commented on {$issue}:
+email-event-linked = {$actor} linked {$issue} to {$related}.
+email-event-unlinked = {$actor} unlinked {$issue} from {$related}.
+email-event-started = {$actor} started {$issue}.
+email-event-completed = {$actor} completed {$issue}.
+email-event-reopened = {$actor} reopened {$issue}.
+email-event-mentioned = {$actor} mentioned you in {$issue}.
+email-view-issue = View the issue: {$url}
+email-footer = You are receiving this email because you are watching these issues on {$site}. To stop receiving email, stop watching them or remove your email address from your profile: {$url}
+email-digest-subject-daily = [{$site}] Your daily digest
+email-digest-subject-weekly = [{$site}] Your weekly digest
+email-digest-new-issues = New issues in projects you watch:
+email-digest-changed-issues = Issues you watch that changed:
+email-digest-unblocked-issues = Issues you watch that are no longer blocked:
+email-digest-footer = You are receiving this digest because of your email preferences on {$site}. To change how often you receive it, edit your profile: {$url}
@@ -13,15 +13,25 @@ issue-add-relationship-button = Link Issue
 issue-revision-comment = Comment
 issue-project = Project
 issue-project-none = No Project
+issue-milestone = Milestone
+issue-milestone-none = No Milestone
+issue-iteration = Iteration
+issue-iteration-none = No Iteration
 issue-parent = Parent Issue
+issue-estimate = Estimate (hours)
+issue-rank = Position
 issue-related = Related Issues
 issue-relationship = Relationship
 issue-tags = Tags
+issue-attachments = Attachments
+issue-no-attachments = No attachments
 edit-issue = Edit #{$issue_id}
 save-issue = Save Issue
 view-issue = Issue #{$issue_id}
 issue-child-of = Belongs to #{$issue_id}
 issue-updated-summmary = Issue updated by {$user} at {$date}
+issue-updated-automatically = Carried over automatically at {$date}
+issue-commit-summary = Commit {$sha} by {$author} in {$repository} at {$date}
 issue-change-property = Property
 issue-change-old-value = Previous
 issue-change-new-value = Updated
@@ -30,11 +40,22 @@ issue-started = Started
 issue-completed = Completed
 issue-new = Ready
 issue-blocked = Blocked
+issue-workflow-state = State
+issue-resolution = Resolution
+issue-resolution-fixed = Fixed
+issue-resolution-duplicate = Duplicate
+issue-resolution-wont-fix = Won't Fix
+issue-resolution-cant-reproduce = Can't Reproduce
+issue-duplicate-of = Duplicate of Issue
 
 issues-error-already-updated = Another change has been made to the issue already. Please confirm your changes.
 issues-error-parent-not-found = Parent issue could not be found
+issues-error-parent-cycle = An issue can't be moved beneath itself or one of its own subtasks.
 issues-error-cant-close-child = This issue cannot be marked as completed because a child issue is open.
 issues-error-cant-close-blocked = This issue cannot be closed because it is blocked by another open issue.
+issues-error-transition-not-allowed = The issue's workflow does not allow moving it to this state.
+issues-error-duplicate-not-found = Enter the number of the issue this one duplicates.
+issues-error-milestone-not-in-project = Issues can only be planned into milestones of their own project.
 
 
 link-issue = Link Issue
@@ -48,7 +69,36 @@ issue-relationship-precedes-summary = Precedes
 issue-relationship-preceded-summary = Follows
 issue-relationship-causes-summary = Is source of
 issue-relationship-caused-summary = Caused by
+issue-relationship-duplicates-summary = Duplicates
+issue-relationship-duplicated-summary = Is duplicated by
+issue-relationship-split-from-summary = Split from
+issue-relationship-split-into-summary = Split into
 link-comment = Comment
+link-error-cycle = These issues would depend on each other in a loop, so none of them could ever be finished:
+
+issue-tree = All Subtasks
+issue-tree-rearrange-button = Rearrange
+issue-tree-editor = Rearrange Subtasks
+issue-tree-editor-tip = Drag an issue onto another to make it a subtask, or above or below it to change the order.
+issue-tree-editor-error-cycle = An issue can't be moved beneath itself or one of its own subtasks.
+issue-tree-editor-error = The issue could not be moved.
+issue-tree-progress = {$completed}/{$total} done
+issue-tree-blocked = {$count} blocked
+issue-tree-estimate = {$hours}h estimated, {$remaining}h remaining
+
+move-issue-project = Move to Project
+move-issue-project-subtasks = { $count ->
+    [0] Move subtasks too (this issue has none)
+    [one] Move its subtask too
+   *[other] Move all {$count} subtasks too
+}
+move-issue-project-keep-tags = Keep tags
+move-issue-project-help = Workflow states are changed to the matching state in the new project's workflow, and each issue gets a new number in the new project. Relationships with other issues and links using the old numbers keep working.
+
+issue-graph = Dependency Graph
+issue-graph-button = Dependency Graph
+issue-graph-blocked-by = This issue is waiting on these unfinished issues:
+issue-graph-not-blocked = Nothing unfinished is blocking this issue.
 
 issue-update-completed = marked this issue as completed
 issue-update-reopened = reopened this issue
@@ -56,6 +106,16 @@ issue-update-started = started work on this issue
 issue-update-stopped = cleared the started status
 issue-update-generic = changed {$property} from {$old} to {$new}
 issue-update-changed-project = moved projects from {$old} to {$new}
+issue-update-removed-tags = removed the tags {$tags}
+issue-update-changed-milestone = moved this issue from milestone {$old} to {$new}
+issue-update-changed-iteration = moved this issue from iteration {$old} to {$new}
+issue-update-changed-state = moved this issue from {$old} to {$new}
+issue-update-resolved = resolved this issue as {$resolution}
+issue-update-cleared-resolution = cleared the resolution
 issue-update-changed-description = updated the description
 
-issue-update-see-changes = See Changes
\ No newline at end of file
+issue-update-see-changes = See Changes
+
+attachment-upload-button = Upload
+attachment-delete = Delete
+attachment-error-upload = The file could not be uploaded. It may be larger than the maximum allowed size.
\ No newline at end of file
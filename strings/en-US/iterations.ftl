@@ -0,0 +1,39 @@
+iterations = Iterations
+iteration-new = New Iteration
+iteration-edit = Edit Iteration
+iteration-save = Save Iteration
+iteration-name = Name
+iteration-starts-on = Starts On
+iteration-ends-on = Ends On
+iteration-capacity = Capacity (hours)
+iteration-current = Current
+iteration-ended = Ended
+iteration-dates = {$starts_on} to {$ends_on}
+iteration-none = No iterations have been scheduled yet.
+iteration-no-issues = No issues have been planned into this iteration.
+
+iteration-report-issues = Issues
+iteration-report-hours = Hours
+iteration-report-planned = Planned
+iteration-report-completed = Completed
+iteration-report-carried-over = Carried Over
+iteration-report-status = Status
+iteration-report-percent-of-capacity = {$percent}% of capacity
+iteration-report-unestimated = { $count ->
+    [one] One planned issue has no estimate and isn't counted in the hours.
+   *[other] {$count} planned issues have no estimate and aren't counted in the hours.
+}
+iteration-report-started-before = { $count ->
+    [one] One completed issue was started before the iteration began.
+   *[other] {$count} completed issues were started before the iteration began.
+}
+
+iteration-status-completed = Completed
+iteration-status-in-progress = In Progress
+iteration-status-not-started = Not Started
+iteration-status-carried-over = Carried Over
+
+iteration-error-name-required = Iterations must have a name.
+iteration-error-invalid-date = Iterations need a valid start and end date.
+iteration-error-ends-before-start = Iterations can't end before they start.
+iteration-error-invalid-capacity = The capacity can't be negative.
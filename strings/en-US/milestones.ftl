@@ -0,0 +1,19 @@
+milestones = Milestones
+milestone-new = New Milestone
+milestone-edit = Edit Milestone
+milestone-save = Save Milestone
+milestone-name = Name
+milestone-description = Description
+milestone-target-date = Target Date
+milestone-closed = Closed
+milestone-progress = {$completed} of {$total} issues completed
+milestone-open = Remaining Issues
+milestone-blocked = Blocked Issues
+milestone-release-notes = Release Notes
+milestone-release-notes-other = Other Changes
+milestone-release-notes-empty = No issues have been completed yet.
+milestone-none = This project has no milestones yet.
+
+milestone-error-name-required = Milestones must have a name.
+milestone-error-name-conflict = Another milestone in this project already has that name.
+milestone-error-invalid-date = The target date isn't a valid date.
@@ -0,0 +1,26 @@
+notifications = Notifications
+notifications-empty = You don't have any notifications. Watch issues or projects to be notified when they change.
+notifications-mark-all-read = Mark All Read
+notification-created = {$actor} created {$issue}
+notification-updated = {$actor} updated {$issue}
+notification-commented = {$actor} commented on {$issue}
+notification-linked = {$actor} linked {$issue} to {$related}
+notification-unlinked = {$actor} unlinked {$issue} from {$related}
+notification-started = {$actor} started {$issue}
+notification-completed = {$actor} completed {$issue}
+notification-reopened = {$actor} reopened {$issue}
+notification-preferences = Notification Preferences
+notification-preferences-event = Event
+notification-preferences-inbox = Inbox
+notification-preferences-email = Email
+notification-preferences-save = Save Preferences
+notification-kind-created = Issue created
+notification-kind-updated = Issue details changed
+notification-kind-commented = New comments
+notification-kind-linked = Issues linked
+notification-kind-unlinked = Issues unlinked
+notification-kind-started = Issue started
+notification-kind-completed = Issue completed
+notification-kind-reopened = Issue reopened
+notification-mentioned = {$actor} mentioned you in {$issue}
+notification-kind-mentioned = Mentions of you
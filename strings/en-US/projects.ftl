@@ -2,5 +2,24 @@ edit-project = Edit Project
 new-project = New Project
 save-project = Save Project
 project-slug = Slug
+project-key = Key
+project-key-help = Issues in this project are numbered with this key, such as GOOEY-42. Up to 10 letters and numbers, starting with a letter.
 project-name = Name
-project-description = Description
\ No newline at end of file
+project-description = Description
+project-error-invalid-key = Keys must start with a letter and contain only letters and numbers, up to 10 characters.
+project-error-key-conflict = Another project already uses that key.
+
+project-schedule = Schedule
+project-schedule-json = JSON
+project-schedule-forecast = All open issues are forecast to be complete by
+project-schedule-critical-path = Critical Path
+project-schedule-remaining = Hours Remaining
+project-schedule-finish = Forecast Finish
+project-schedule-unestimated = { $count ->
+    [one] One issue has no estimate, so it's assumed to take the typical {$hours} hours (marked *).
+   *[other] {$count} issues have no estimate, so they're assumed to take the typical {$hours} hours (marked *).
+}
+project-schedule-unestimated-no-history = { $count ->
+    [one] One issue has no estimate and no issues have been completed yet, so it isn't counted (marked *).
+   *[other] {$count} issues have no estimate and no issues have been completed yet, so they aren't counted (marked *).
+}
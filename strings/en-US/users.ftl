@@ -4,4 +4,18 @@ user-display-name = Display Name
 save-user = Save User
 user-username-format-tip = Usernames are unique, must be at least 3 character slong, and can only contain the letters A through Z and numbers 0-9.
 user-error-invalid-username = The username provided is invalid.
-user-error-username-conflict = The username provided is already taken.
\ No newline at end of file
+user-error-username-conflict = The username provided is already taken.
+user-avatar = Avatar
+user-avatar-upload = Upload Avatar
+user-avatar-remove = Remove Avatar
+user-avatar-tip = Images are cropped to a square. Without an avatar, a generated pattern is shown instead.
+user-avatar-error = The avatar could not be uploaded. Make sure it is a PNG, JPEG, GIF, WebP or BMP image.user-email = Email Address
+user-email-tip = Notifications about issues you watch are sent to this address. Leave it blank to stop receiving email.
+user-error-invalid-email = The email address provided is invalid.
+user-digest-schedule = Email Digest
+user-digest-schedule-none = Don't send a digest
+user-digest-schedule-daily = Daily, each morning
+user-digest-schedule-weekly = Weekly, on Monday mornings
+user-timezone = Time Zone
+user-timezone-tip = Digests are sent at 8:00 AM in this time zone. Leave it blank to use the site's time zone.
+user-error-invalid-timezone = The time zone provided isn't recognized.
@@ -0,0 +1,27 @@
+webhook = Webhook
+webhooks = Webhooks
+webhooks-site = Site-wide Webhooks
+webhooks-site-tip = Site-wide webhooks receive events for every issue.
+webhooks-project = Webhooks for {$project}
+webhooks-project-tip = Project webhooks receive events for issues in this project.
+webhooks-empty = No webhooks have been registered.
+webhook-inactive = Inactive
+webhook-add = Add Webhook
+webhook-save = Save Webhook
+webhook-delete = Delete Webhook
+webhook-url = Payload URL
+webhook-secret = Secret
+webhook-secret-tip = Used to sign each payload with HMAC-SHA256, sent in the X-Khonsubase-Signature header. Leave blank to generate one.
+webhook-secret-change-tip = Leave blank to keep the current secret.
+webhook-events = Events
+webhook-active = Active
+webhook-deliveries = Recent Deliveries
+webhook-deliveries-empty = Nothing has been sent to this webhook yet.
+webhook-delivery-created = Queued
+webhook-delivery-event = Event
+webhook-delivery-status = Response
+webhook-delivery-attempts = Attempts
+webhook-delivery-pending = Pending
+webhook-delivery-failed = Failed
+webhook-error-invalid-url = The payload URL must be an http or https URL.
+webhook-error-no-events = Choose at least one event to send.
@@ -0,0 +1,22 @@
+workflow = Workflow
+workflow-default = Default Workflow
+workflow-project = Workflow for {$project}
+workflow-inherited-tip = This project uses the default workflow. Customize it to give the project its own states and transitions.
+workflow-customize = Customize Workflow
+workflow-reset = Use Default Workflow
+workflow-states = States
+workflow-state-name = Name
+workflow-state-position = Position
+workflow-state-initial = New Issues
+workflow-state-started = In Progress
+workflow-state-completes = Counts as Complete
+workflow-state-add = Add State
+workflow-state-save = Save
+workflow-state-delete = Delete
+workflow-transitions = Transitions
+workflow-transitions-tip = Each row lists the states an issue can be moved to from that state.
+workflow-transitions-save = Save Transitions
+workflow-error-empty-name = States must have a name.
+workflow-error-duplicate-name = Another state in this workflow already has that name.
+workflow-error-state-in-use = This state can't be deleted while issues are in it.
+workflow-error-status-in-use = Whether a state counts as in progress or complete can't be changed while issues are in it.